@@ -0,0 +1,69 @@
+//! The architecture functions for a userspace process on the host.
+//!
+//! Every thread is a hart, the interrupt state is only a flag of the thread
+//! and the timer counts from the start of the process at `CLOCK_FREQ`.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+use config::CLOCK_FREQ;
+
+static NEXT_HART: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HART_ID: usize = NEXT_HART.fetch_add(1, Ordering::Relaxed);
+    static INTERRUPT_ENABLE: Cell<bool> = const { Cell::new(true) };
+}
+
+fn start() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+    *START.get_or_init(Instant::now)
+}
+
+/// Return the id of the current thread, the ids start from 0.
+#[inline(always)]
+pub fn hart_id() -> usize {
+    HART_ID.with(|id| *id)
+}
+
+pub fn is_interrupt_enable() -> bool {
+    INTERRUPT_ENABLE.with(|enable| enable.get())
+}
+
+pub fn interrupt_disable() {
+    INTERRUPT_ENABLE.with(|enable| enable.set(false));
+}
+
+pub fn interrupt_enable() {
+    INTERRUPT_ENABLE.with(|enable| enable.set(true));
+}
+
+pub fn external_interrupt_enable() {}
+
+pub fn software_interrupt_enable() {}
+
+pub fn external_interrupt_disable() {}
+
+pub fn timer_interrupt_enable() {}
+
+/// Read the timer, it ticks at `CLOCK_FREQ` like the timer of the board.
+pub fn read_timer() -> usize {
+    let ns = start().elapsed().as_nanos();
+    (ns * CLOCK_FREQ as u128 / 1_000_000_000) as usize
+}
+
+pub fn read_cycle() -> usize {
+    start().elapsed().as_nanos() as usize
+}
+
+pub fn activate_paging_mode(_root_ppn: usize) {}
+
+pub fn sfence_vma_all() {}
+
+pub fn allow_access_user_memory() {}
@@ -1,7 +1,11 @@
-#![no_std]
+#![cfg_attr(not(feature = "hosted"), no_std)]
 
-#[cfg(feature = "rv")]
+#[cfg(feature = "hosted")]
+mod hosted;
+#[cfg(all(feature = "rv", not(feature = "hosted")))]
 mod riscv;
 
-#[cfg(feature = "rv")]
+#[cfg(feature = "hosted")]
+pub use hosted::*;
+#[cfg(all(feature = "rv", not(feature = "hosted")))]
 pub use riscv::*;
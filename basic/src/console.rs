@@ -5,13 +5,7 @@ use ksync::Mutex;
 macro_rules! print {
     ($($arg:tt)*) => {
         let domain_id = shared_heap::domain_id();
-        let mut id: usize;
-        unsafe {
-            core::arch::asm!(
-            "mv {},tp", out(reg)id,
-            );
-        }
-        id = id as u32 as usize;
+        let id = $crate::arch::hart_id();
         $crate::console::__print(format_args!("[{}][Domain:{}] {}", id,domain_id, format_args!($($arg)*)))
     };
 }
@@ -1,5 +1,6 @@
 #![feature(downcast_unchecked)]
-#![no_std]
+#![cfg_attr(not(target_os = "none"), feature(backtrace_frames))]
+#![cfg_attr(target_os = "none", no_std)]
 #[macro_use]
 pub mod console;
 pub mod arch;
@@ -12,6 +13,7 @@ pub mod sync;
 #[cfg(feature = "task")]
 pub mod task;
 pub mod time;
+pub mod tls;
 pub mod vm;
 
 extern crate alloc;
@@ -20,14 +22,17 @@ use alloc::{boxed::Box, format, sync::Arc};
 
 use corelib::domain_info::DomainInfo;
 pub use corelib::{
-    add_one_task, backtrace, blk_crash_trick, checkout_shared_data, constants, create_domain,
-    current_tid, exit_now, get_domain, get_task_priority, is_task_exit, kernel_satp,
-    register_domain, reload_domain, remove_task, set_task_priority, trap_from_user, trap_to_user,
-    update_domain, vaddr_to_paddr_in_kernel, wait_now, wake_up_wait_task, write_console, yield_now,
-    AlienError, AlienResult, CoreFunction,
+    backtrace, blk_crash_trick, checkout_shared_data, constants, create_domain, current_tid,
+    domain_info::RestartPolicy, event, get_domain, get_task_priority, is_task_exit, kernel_satp,
+    poll_domain_event, register_domain, register_domain_with_policy, reload_domain,
+    set_restart_policy, set_task_priority, subscribe_domain_event, symbolize, trap_from_user,
+    trap_to_user, unsubscribe_domain_event, update_domain, vaddr_to_paddr_in_kernel, wait_now,
+    wake_up_wait_task, write_console, yield_now, AlienError, AlienResult, CoreFunction,
 };
 pub use domain_main::domain_main;
 use ksync::Mutex;
+pub use manifest;
+pub use tls::{add_one_task, exit_now, remove_task};
 
 pub type DomainInfoSet = Mutex<DomainInfo>;
 
@@ -37,15 +42,80 @@ pub fn domain_info() -> Arc<DomainInfoSet> {
 }
 
 pub fn catch_unwind<F: FnOnce() -> AlienResult<R>, R>(f: F) -> AlienResult<R> {
-    let res = unwinding::panic::catch_unwind(f).unwrap_or_else(|_| {
+    #[cfg(target_os = "none")]
+    let res = unwinding::panic::catch_unwind(f);
+    #[cfg(not(target_os = "none"))]
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    res.unwrap_or_else(|_| {
         println_color!(31, "catch unwind error");
         Err(AlienError::DOMAINCRASH)
-    });
-    res
+    })
 }
 
 pub fn unwind_from_panic() {
+    #[cfg(target_os = "none")]
     unwinding::panic::begin_panic(Box::new(()));
+    #[cfg(not(target_os = "none"))]
+    std::panic::resume_unwind(Box::new(()));
+}
+
+/// Print the frames of the current domain with the function names.
+///
+/// The frames are found by the unwinder and the names are resolved by the
+/// kernel. Return the number of frames.
+#[cfg(target_os = "none")]
+pub fn domain_backtrace() -> usize {
+    use core::ffi::c_void;
+
+    use unwinding::abi::{UnwindContext, UnwindReasonCode, _Unwind_Backtrace, _Unwind_GetIP};
+
+    extern "C" fn trace(ctx: &UnwindContext<'_>, arg: *mut c_void) -> UnwindReasonCode {
+        let depth = unsafe { &mut *(arg as *mut usize) };
+        let ip = _Unwind_GetIP(ctx);
+        let mut buf = [0u8; 128];
+        match symbolize(ip, &mut buf) {
+            Ok(name) => println_color!(31, "  #{} {:#x} {}", depth, ip, name),
+            Err(_) => println_color!(31, "  #{} {:#x}", depth, ip),
+        }
+        *depth += 1;
+        UnwindReasonCode::NO_REASON
+    }
+
+    let mut depth = 0usize;
+    _Unwind_Backtrace(trace, &mut depth as *mut usize as *mut c_void);
+    depth
+}
+
+/// Print the frames of the current thread with the symbols of the host.
+#[cfg(not(target_os = "none"))]
+pub fn domain_backtrace() -> usize {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    println_color!(31, "{}", backtrace);
+    backtrace.frames().len()
+}
+
+/// Run the constructors of the domain in `.init_array`.
+///
+/// The loader only finds them. The `main` of the domain calls it once
+/// `corelib::init` and `shared_heap::init` are done, a constructor may
+/// allocate. Later calls do nothing.
+pub fn run_init_array() {
+    static DONE: spin::Once = spin::Once::new();
+    DONE.call_once(|| {
+        let functions = match corelib::init_array(shared_heap::domain_id()) {
+            Some(functions) => functions,
+            None => return,
+        };
+        for slot in functions.step_by(core::mem::size_of::<usize>()) {
+            let f = unsafe { *(slot as *const usize) };
+            // 0 and -1 are placeholders of the linker
+            if f == 0 || f == usize::MAX {
+                continue;
+            }
+            let f = unsafe { core::mem::transmute::<usize, extern "C" fn()>(f) };
+            f();
+        }
+    });
 }
 
 use getrandom::Error;
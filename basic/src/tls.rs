@@ -0,0 +1,118 @@
+//! Thread local storage of the tasks of a domain.
+//!
+//! `tp` holds the id of the current task, so a domain can not use the
+//! local-exec TLS model. A domain with `#[thread_local]` variables must be
+//! built with `-Z tls-model=global-dynamic`, every access goes through
+//! [`__tls_get_addr`] which finds the block of the current task.
+//!
+//! A block is created from the `PT_TLS` template of the domain when a task
+//! first touches a thread local, [`add_one_task`] creates it before the task
+//! runs. [`exit_now`] and [`remove_task`] free it when the task exits, `basic`
+//! exports them instead of the functions of `corelib`.
+
+use alloc::{
+    alloc::{alloc, dealloc, Layout},
+    collections::BTreeMap,
+};
+
+use corelib::{AlienError, AlienResult};
+use spin::{Mutex, Once};
+use task_meta::{TaskMeta, TlsTemplate};
+
+/// `__tls_get_addr` adds it to the offsets the loader writes, see the
+/// RISC-V ELF psABI.
+const TLS_DTV_OFFSET: usize = 0x800;
+
+/// The argument of `__tls_get_addr`.
+#[repr(C)]
+pub struct TlsIndex {
+    module: usize,
+    offset: usize,
+}
+
+static TEMPLATE: Once<Option<TlsTemplate>> = Once::new();
+/// The blocks of the tasks, keyed by the task id.
+static BLOCKS: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+fn template() -> Option<TlsTemplate> {
+    *TEMPLATE.call_once(|| corelib::tls_template(shared_heap::domain_id()))
+}
+
+fn layout(template: &TlsTemplate) -> Layout {
+    // the loader refuses a domain whose template has no valid layout
+    Layout::from_size_align(template.mem_size.max(1), template.align).unwrap()
+}
+
+/// Return the block of the task, create it if the task has none.
+fn block(tid: usize) -> AlienResult<usize> {
+    let template = template().ok_or(AlienError::EINVAL)?;
+    let mut blocks = BLOCKS.lock();
+    if let Some(block) = blocks.get(&tid) {
+        return Ok(*block);
+    }
+    let block = unsafe { alloc(layout(&template)) };
+    if block.is_null() {
+        return Err(AlienError::ENOMEM);
+    }
+    unsafe { template.init_block(block) };
+    blocks.insert(tid, block as usize);
+    Ok(block as usize)
+}
+
+/// Create the thread local block of the task before it runs.
+///
+/// It does nothing if the domain has no thread locals.
+pub fn prepare(tid: usize) -> AlienResult<()> {
+    if template().is_none() {
+        return Ok(());
+    }
+    block(tid).map(|_| ())
+}
+
+/// Free the thread local block of the task.
+pub fn release(tid: usize) {
+    let block = BLOCKS.lock().remove(&tid);
+    if let (Some(block), Some(template)) = (block, template()) {
+        unsafe { dealloc(block as *mut u8, layout(&template)) };
+    }
+}
+
+/// [`corelib::add_one_task`] with the thread local block of the task.
+pub fn add_one_task(task_meta: TaskMeta) -> AlienResult<usize> {
+    let tid = task_meta.task_basic_info.tid;
+    prepare(tid)?;
+    corelib::add_one_task(task_meta).inspect_err(|_| release(tid))
+}
+
+/// [`corelib::exit_now`], the thread local block of the current task is
+/// freed first, the task must not touch its thread locals after it.
+pub fn exit_now() -> AlienResult<()> {
+    if let Some(tid) = corelib::current_tid()? {
+        release(tid);
+    }
+    corelib::exit_now()
+}
+
+/// [`corelib::remove_task`] with the thread local block of the task.
+pub fn remove_task(tid: usize) -> AlienResult<()> {
+    release(tid);
+    corelib::remove_task(tid)
+}
+
+/// Return the address of a thread local variable of the current task.
+///
+/// # Safety
+///
+/// It is called by the code the compiler generates for thread locals.
+#[no_mangle]
+pub unsafe extern "C" fn __tls_get_addr(index: *const TlsIndex) -> *mut u8 {
+    // the block of another task must never be handed out
+    let tid = match corelib::current_tid() {
+        Ok(Some(tid)) => tid,
+        _ => panic!("thread local accessed without a current task"),
+    };
+    let block = block(tid).expect("no thread local storage for the task");
+    let index = &*index;
+    debug_assert_eq!(index.module, 1);
+    (block + index.offset.wrapping_add(TLS_DTV_OFFSET)) as *mut u8
+}
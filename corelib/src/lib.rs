@@ -2,19 +2,19 @@
 extern crate alloc;
 
 use alloc::sync::Arc;
-use core::any::Any;
+use core::{any::Any, ops::Range};
 
 #[cfg(feature = "core_impl")]
 pub use core_impl::*;
+pub use domain_meta::{domain_info, event, AlienError, AlienResult};
 use interface::{DomainType, DomainTypeRaw};
-use pconst::LinuxErrno;
 use spin::Once;
-use task_meta::{OperationResult, TaskOperation};
+use task_meta::{OperationResult, TaskOperation, TlsTemplate};
 
-pub mod domain_info;
-
-pub type AlienError = LinuxErrno;
-pub type AlienResult<T> = Result<T, LinuxErrno>;
+use crate::{
+    domain_info::RestartPolicy,
+    event::{DomainEvent, EventDelivery, EventFilter},
+};
 
 pub mod constants {
     pub use pconst::*;
@@ -78,21 +78,56 @@ pub trait CoreFunction: Send + Sync {
     fn sys_alloc_pages(&self, domain_id: u64, n: usize) -> *mut u8;
     fn sys_free_pages(&self, domain_id: u64, p: *mut u8, n: usize);
     fn sys_write_console(&self, s: &str);
+    /// Print the backtrace of the domain.
+    ///
+    /// The addresses in a domain should be printed with the function names
+    /// from `loader::symbolize`.
     fn sys_backtrace(&self, domain_id: u64);
+    /// Write the function which contains `addr` to `buf` as
+    /// `[domain] function+offset` and return the length.
+    ///
+    /// Return `ENOENT` if the address is not in the code of a domain, the
+    /// name is truncated if `buf` is too small. It can be implemented with
+    /// `loader::symbolize_into`.
+    fn sys_symbolize(&self, addr: usize, buf: &mut [u8]) -> AlienResult<usize>;
+    /// Return the thread local storage template of the domain, it can be
+    /// implemented with `DomainLoader::tls_template`.
+    fn sys_tls_template(&self, domain_id: u64) -> Option<TlsTemplate>;
+    /// Return the address range of the constructors of the domain, it can be
+    /// implemented with `DomainLoader::init_array`.
+    fn sys_init_array(&self, domain_id: u64) -> Option<Range<usize>>;
     fn sys_trampoline_addr(&self) -> usize;
     fn sys_kernel_satp(&self) -> usize;
     fn sys_trap_from_user(&self) -> usize;
     fn sys_trap_to_user(&self) -> usize;
     /// This func will be deleted
     fn blk_crash_trick(&self) -> bool;
-    fn sys_get_domain(&self, name: &str) -> Option<DomainType>;
+    /// Return the domain with the name to the domain `domain_id`.
+    ///
+    /// The kernel records that `domain_id` depends on the domain, see
+    /// `domain_manager::graph`.
+    fn sys_get_domain(&self, domain_id: u64, name: &str) -> Option<DomainType>;
     fn sys_create_domain(
         &self,
         domain_file_name: &str,
         identifier: &mut [u8],
     ) -> AlienResult<DomainType>;
     /// Register a new domain with the given name and type
-    fn sys_register_domain(&self, ident: &str, ty: DomainTypeRaw, data: &[u8]) -> AlienResult<()>;
+    ///
+    /// The image is checked against the verify policy of the loader when the
+    /// domain is created or updated, an untrusted image is refused there.
+    /// The type in the manifest of the image must be `ty`, the kernel checks
+    /// it with `loader::verify_registration` and returns `EINVAL` otherwise.
+    fn sys_register_domain(
+        &self,
+        ident: &str,
+        ty: DomainTypeRaw,
+        data: &[u8],
+        policy: RestartPolicy,
+    ) -> AlienResult<()>;
+    /// Change the restart policy of the domains with the name, it can be
+    /// implemented with `domain_manager::restart`.
+    fn sys_set_restart_policy(&self, name: &str, policy: RestartPolicy) -> AlienResult<()>;
     /// Replace the old domain with the new domain
     fn sys_update_domain(
         &self,
@@ -101,6 +136,20 @@ pub trait CoreFunction: Send + Sync {
         ty: DomainTypeRaw,
     ) -> AlienResult<()>;
     fn sys_reload_domain(&self, domain_name: &str) -> AlienResult<()>;
+    /// Subscribe the domain to the lifecycle events of the domains `filter`
+    /// matches and return the id of the subscription.
+    ///
+    /// The subscriptions of a domain are removed when it is unloaded. It can
+    /// be implemented with `domain_manager::event`.
+    fn sys_subscribe_domain_event(
+        &self,
+        domain_id: u64,
+        filter: EventFilter<'_>,
+        delivery: EventDelivery,
+    ) -> AlienResult<u64>;
+    fn sys_unsubscribe_domain_event(&self, subscription: u64) -> AlienResult<()>;
+    /// Take the oldest event of a subscription with [`EventDelivery::Queue`].
+    fn sys_poll_domain_event(&self, subscription: u64) -> AlienResult<Option<DomainEvent>>;
     fn vaddr_to_paddr_in_kernel(&self, vaddr: usize) -> AlienResult<usize>;
     fn task_op(&self, op: TaskOperation) -> AlienResult<OperationResult>;
     fn checkout_shared_data(&self) -> AlienResult<()>;
@@ -111,21 +160,27 @@ pub trait CoreFunction: Send + Sync {
 #[cfg(feature = "core_impl")]
 mod core_impl {
     use alloc::sync::Arc;
-    use core::any::Any;
+    use core::{any::Any, ops::Range};
 
     use interface::{DomainType, DomainTypeRaw};
     use spin::Once;
-    use task_meta::{TaskMeta, TaskOperation};
+    use task_meta::{TaskMeta, TaskOperation, TlsTemplate};
 
     use super::{AlienError, AlienResult, OnceGet};
-    use crate::CoreFunction;
+    use crate::{
+        domain_info::RestartPolicy,
+        event::{DomainEvent, EventDelivery, EventFilter},
+        CoreFunction,
+    };
 
     static CORE_FUNC: Once<&'static dyn CoreFunction> = Once::new();
 
+    #[cfg(not(feature = "hosted"))]
     extern "C" {
         fn sbss();
         fn ebss();
     }
+    #[cfg(not(feature = "hosted"))]
     fn clear_bss() {
         unsafe {
             core::slice::from_raw_parts_mut(
@@ -137,6 +192,9 @@ mod core_impl {
     }
 
     pub fn init(syscall: &'static dyn CoreFunction) {
+        // A hosted domain is a part of the process, the loader of the host
+        // has cleared its bss.
+        #[cfg(not(feature = "hosted"))]
         clear_bss();
         CORE_FUNC.call_once(|| syscall);
     }
@@ -157,6 +215,25 @@ mod core_impl {
         CORE_FUNC.get_must().sys_backtrace(domain_id);
     }
 
+    /// Return the function which contains `addr`, the name is written to `buf`.
+    pub fn symbolize(addr: usize, buf: &mut [u8]) -> AlienResult<&str> {
+        let len = CORE_FUNC.get_must().sys_symbolize(addr, buf)?;
+        let name = buf.get(..len).ok_or(AlienError::EINVAL)?;
+        // the name may be truncated in the middle of a character
+        match core::str::from_utf8(name) {
+            Ok(name) => Ok(name),
+            Err(e) => Ok(unsafe { core::str::from_utf8_unchecked(&name[..e.valid_up_to()]) }),
+        }
+    }
+
+    pub fn tls_template(domain_id: u64) -> Option<TlsTemplate> {
+        CORE_FUNC.get_must().sys_tls_template(domain_id)
+    }
+
+    pub fn init_array(domain_id: u64) -> Option<Range<usize>> {
+        CORE_FUNC.get_must().sys_init_array(domain_id)
+    }
+
     pub fn trampoline_addr() -> usize {
         static TRAMPOLINE_ADDR: Once<usize> = Once::new();
 
@@ -191,7 +268,9 @@ mod core_impl {
     }
 
     pub fn get_domain(name: &str) -> Option<DomainType> {
-        CORE_FUNC.get_must().sys_get_domain(name)
+        CORE_FUNC
+            .get_must()
+            .sys_get_domain(shared_heap::domain_id(), name)
     }
 
     pub fn create_domain(
@@ -206,8 +285,24 @@ mod core_impl {
             .sys_create_domain(domain_file_name, domain_identifier)
     }
 
+    /// Register a domain with [`RestartPolicy::Always`].
     pub fn register_domain(ident: &str, ty: DomainTypeRaw, data: &[u8]) -> AlienResult<()> {
-        CORE_FUNC.get_must().sys_register_domain(ident, ty, data)
+        register_domain_with_policy(ident, ty, data, RestartPolicy::default())
+    }
+
+    pub fn register_domain_with_policy(
+        ident: &str,
+        ty: DomainTypeRaw,
+        data: &[u8],
+        policy: RestartPolicy,
+    ) -> AlienResult<()> {
+        CORE_FUNC
+            .get_must()
+            .sys_register_domain(ident, ty, data, policy)
+    }
+
+    pub fn set_restart_policy(name: &str, policy: RestartPolicy) -> AlienResult<()> {
+        CORE_FUNC.get_must().sys_set_restart_policy(name, policy)
     }
 
     pub fn update_domain(
@@ -223,10 +318,30 @@ mod core_impl {
     pub fn reload_domain(domain_name: &str) -> AlienResult<()> {
         CORE_FUNC.get_must().sys_reload_domain(domain_name)
     }
+
+    /// Subscribe the domain to lifecycle events, see [`crate::event`].
+    pub fn subscribe_domain_event(
+        domain_id: u64,
+        filter: EventFilter<'_>,
+        delivery: EventDelivery,
+    ) -> AlienResult<u64> {
+        CORE_FUNC
+            .get_must()
+            .sys_subscribe_domain_event(domain_id, filter, delivery)
+    }
+    pub fn unsubscribe_domain_event(subscription: u64) -> AlienResult<()> {
+        CORE_FUNC
+            .get_must()
+            .sys_unsubscribe_domain_event(subscription)
+    }
+    pub fn poll_domain_event(subscription: u64) -> AlienResult<Option<DomainEvent>> {
+        CORE_FUNC.get_must().sys_poll_domain_event(subscription)
+    }
     pub fn vaddr_to_paddr_in_kernel(vaddr: usize) -> AlienResult<usize> {
         CORE_FUNC.get_must().vaddr_to_paddr_in_kernel(vaddr)
     }
 
+    #[cfg(not(feature = "hosted"))]
     #[inline(always)]
     fn current_tid_from_tp() -> Option<usize> {
         let mut tp: usize;
@@ -245,11 +360,18 @@ mod core_impl {
     }
     #[inline(always)]
     pub fn current_tid() -> AlienResult<Option<usize>> {
-        // CORE_FUNC
-        //     .get_must()
-        //     .task_op(TaskOperation::Current)
-        //     .map(|res| res.current_tid())
-        Ok(current_tid_from_tp())
+        #[cfg(not(feature = "hosted"))]
+        {
+            Ok(current_tid_from_tp())
+        }
+        // The host owns `tp`, ask the backend instead.
+        #[cfg(feature = "hosted")]
+        {
+            CORE_FUNC
+                .get_must()
+                .task_op(TaskOperation::Current)
+                .map(|res| res.current_tid())
+        }
     }
     /// return kstack top
     pub fn add_one_task(task_meta: TaskMeta) -> AlienResult<usize> {
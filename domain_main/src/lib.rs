@@ -1,29 +1,49 @@
+mod manifest_impl;
+
 use proc_macro2::TokenStream;
 use quote::quote;
 
+/// The entry of a domain.
+///
+/// With `ty = ...` the domain gets a manifest, e.g.
+/// `#[domain_main(ty = FsDomain, deps = ["vfs"], mmio = [(0x1000_0000, 0x1000)], irq = [10])]`.
+///
+/// The allocator, the panic handler and the exported symbol are only
+/// emitted for `target_os = "none"`, on the host the domain is a library
+/// of the process which the `hosted` crate runs.
 #[proc_macro_attribute]
 pub fn domain_main(
-    _attr: proc_macro::TokenStream,
+    attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
     let item = TokenStream::from(item);
     let panic = panic_impl();
+    let manifest = match manifest_impl::manifest_impl(TokenStream::from(attr)) {
+        Ok(manifest) => manifest,
+        Err(e) => return e.to_compile_error().into(),
+    };
     quote! (
+        #[cfg(target_os = "none")]
         #[global_allocator]
         static HEAP_ALLOCATOR: malloc::HeapAllocator =  malloc::HeapAllocator::new(corelib::alloc_raw_pages);
-        #[no_mangle]
+        #[cfg_attr(target_os = "none", no_mangle)]
         #item
         #panic
+        #manifest
     )
     .into()
 }
 
 fn panic_impl() -> TokenStream {
     quote!(
+        #[cfg(target_os = "none")]
         #[panic_handler]
         fn panic(info: &PanicInfo) -> ! {
             basic::println_color!(31, "{:?}", info);
-            basic::backtrace(domain_id());
+            // fall back to the raw backtrace of the kernel if the unwinder finds no frame
+            if basic::domain_backtrace() == 0 {
+                basic::backtrace(domain_id());
+            }
             #[cfg(feature = "rust-unwind")]
             {
                 basic::unwind_from_panic();
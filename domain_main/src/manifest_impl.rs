@@ -0,0 +1,107 @@
+use manifest::{encode_body, MmioRange, MANIFEST_SECTION};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::Parser, punctuated::Punctuated, spanned::Spanned, Error, Expr, ExprArray, Ident, Lit,
+    MetaNameValue, Result, Token,
+};
+
+fn array(expr: &Expr) -> Result<&ExprArray> {
+    match expr {
+        Expr::Array(array) => Ok(array),
+        _ => Err(Error::new(expr.span(), "expected an array")),
+    }
+}
+
+fn lit_int<T: core::str::FromStr>(expr: &Expr) -> Result<T>
+where
+    T::Err: core::fmt::Display,
+{
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse(),
+            _ => Err(Error::new(expr.span(), "expected an integer")),
+        },
+        _ => Err(Error::new(expr.span(), "expected an integer")),
+    }
+}
+
+fn lit_str(expr: &Expr) -> Result<String> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(s) => Ok(s.value()),
+            _ => Err(Error::new(expr.span(), "expected a string")),
+        },
+        _ => Err(Error::new(expr.span(), "expected a string")),
+    }
+}
+
+fn mmio_range(expr: &Expr) -> Result<MmioRange> {
+    match expr {
+        Expr::Tuple(tuple) if tuple.elems.len() == 2 => Ok(MmioRange {
+            start: lit_int(&tuple.elems[0])?,
+            size: lit_int(&tuple.elems[1])?,
+        }),
+        _ => Err(Error::new(expr.span(), "expected (start, size)")),
+    }
+}
+
+/// Emit the manifest static of the domain.
+///
+/// Nothing is emitted if the domain type is not given.
+pub fn manifest_impl(attr: TokenStream) -> Result<TokenStream> {
+    let args = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse2(attr)?;
+    let mut ty: Option<Ident> = None;
+    let mut deps = vec![];
+    let mut mmio = vec![];
+    let mut irqs = vec![];
+    for arg in args.iter() {
+        let name = arg
+            .path
+            .get_ident()
+            .ok_or_else(|| Error::new(arg.path.span(), "expected a name"))?;
+        match name.to_string().as_str() {
+            "ty" => match &arg.value {
+                Expr::Path(path) if path.path.get_ident().is_some() => {
+                    ty = path.path.get_ident().cloned();
+                }
+                v => return Err(Error::new(v.span(), "expected a domain type")),
+            },
+            "deps" => {
+                for v in array(&arg.value)?.elems.iter() {
+                    deps.push(lit_str(v)?);
+                }
+            }
+            "mmio" => {
+                for v in array(&arg.value)?.elems.iter() {
+                    mmio.push(mmio_range(v)?);
+                }
+            }
+            "irq" => {
+                for v in array(&arg.value)?.elems.iter() {
+                    irqs.push(lit_int::<u32>(v)?);
+                }
+            }
+            _ => return Err(Error::new(name.span(), "unknown manifest field")),
+        }
+    }
+    let ty = match ty {
+        Some(ty) => ty,
+        None if args.is_empty() => return Ok(quote!()),
+        None => return Err(Error::new(args.span(), "the domain type is required")),
+    };
+    let deps = deps.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+    let body = encode_body(&deps, &mmio, &irqs);
+    let len = body.len();
+    Ok(quote!(
+        #[used]
+        #[link_section = #MANIFEST_SECTION]
+        static DOMAIN_MANIFEST: basic::manifest::RawManifest<#len> = basic::manifest::RawManifest::new(
+            interface::DomainTypeRaw::#ty as u8,
+            interface::INTERFACE_VERSION,
+            interface::abi_fingerprint(interface::DomainTypeRaw::#ty),
+            env!("CARGO_PKG_VERSION"),
+            [#(#body),*],
+        );
+    ))
+}
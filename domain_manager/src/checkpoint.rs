@@ -0,0 +1,284 @@
+//! Checkpoint and restore of the state of a domain.
+//!
+//! The image holds the values of the domain storage and a raw copy of the
+//! shared heap objects of the domain. Values are saved by their [`Checkpoint`]
+//! implementation and can be restored into a new domain, even after a reboot.
+//! Shared heap objects contain pointers, so they are only kept for post-mortem
+//! analysis. Writing the image to a file or a block device is up to the caller.
+//!
+//! Image layout, all integers are little endian:
+//! ```text
+//! magic: [u8; 8]
+//! entry count: u32
+//!     key: str, type name: str, type hash: u64, version: u32, saved: u8, data: bytes
+//! shared object count: u32
+//!     size: u64, align: u64, data: bytes
+//! ```
+//! `str` and `bytes` are a `u32` length followed by the data.
+//!
+//! [`Checkpoint`]: storage::Checkpoint
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{Display, Formatter};
+
+use storage::{ArcValueType, ImageWriter, SaveFn, ValueInfo};
+
+use crate::{
+    resource::DOMAIN_RESOURCE,
+    sheap::for_each_domain_shared_data,
+    storage_heap::{get_domain_database, RestoredRecord},
+};
+
+const IMAGE_MAGIC: &[u8; 8] = b"DOMSTAT2";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// The domain has no database.
+    NoDatabase,
+    /// The domain is unloaded or failed, the save functions of its values
+    /// are gone with its code.
+    NotRunning,
+    /// The image does not start with the magic.
+    BadMagic,
+    /// The image ends in the middle of a record.
+    Truncated,
+    /// A key or a type name is not valid utf-8.
+    InvalidString,
+    /// A record is longer than a `u32` length can describe.
+    TooLarge,
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CheckpointError::NoDatabase => write!(f, "domain has no database"),
+            CheckpointError::NotRunning => write!(f, "domain is not running"),
+            CheckpointError::BadMagic => write!(f, "not a checkpoint image"),
+            CheckpointError::Truncated => write!(f, "checkpoint image is truncated"),
+            CheckpointError::InvalidString => write!(f, "invalid string in checkpoint image"),
+            CheckpointError::TooLarge => write!(f, "record is too large for a checkpoint image"),
+        }
+    }
+}
+
+/// A value of the domain storage in the image.
+#[derive(Debug, Clone)]
+pub struct ImageEntry {
+    pub key: String,
+    pub type_name: String,
+    /// The hash of the type, the restored value must have the same one.
+    pub type_hash: u64,
+    pub version: u32,
+    /// False if the value has no save function, the data is empty then.
+    pub saved: bool,
+    pub data: Vec<u8>,
+}
+
+/// A raw copy of a shared heap object in the image.
+#[derive(Debug, Clone)]
+pub struct SharedObject {
+    pub size: u64,
+    pub align: u64,
+    pub data: Vec<u8>,
+}
+
+/// A parsed checkpoint image.
+#[derive(Debug, Clone, Default)]
+pub struct CheckpointImage {
+    pub entries: Vec<ImageEntry>,
+    pub shared: Vec<SharedObject>,
+}
+
+struct VecWriter<'a>(&'a mut Vec<u8>);
+
+impl ImageWriter for VecWriter<'_> {
+    fn write(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+    }
+}
+
+/// Write a length or a count, they are `u32` in the image.
+fn put_len(buf: &mut Vec<u8>, len: usize) -> Result<(), CheckpointError> {
+    let len = u32::try_from(len).map_err(|_| CheckpointError::TooLarge)?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) -> Result<(), CheckpointError> {
+    put_len(buf, data.len())?;
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+fn put_entry(
+    buf: &mut Vec<u8>,
+    key: &str,
+    info: ValueInfo<'_>,
+    save: Option<SaveFn>,
+    value: &ArcValueType,
+) -> Result<(), CheckpointError> {
+    put_bytes(buf, key.as_bytes())?;
+    put_bytes(buf, info.type_name.as_bytes())?;
+    buf.extend_from_slice(&info.type_hash.to_le_bytes());
+    buf.extend_from_slice(&info.version.to_le_bytes());
+    buf.push(save.is_some() as u8);
+    let mut data = Vec::new();
+    if let Some(save) = save {
+        save(value, &mut VecWriter(&mut data));
+    }
+    put_bytes(buf, &data)
+}
+
+/// Save the storage and the shared heap objects of the domain to an image.
+///
+/// The domain should not run while the image is taken, the save functions
+/// are called on a copy of the storage entries and the shared heap is copied
+/// as is. A domain which is unloaded or failed is refused, and it must not be
+/// unloaded while the image is taken.
+pub fn checkpoint_domain(domain_id: u64) -> Result<Vec<u8>, CheckpointError> {
+    if DOMAIN_RESOURCE.lock().is_freed(domain_id) {
+        return Err(CheckpointError::NotRunning);
+    }
+    let data_map = get_domain_database(domain_id).ok_or(CheckpointError::NoDatabase)?;
+    let mut image = Vec::new();
+    image.extend_from_slice(IMAGE_MAGIC);
+
+    let mut entries = Vec::new();
+    let mut count = 0;
+    let mut res = Ok(());
+    data_map.save(&mut |key, info, save, value| {
+        if res.is_ok() {
+            res = put_entry(&mut entries, key, info, save, value);
+            count += 1;
+        }
+    });
+    res?;
+    put_len(&mut image, count)?;
+    image.extend_from_slice(&entries);
+
+    let mut objects = Vec::new();
+    let mut count = 0;
+    let mut res = Ok(());
+    for_each_domain_shared_data(domain_id, |allocation| {
+        if res.is_err() {
+            return;
+        }
+        let layout = allocation.layout;
+        objects.extend_from_slice(&(layout.size() as u64).to_le_bytes());
+        objects.extend_from_slice(&(layout.align() as u64).to_le_bytes());
+        // SAFETY: the allocation is alive while the shared heap is locked
+        let raw = unsafe { core::slice::from_raw_parts(allocation.value_pointer, layout.size()) };
+        res = put_bytes(&mut objects, raw);
+        count += 1;
+    });
+    res?;
+    put_len(&mut image, count)?;
+    image.extend_from_slice(&objects);
+    log::info!(
+        "checkpoint domain {}: {} bytes, {} shared objects",
+        domain_id,
+        image.len(),
+        count
+    );
+    Ok(image)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.data.len() < len {
+            return Err(CheckpointError::Truncated);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, CheckpointError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, CheckpointError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, CheckpointError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], CheckpointError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn str(&mut self) -> Result<&'a str, CheckpointError> {
+        core::str::from_utf8(self.bytes()?).map_err(|_| CheckpointError::InvalidString)
+    }
+}
+
+/// Parse a checkpoint image, e.g. for post-mortem analysis.
+pub fn parse_checkpoint(image: &[u8]) -> Result<CheckpointImage, CheckpointError> {
+    let mut reader = Reader { data: image };
+    if reader.take(IMAGE_MAGIC.len()).ok() != Some(IMAGE_MAGIC.as_slice()) {
+        return Err(CheckpointError::BadMagic);
+    }
+    let mut res = CheckpointImage::default();
+    for _ in 0..reader.u32()? {
+        let key = reader.str()?.to_string();
+        let type_name = reader.str()?.to_string();
+        let type_hash = reader.u64()?;
+        let version = reader.u32()?;
+        let saved = reader.u8()? != 0;
+        let data = reader.bytes()?.to_vec();
+        res.entries.push(ImageEntry {
+            key,
+            type_name,
+            type_hash,
+            version,
+            saved,
+            data,
+        });
+    }
+    for _ in 0..reader.u32()? {
+        let size = reader.u64()?;
+        let align = reader.u64()?;
+        let data = reader.bytes()?.to_vec();
+        res.shared.push(SharedObject { size, align, data });
+    }
+    Ok(res)
+}
+
+/// Give the saved values of an image to the domain.
+///
+/// The values are not inserted until the domain claims them with
+/// `storage::restore`, because only the domain can create them. Return the
+/// number of values which can be restored.
+pub fn restore_domain(domain_id: u64, image: &[u8]) -> Result<usize, CheckpointError> {
+    let data_map = get_domain_database(domain_id).ok_or(CheckpointError::NoDatabase)?;
+    let image = parse_checkpoint(image)?;
+    let records = image
+        .entries
+        .into_iter()
+        .filter(|entry| entry.saved)
+        .map(|entry| {
+            let record = RestoredRecord {
+                type_hash: entry.type_hash,
+                type_name: entry.type_name,
+                version: entry.version,
+                data: entry.data,
+            };
+            (entry.key, record)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let count = records.len();
+    data_map.set_restored(records);
+    log::info!("restore domain {}: {} values", domain_id, count);
+    Ok(count)
+}
@@ -0,0 +1,283 @@
+//! The subscriptions to the lifecycle events of domains.
+//!
+//! The kernel calls [`publish`] when a domain is registered, created,
+//! panics, is reloaded, updated or unloaded, and implements the event
+//! functions of `CoreFunction` with [`subscribe`], [`unsubscribe`] and
+//! [`poll`]. The callbacks point into the code of the subscriber, so the
+//! subscriptions of a domain are removed by `free_domain_resource` before its
+//! code is freed, and moved with [`move_domain_subscriptions`] when the domain
+//! is updated. [`publish`] calls the callbacks without the lock, so
+//! `free_domain_resource` also waits for the callbacks of the domain which
+//! are running with [`wait_domain_callbacks`]. A panic of a callback is
+//! caught with the function set by [`init_catch_unwind`].
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{Display, Formatter},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use domain_meta::{
+    event::{DomainEvent, DomainEventKind, EventDelivery, EventFilter},
+    AlienError, DomainTypeRaw,
+};
+use spin::{Mutex, Once};
+
+/// The number of events a queue keeps, the oldest are dropped first.
+pub const EVENT_QUEUE_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventError {
+    /// No subscription has the id.
+    NoSubscription,
+    /// The subscription delivers the events with a callback.
+    NotQueue,
+}
+
+impl Display for EventError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EventError::NoSubscription => write!(f, "no such subscription"),
+            EventError::NotQueue => write!(f, "subscription has no queue"),
+        }
+    }
+}
+
+impl From<EventError> for AlienError {
+    fn from(e: EventError) -> Self {
+        match e {
+            EventError::NoSubscription => AlienError::ENOENT,
+            EventError::NotQueue => AlienError::EINVAL,
+        }
+    }
+}
+
+enum Filter {
+    Name(String),
+    Type(DomainTypeRaw),
+    All,
+}
+
+impl Filter {
+    fn matches(&self, event: &DomainEvent) -> bool {
+        match self {
+            Filter::Name(name) => name == event.name(),
+            Filter::Type(ty) => *ty == event.ty,
+            Filter::All => true,
+        }
+    }
+}
+
+enum Delivery {
+    Callback(fn(&DomainEvent)),
+    Queue {
+        events: VecDeque<DomainEvent>,
+        dropped: usize,
+    },
+}
+
+struct Subscription {
+    /// The domain which subscribes.
+    domain_id: u64,
+    filter: Filter,
+    delivery: Delivery,
+}
+
+static SUBSCRIPTIONS: Mutex<BTreeMap<u64, Subscription>> = Mutex::new(BTreeMap::new());
+static NEXT_SUBSCRIPTION: AtomicU64 = AtomicU64::new(1);
+/// The number of running callbacks of each domain.
+static RUNNING: Mutex<BTreeMap<u64, usize>> = Mutex::new(BTreeMap::new());
+
+/// A callback of the domain which is about to run or runs.
+struct Running(u64);
+
+impl Running {
+    /// It is created under the lock of the subscriptions, so a domain whose
+    /// subscriptions are removed gets no new running callback.
+    fn new(domain_id: u64) -> Self {
+        *RUNNING.lock().entry(domain_id).or_default() += 1;
+        Self(domain_id)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let mut running = RUNNING.lock();
+        if let Some(count) = running.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                running.remove(&self.0);
+            }
+        }
+    }
+}
+
+/// Call the function and return `false` if it panicked.
+pub type CatchUnwind = fn(&mut dyn FnMut()) -> bool;
+
+static CATCH_UNWIND: Once<CatchUnwind> = Once::new();
+
+/// Set the function which catches the panic of a callback, e.g. one with
+/// `unwinding::panic::catch_unwind`.
+///
+/// Without it a panic of a callback unwinds into the task which publishes
+/// the event.
+pub fn init_catch_unwind(catch: CatchUnwind) {
+    CATCH_UNWIND.call_once(|| catch);
+}
+
+/// Subscribe `domain_id` to the events `filter` matches and return the id of
+/// the subscription.
+pub fn subscribe(domain_id: u64, filter: EventFilter<'_>, delivery: EventDelivery) -> u64 {
+    let filter = match filter {
+        EventFilter::Name(name) => Filter::Name(name.to_string()),
+        EventFilter::Type(ty) => Filter::Type(ty),
+        EventFilter::All => Filter::All,
+    };
+    let delivery = match delivery {
+        EventDelivery::Callback(f) => Delivery::Callback(f),
+        EventDelivery::Queue => Delivery::Queue {
+            events: VecDeque::new(),
+            dropped: 0,
+        },
+    };
+    let id = NEXT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+    SUBSCRIPTIONS.lock().insert(
+        id,
+        Subscription {
+            domain_id,
+            filter,
+            delivery,
+        },
+    );
+    id
+}
+
+pub fn unsubscribe(subscription: u64) -> Result<(), EventError> {
+    SUBSCRIPTIONS
+        .lock()
+        .remove(&subscription)
+        .map(|_| ())
+        .ok_or(EventError::NoSubscription)
+}
+
+/// Take the oldest event of a queue subscription.
+pub fn poll(subscription: u64) -> Result<Option<DomainEvent>, EventError> {
+    let mut subscriptions = SUBSCRIPTIONS.lock();
+    let subscription = subscriptions
+        .get_mut(&subscription)
+        .ok_or(EventError::NoSubscription)?;
+    match &mut subscription.delivery {
+        Delivery::Queue { events, .. } => Ok(events.pop_front()),
+        Delivery::Callback(_) => Err(EventError::NotQueue),
+    }
+}
+
+/// Return the number of events a queue subscription dropped.
+pub fn dropped_events(subscription: u64) -> Result<usize, EventError> {
+    let subscriptions = SUBSCRIPTIONS.lock();
+    match subscriptions.get(&subscription).map(|s| &s.delivery) {
+        Some(Delivery::Queue { dropped, .. }) => Ok(*dropped),
+        Some(Delivery::Callback(_)) => Err(EventError::NotQueue),
+        None => Err(EventError::NoSubscription),
+    }
+}
+
+/// Deliver the event to the subscriptions which match it.
+///
+/// The callbacks are called after the subscriptions are unlocked, so a
+/// callback can subscribe or poll. A callback which panics is unsubscribed.
+pub fn publish(event: DomainEvent) {
+    let mut callbacks = Vec::new();
+    let mut subscriptions = SUBSCRIPTIONS.lock();
+    for (id, subscription) in subscriptions.iter_mut() {
+        if !subscription.filter.matches(&event) {
+            continue;
+        }
+        match &mut subscription.delivery {
+            Delivery::Callback(f) => {
+                callbacks.push((*id, *f, Running::new(subscription.domain_id)))
+            }
+            Delivery::Queue { events, dropped } => {
+                if events.len() == EVENT_QUEUE_LEN {
+                    events.pop_front();
+                    *dropped += 1;
+                }
+                events.push_back(event);
+            }
+        }
+    }
+    drop(subscriptions);
+    log::debug!("domain event: {}", event);
+    for (id, f, running) in callbacks {
+        let mut call = || f(&event);
+        let finished = match CATCH_UNWIND.get() {
+            Some(catch) => catch(&mut call),
+            None => {
+                call();
+                true
+            }
+        };
+        drop(running);
+        if !finished {
+            log::warn!(
+                "the callback of subscription {} panicked, unsubscribe it",
+                id
+            );
+            SUBSCRIPTIONS.lock().remove(&id);
+        }
+    }
+}
+
+/// Remove the subscriptions of the domain, `free_domain_resource` calls it
+/// before the domain is freed.
+pub fn remove_domain_subscriptions(domain_id: u64) {
+    SUBSCRIPTIONS
+        .lock()
+        .retain(|_, subscription| subscription.domain_id != domain_id);
+}
+
+/// Wait until the callbacks of the domain which [`publish`] has collected
+/// return, so the code of the domain can be freed.
+///
+/// The subscriptions of the domain must be removed first. It must not be
+/// called from a callback of the domain.
+pub fn wait_domain_callbacks(domain_id: u64) {
+    while RUNNING.lock().contains_key(&domain_id) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Move the subscriptions of the domain to the domain which replaces it.
+///
+/// The queues are kept and the new domain polls them with the same ids, the
+/// callbacks point into the code of the old domain and are removed.
+pub fn move_domain_subscriptions(old_id: u64, new_id: u64) {
+    SUBSCRIPTIONS.lock().retain(|_, subscription| {
+        if subscription.domain_id != old_id {
+            return true;
+        }
+        match subscription.delivery {
+            Delivery::Callback(_) => false,
+            Delivery::Queue { .. } => {
+                subscription.domain_id = new_id;
+                true
+            }
+        }
+    });
+}
+
+/// [`publish`] an `Unloaded` event and [`remove_domain_subscriptions`].
+pub fn domain_unloaded(ty: DomainTypeRaw, domain_id: u64, name: &str) {
+    remove_domain_subscriptions(domain_id);
+    publish(DomainEvent::new(
+        DomainEventKind::Unloaded,
+        ty,
+        domain_id,
+        name,
+    ));
+}
@@ -0,0 +1,264 @@
+//! The dependencies between domains.
+//!
+//! A domain depends on the domains it looks up with `get_domain`, the kernel
+//! records the lookups with [`add_dependency`]. The edges are kept by name,
+//! so they stay valid when a domain is replaced by a new instance.
+//!
+//! A domain restarts after the domains it depends on. When a domain is
+//! replaced or crashes, [`cascade_reload`] reloads the domains which depend
+//! on it and asked for it with [`set_cascade`], so they look up the new
+//! instance again.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{Display, Formatter};
+
+use spin::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// The domains depend on each other, they have no restart order.
+    Cycle(Vec<String>),
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GraphError::Cycle(names) => write!(f, "dependency cycle in {:?}", names),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    dependencies: BTreeSet<String>,
+    dependents: BTreeSet<String>,
+    cascade: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    nodes: BTreeMap<String, Node>,
+}
+
+static GRAPH: Mutex<DependencyGraph> = Mutex::new(DependencyGraph::new());
+
+impl DependencyGraph {
+    pub const fn new() -> Self {
+        Self {
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    fn node(&mut self, name: &str) -> &mut Node {
+        self.nodes.entry(name.to_string()).or_default()
+    }
+
+    /// Record that `dependent` uses `dependency`, return false if it is
+    /// known.
+    pub fn add_dependency(&mut self, dependent: &str, dependency: &str) -> bool {
+        if dependent == dependency {
+            return false;
+        }
+        self.node(dependency)
+            .dependents
+            .insert(dependent.to_string());
+        self.node(dependent)
+            .dependencies
+            .insert(dependency.to_string())
+    }
+
+    /// Remove the domain and its edges.
+    pub fn remove_domain(&mut self, name: &str) {
+        let Some(node) = self.nodes.remove(name) else {
+            return;
+        };
+        for dependency in node.dependencies.iter() {
+            if let Some(n) = self.nodes.get_mut(dependency) {
+                n.dependents.remove(name);
+            }
+        }
+        for dependent in node.dependents.iter() {
+            if let Some(n) = self.nodes.get_mut(dependent) {
+                n.dependencies.remove(name);
+            }
+        }
+    }
+
+    pub fn set_cascade(&mut self, name: &str, cascade: bool) {
+        self.node(name).cascade = cascade;
+    }
+
+    /// Return the domains `name` uses.
+    pub fn dependencies(&self, name: &str) -> Vec<String> {
+        self.nodes
+            .get(name)
+            .map(|n| n.dependencies.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Return the domains which use `name`.
+    pub fn dependents(&self, name: &str) -> Vec<String> {
+        self.nodes
+            .get(name)
+            .map(|n| n.dependents.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Return every domain with the domains it uses.
+    pub fn dependency_map(&self) -> BTreeMap<String, Vec<String>> {
+        self.nodes
+            .iter()
+            .map(|(name, n)| (name.clone(), n.dependencies.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// Collect `name` and the domains which depend on it, following only
+    /// the dependents `follow` accepts.
+    fn reachable(&self, name: &str, follow: impl Fn(&Node) -> bool) -> BTreeSet<String> {
+        let mut set = BTreeSet::new();
+        let mut stack = Vec::from([name.to_string()]);
+        while let Some(name) = stack.pop() {
+            if !set.insert(name.clone()) {
+                continue;
+            }
+            let Some(node) = self.nodes.get(&name) else {
+                continue;
+            };
+            for dependent in node.dependents.iter() {
+                if self.nodes.get(dependent).is_some_and(&follow) {
+                    stack.push(dependent.clone());
+                }
+            }
+        }
+        set
+    }
+
+    /// Sort the domains so that every domain comes after the domains of the
+    /// set it depends on.
+    fn sort(&self, set: &BTreeSet<String>) -> Result<Vec<String>, GraphError> {
+        let mut pending = BTreeMap::new();
+        for name in set.iter() {
+            let count = self
+                .nodes
+                .get(name)
+                .map_or(0, |n| n.dependencies.intersection(set).count());
+            pending.insert(name.as_str(), count);
+        }
+        let mut ready = pending
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        let mut order = Vec::with_capacity(set.len());
+        while let Some(name) = ready.pop() {
+            pending.remove(name);
+            order.push(name.to_string());
+            let Some(node) = self.nodes.get(name) else {
+                continue;
+            };
+            for dependent in node.dependents.iter() {
+                if let Some(count) = pending.get_mut(dependent.as_str()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(dependent.as_str());
+                    }
+                }
+            }
+        }
+        if !pending.is_empty() {
+            let names = pending.keys().map(|name| name.to_string()).collect();
+            return Err(GraphError::Cycle(names));
+        }
+        Ok(order)
+    }
+
+    /// Return `name` and every domain which depends on it, in the order to
+    /// restart them.
+    pub fn restart_order(&self, name: &str) -> Result<Vec<String>, GraphError> {
+        self.sort(&self.reachable(name, |_| true))
+    }
+
+    /// Return every domain in the order to start them.
+    pub fn startup_order(&self) -> Result<Vec<String>, GraphError> {
+        self.sort(&self.nodes.keys().cloned().collect())
+    }
+
+    /// Return the domains to reload after `name` is replaced or crashed, in
+    /// the order to reload them. `name` is not included.
+    pub fn cascade_targets(&self, name: &str) -> Result<Vec<String>, GraphError> {
+        let mut order = self.sort(&self.reachable(name, |n| n.cascade))?;
+        order.retain(|n| n != name);
+        Ok(order)
+    }
+}
+
+/// Run `f` with the dependency graph of the kernel.
+pub fn with_graph<R>(f: impl FnOnce(&mut DependencyGraph) -> R) -> R {
+    f(&mut GRAPH.lock())
+}
+
+/// Record that `dependent` looked up `dependency`.
+pub fn add_dependency(dependent: &str, dependency: &str) {
+    if GRAPH.lock().add_dependency(dependent, dependency) {
+        log::info!("domain [{}] depends on [{}]", dependent, dependency);
+    }
+}
+
+/// Remove the domain from the graph, it is called when the domain is
+/// unloaded.
+pub fn remove_domain(name: &str) {
+    GRAPH.lock().remove_domain(name);
+}
+
+/// Reload the domain with its dependencies when one of them is replaced or
+/// crashes.
+pub fn set_cascade(name: &str, cascade: bool) {
+    GRAPH.lock().set_cascade(name, cascade);
+}
+
+pub fn restart_order(name: &str) -> Result<Vec<String>, GraphError> {
+    GRAPH.lock().restart_order(name)
+}
+
+pub fn dependency_map() -> BTreeMap<String, Vec<String>> {
+    GRAPH.lock().dependency_map()
+}
+
+/// Reload the domains which cascade from `name` with `reload`, in restart
+/// order, and return the number of reloaded domains.
+///
+/// The graph is not locked while `reload` runs. It stops at the first
+/// error, the domains after it are not reloaded.
+pub fn cascade_reload<E>(
+    name: &str,
+    mut reload: impl FnMut(&str) -> Result<(), E>,
+) -> Result<usize, CascadeError<E>> {
+    let targets = GRAPH
+        .lock()
+        .cascade_targets(name)
+        .map_err(CascadeError::Graph)?;
+    for (index, target) in targets.iter().enumerate() {
+        log::warn!("[{}] changed, reload [{}]", name, target);
+        reload(target).map_err(|e| CascadeError::Reload {
+            domain: target.clone(),
+            reloaded: index,
+            error: e,
+        })?;
+    }
+    Ok(targets.len())
+}
+
+#[derive(Debug)]
+pub enum CascadeError<E> {
+    Graph(GraphError),
+    /// Reloading `domain` failed after `reloaded` domains were reloaded.
+    Reload {
+        domain: String,
+        reloaded: usize,
+        error: E,
+    },
+}
@@ -3,10 +3,30 @@
 #![no_std]
 extern crate alloc;
 
+pub mod checkpoint;
+pub mod event;
+pub mod graph;
 pub mod resource;
+pub mod restart;
 pub mod sheap;
+pub mod stats;
 pub mod storage_heap;
+pub mod watchdog;
 
 pub const FRAME_SIZE: usize = 4096;
 
 pub const FRAME_BITS: usize = 12;
+
+static TIMER: spin::Once<fn() -> u64> = spin::Once::new();
+
+/// Set the clock used to timestamp the values in the domain storage.
+///
+/// The clock should return the current time in nanoseconds.
+pub fn init_timer(now_ns: fn() -> u64) {
+    TIMER.call_once(|| now_ns);
+}
+
+/// Return the current time in nanoseconds, or 0 if no clock is set.
+pub(crate) fn now_ns() -> u64 {
+    TIMER.get().map_or(0, |now| now())
+}
@@ -1,4 +1,8 @@
-use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
 
 use spin::Mutex;
 
@@ -12,6 +16,9 @@ pub static DOMAIN_RESOURCE: Mutex<DomainResource> = Mutex::new(DomainResource::n
 pub struct DomainResource {
     page_map: BTreeMap<u64, Vec<(usize, usize)>>,
     box_data: BTreeMap<u64, usize>,
+    /// The domains whose resources are freed, they are unloaded, failed or
+    /// replaced.
+    freed: BTreeSet<u64>,
 }
 
 impl DomainResource {
@@ -19,6 +26,7 @@ impl DomainResource {
         Self {
             page_map: BTreeMap::new(),
             box_data: BTreeMap::new(),
+            freed: BTreeSet::new(),
         }
     }
 
@@ -32,6 +40,19 @@ impl DomainResource {
         vec.retain(|(s, _)| *s != page);
     }
 
+    /// Return the number of pages the domain holds.
+    pub fn pages(&self, domain_id: u64) -> usize {
+        self.page_map
+            .get(&domain_id)
+            .map_or(0, |vec| vec.iter().map(|(_, n)| n).sum())
+    }
+
+    /// Return true if the resources of the domain are freed, its code may be
+    /// gone.
+    pub fn is_freed(&self, domain_id: u64) -> bool {
+        self.freed.contains(&domain_id)
+    }
+
     pub fn insert_box_data(&mut self, domain_id: u64, data: usize) {
         self.box_data.insert(domain_id, data);
     }
@@ -51,10 +72,15 @@ where
 {
     // println!("free_domain_resource for domain_id: {}", domain_id);
 
+    // the callbacks of the domain must not be called once its code is freed
+    crate::event::remove_domain_subscriptions(domain_id);
+    crate::event::wait_domain_callbacks(domain_id);
+
     // free shared data
     free_domain_shared_data(domain_id, free_shared);
 
     let mut binding = DOMAIN_RESOURCE.lock();
+    binding.freed.insert(domain_id);
     // free pages
     if let Some(vec) = binding.page_map.remove(&domain_id) {
         for (page_start, n) in vec {
@@ -77,3 +103,17 @@ where
         // println_color!(31, "[Domain: {}] free DomainDataMap resource", domain_id);
     }
 }
+
+/// Keep the resources of a replaced domain which calls abandoned by the
+/// watchdog still run in, see `watchdog::CallWatch::abandon`.
+///
+/// The pages of the domain are leaked. It is marked as freed and its
+/// subscriptions are removed like [`free_domain_resource`] does.
+pub fn abandon_domain_resource(domain_id: u64) {
+    log::warn!(
+        "[Domain: {}] keep the resources, abandoned calls are in it",
+        domain_id
+    );
+    crate::event::remove_domain_subscriptions(domain_id);
+    DOMAIN_RESOURCE.lock().freed.insert(domain_id);
+}
@@ -0,0 +1,119 @@
+//! The restart policies of domains.
+//!
+//! The kernel sets the policy of a domain when it is registered and when a
+//! domain asks for it with `sys_set_restart_policy`. When a domain panics
+//! or the supervisor finds it hung, the kernel asks [`on_failure`] what to
+//! do: reload the domain after a backoff, or fail it. A failed domain is
+//! replaced by its empty implementation with `replace_with_empty` of its
+//! proxy, every call returns `ENOSYS` until the domain is updated and
+//! [`reset`].
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::{String, ToString},
+};
+
+use domain_meta::domain_info::RestartPolicy;
+use spin::Mutex;
+
+use crate::now_ns;
+
+/// What to do with a domain which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartDecision {
+    /// Reload the domain after the delay.
+    Restart { delay_ns: u64 },
+    /// Replace the domain by its empty implementation.
+    Fail,
+}
+
+#[derive(Debug, Default)]
+struct RestartState {
+    policy: RestartPolicy,
+    /// The times of the reloads within the window of the policy.
+    restarts: VecDeque<u64>,
+    failed: bool,
+}
+
+static POLICIES: Mutex<BTreeMap<String, RestartState>> = Mutex::new(BTreeMap::new());
+
+/// Set the policy of the domains with the name.
+///
+/// The reloads counted so far are kept, a failed domain stays failed.
+pub fn set_policy(name: &str, policy: RestartPolicy) {
+    POLICIES.lock().entry(name.to_string()).or_default().policy = policy;
+}
+
+/// Return the policy of the domains with the name, [`RestartPolicy::Always`]
+/// if none is set.
+pub fn policy(name: &str) -> RestartPolicy {
+    POLICIES
+        .lock()
+        .get(name)
+        .map(|state| state.policy)
+        .unwrap_or_default()
+}
+
+/// Record a failure of the domain and decide whether it is reloaded.
+pub fn on_failure(name: &str) -> RestartDecision {
+    let now = now_ns();
+    let mut policies = POLICIES.lock();
+    let state = policies.entry(name.to_string()).or_default();
+    if state.failed {
+        return RestartDecision::Fail;
+    }
+    let decision = match state.policy {
+        RestartPolicy::Always => RestartDecision::Restart { delay_ns: 0 },
+        RestartPolicy::Never => RestartDecision::Fail,
+        RestartPolicy::OnFailure {
+            max_restarts,
+            window_ns,
+            backoff_ns,
+            max_backoff_ns,
+        } => {
+            while state
+                .restarts
+                .front()
+                .is_some_and(|time| now.saturating_sub(*time) > window_ns)
+            {
+                state.restarts.pop_front();
+            }
+            let count = state.restarts.len() as u32;
+            if count >= max_restarts {
+                RestartDecision::Fail
+            } else {
+                state.restarts.push_back(now);
+                let delay_ns = backoff_ns
+                    .checked_shl(count)
+                    .filter(|delay| delay >> count == backoff_ns)
+                    .unwrap_or(u64::MAX)
+                    .min(max_backoff_ns);
+                RestartDecision::Restart { delay_ns }
+            }
+        }
+    };
+    if decision == RestartDecision::Fail {
+        state.failed = true;
+        log::error!("domain [{}] failed permanently", name);
+    }
+    decision
+}
+
+pub fn is_failed(name: &str) -> bool {
+    POLICIES.lock().get(name).is_some_and(|state| state.failed)
+}
+
+/// Forget the failures of the domain, e.g. after it is updated to a new
+/// image. The policy is kept.
+pub fn reset(name: &str) {
+    if let Some(state) = POLICIES.lock().get_mut(name) {
+        state.restarts.clear();
+        state.failed = false;
+    }
+}
+
+/// Remove the policy of the domain, it is called when the domain is
+/// unloaded.
+pub fn remove(name: &str) {
+    POLICIES.lock().remove(name);
+}
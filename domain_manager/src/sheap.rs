@@ -173,6 +173,25 @@ pub fn checkout_shared_data() {
     log::info!("<checkout_shared_data> shared heap size: {}", heap.len());
 }
 
+/// Return the number of shared heap objects owned by the domain.
+pub fn shared_data_count(domain_id: u64) -> usize {
+    SHARED_HEAP
+        .lock()
+        .values()
+        .filter(|v| v.domain_id() == domain_id)
+        .count()
+}
+
+/// Return the number of bytes of the shared heap objects owned by the domain.
+pub fn shared_data_size(domain_id: u64) -> usize {
+    SHARED_HEAP
+        .lock()
+        .values()
+        .filter(|v| v.domain_id() == domain_id)
+        .map(|v| v.layout.size())
+        .sum()
+}
+
 pub enum FreeShared {
     Free,
     NotFree(u64),
@@ -210,3 +229,11 @@ pub fn free_domain_shared_data(id: u64, free_shared: FreeShared) {
         }
     }
 }
+
+/// Call `f` for every shared heap object owned by the domain.
+pub(crate) fn for_each_domain_shared_data(id: u64, mut f: impl FnMut(&SharedHeapAllocation)) {
+    let heap = SHARED_HEAP.lock();
+    heap.values()
+        .filter(|v| v.domain_id() == id)
+        .for_each(&mut f);
+}
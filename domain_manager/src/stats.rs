@@ -0,0 +1,33 @@
+//! Fill the statistics of `DomainInfo`.
+//!
+//! The kernel keeps the `DomainInfo` which the domains read with
+//! `domain_info()`. It records the creation and the restarts of a domain
+//! when they happen, and calls [`refresh_domain_info`] before it hands the
+//! info out to update the values which change on every call.
+
+use domain_meta::domain_info::DomainInfo;
+
+use crate::{
+    graph,
+    resource::DOMAIN_RESOURCE,
+    sheap::{shared_data_count, shared_data_size},
+    storage_heap::domain_database_len,
+};
+
+/// Update the memory, storage and call statistics of every domain and the
+/// dependencies.
+///
+/// `calls` returns the number of calls into the domain with the name, e.g.
+/// `Supervisor::calls`, the count is kept if it returns `None`.
+pub fn refresh_domain_info(info: &mut DomainInfo, calls: impl Fn(&str) -> Option<u64>) {
+    for (id, data) in info.domain_list.iter_mut() {
+        data.private_pages = DOMAIN_RESOURCE.lock().pages(*id);
+        data.shared_objects = shared_data_count(*id);
+        data.shared_bytes = shared_data_size(*id);
+        data.storage_entries = domain_database_len(*id);
+        if let Some(calls) = calls(&data.name) {
+            data.calls = calls;
+        }
+    }
+    info.dependencies = graph::dependency_map();
+}
@@ -3,15 +3,22 @@ use alloc::{
     collections::BTreeMap,
     string::{String, ToString},
     sync::Arc,
+    vec::Vec,
 };
 use core::{
     alloc::{AllocError, Allocator, GlobalAlloc, Layout},
-    any::Any,
+    fmt::{Debug, Formatter, Write},
+    ops::Bound,
     ptr::NonNull,
 };
 
 use spin::Mutex;
-use storage::{CustomStorge, DomainDataStorage, SendAllocator};
+use storage::{
+    ArcValueType, CustomStorge, DomainDataStorage, EntryMeta, SaveFn, SendAllocator, StorageEntry,
+    StorageMigration, StorageResult, ValueInfo, WriteOp,
+};
+
+use crate::now_ns;
 
 pub struct DomainDataMapManager {
     map_per_domain: BTreeMap<u64, DomainDataMap>,
@@ -43,23 +50,108 @@ impl DomainDataMapManager {
     /// Move the domain data map from the source domain to the target domain.
     fn move_domain(&mut self, from: u64, to: u64) {
         if let Some(data) = self.remove(from) {
+            data.clear_checkpoint();
             // println_color!(32, "move domain database, it's length: {}", data.len());
             self.map_per_domain.insert(to, data);
         }
     }
 }
 
-type ArcValueType = Arc<dyn Any + Send + Sync, CustomStorge>;
+/// A value in the data map with the type it was inserted with.
+///
+/// The type name and the size are recorded on insert because the domain
+/// which inserted the value may be unloaded before the value is read again.
+#[derive(Debug, Clone)]
+struct DataEntry {
+    value: ArcValueType,
+    type_hash: u64,
+    type_name: String,
+    version: u32,
+    size: usize,
+    inserted_at: u64,
+    save: Option<SaveFn>,
+}
+
+impl DataEntry {
+    fn new(value: ArcValueType, info: ValueInfo<'_>) -> Self {
+        let size = core::mem::size_of_val(&*value);
+        Self {
+            value,
+            type_hash: info.type_hash,
+            type_name: info.type_name.to_string(),
+            version: info.version,
+            size,
+            inserted_at: now_ns(),
+            save: None,
+        }
+    }
+
+    fn meta(&self) -> EntryMeta<'_> {
+        EntryMeta {
+            type_name: &self.type_name,
+            version: self.version,
+            size: self.size,
+            strong_count: Arc::strong_count(&self.value),
+            inserted_at: self.inserted_at,
+        }
+    }
+
+    fn to_storage_entry(&self) -> StorageEntry {
+        StorageEntry {
+            value: self.value.clone(),
+            type_hash: self.type_hash,
+            version: self.version,
+        }
+    }
+
+    fn into_storage_entry(self) -> StorageEntry {
+        StorageEntry {
+            value: self.value,
+            type_hash: self.type_hash,
+            version: self.version,
+        }
+    }
+}
+
+type MigrationList = Vec<Box<dyn StorageMigration, CustomStorge>>;
+
+/// Gets the key, the type information, the save function and the value of an
+/// entry, see [`DomainDataMap::save`].
+pub(crate) type SaveVisitor<'a> =
+    dyn FnMut(&str, ValueInfo<'_>, Option<SaveFn>, &ArcValueType) + 'a;
 
+/// A value restored from a checkpoint image which is not claimed by the
+/// domain yet.
 #[derive(Debug)]
+pub(crate) struct RestoredRecord {
+    pub type_hash: u64,
+    pub type_name: String,
+    pub version: u32,
+    pub data: Vec<u8>,
+}
+
 pub struct DomainDataMap {
-    data: Arc<Mutex<BTreeMap<String, ArcValueType>>>,
+    data: Arc<Mutex<BTreeMap<String, DataEntry>>>,
+    migrations: Arc<Mutex<BTreeMap<String, MigrationList>>>,
+    restored: Arc<Mutex<BTreeMap<String, RestoredRecord>>>,
+}
+
+impl Debug for DomainDataMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DomainDataMap")
+            .field("data", &self.data)
+            .field("migrations", &self.migrations.lock().len())
+            .field("restored", &self.restored.lock().len())
+            .finish()
+    }
 }
 
 impl Clone for DomainDataMap {
     fn clone(&self) -> Self {
         Self {
             data: self.data.clone(),
+            migrations: self.migrations.clone(),
+            restored: self.restored.clone(),
         }
     }
 }
@@ -71,41 +163,246 @@ impl DomainDataMap {
     pub fn new() -> Self {
         Self {
             data: Arc::new(Mutex::new(BTreeMap::new())),
+            migrations: Arc::new(Mutex::new(BTreeMap::new())),
+            restored: Arc::new(Mutex::new(BTreeMap::new())),
         }
     }
 
     pub fn len(&self) -> usize {
         self.data.lock().len()
     }
+
+    /// Save every value of the data map to the checkpoint image.
+    ///
+    /// The values without a save function are recorded without data. The
+    /// save functions are code of the domain, they are called on a copy of
+    /// the entries without the data lock held.
+    pub(crate) fn save(&self, f: &mut SaveVisitor<'_>) {
+        let entries = self
+            .data
+            .lock()
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.clone()))
+            .collect::<Vec<_>>();
+        for (key, entry) in entries.iter() {
+            let info = ValueInfo {
+                type_hash: entry.type_hash,
+                type_name: &entry.type_name,
+                version: entry.version,
+            };
+            f(key, info, entry.save, &entry.value);
+        }
+    }
+
+    /// Keep the records of a checkpoint image until the domain claims them.
+    pub(crate) fn set_restored(&self, records: BTreeMap<String, RestoredRecord>) {
+        *self.restored.lock() = records;
+    }
+
+    /// Forget the save functions, they belong to the domain which is replaced.
+    fn clear_checkpoint(&self) {
+        self.data
+            .lock()
+            .values_mut()
+            .for_each(|entry| entry.save = None);
+    }
+
+    /// Write a line for every value in the data map, in key order.
+    pub fn dump(&self, w: &mut dyn Write) -> core::fmt::Result {
+        let data = self.data.lock();
+        writeln!(w, "{} values", data.len())?;
+        for (key, entry) in data.iter() {
+            let meta = entry.meta();
+            writeln!(
+                w,
+                "{}: {} v{}, size: {}, refs: {}, inserted at: {}ns",
+                key, meta.type_name, meta.version, meta.size, meta.strong_count, meta.inserted_at
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Copy the entries of the data map, the values are shared.
+    fn snapshot(&self) -> DataSnapshot {
+        DataSnapshot(self.data.lock().clone())
+    }
+
+    /// Put the entries of the snapshot back, the values inserted or
+    /// replaced since it was taken are dropped.
+    fn rollback(&self, snapshot: DataSnapshot) {
+        let newer = core::mem::replace(&mut *self.data.lock(), snapshot.0);
+        // drop the newer values without holding the lock, they may use the storage
+        drop(newer);
+    }
+
+    /// Run all registered migrations and return the number of migrated values.
+    ///
+    /// The new values are only written back if all migrations succeed. The
+    /// registered migrations are consumed in both cases.
+    pub fn migrate(&self) -> StorageResult<usize> {
+        let migrations = core::mem::take(&mut *self.migrations.lock());
+        let mut staged = BTreeMap::new();
+        // the data lock is not held while migrating, the migration may read the storage
+        for (key, list) in migrations.iter() {
+            let mut current = match self.get(key) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let mut migrated = None;
+            // every migration is applied at most once, so a cycle of versions terminates
+            for _ in 0..list.len() {
+                let migration = match list.iter().find(|m| m.source_version() == current.version) {
+                    Some(migration) => migration,
+                    None => break,
+                };
+                let (value, info) = migration.migrate(&current).inspect_err(|e| {
+                    log::error!("migrate key: {}, version: {}, {}", key, current.version, e)
+                })?;
+                current = StorageEntry {
+                    value: value.clone(),
+                    type_hash: info.type_hash,
+                    version: info.version,
+                };
+                migrated = Some(DataEntry::new(value, info));
+            }
+            if let Some(entry) = migrated {
+                log::info!("migrate key: {} to version {}", key, entry.version);
+                staged.insert(key.clone(), entry);
+            }
+        }
+        let count = staged.len();
+        self.data.lock().extend(staged);
+        Ok(count)
+    }
 }
 impl DomainDataStorage for DomainDataMap {
     /// Insert a new key-value pair into the data map.
     ///
-    /// If the key already exists, the value will be replaced and returned.
-    /// Otherwise, `None` will be returned.
-    fn insert(&self, key: &str, value: ArcValueType) -> Option<ArcValueType> {
+    /// If the key already exists and `check` accepts it, the value will be
+    /// replaced and returned. Otherwise, `None` will be returned.
+    fn insert(
+        &self,
+        key: &str,
+        value: ArcValueType,
+        info: ValueInfo<'_>,
+        check: &dyn Fn(&StorageEntry) -> StorageResult<()>,
+    ) -> StorageResult<Option<StorageEntry>> {
         // println_color!(32, "insert key: {}", key);
-        self.data.lock().insert(key.to_string(), value)
+        let mut data = self.data.lock();
+        if let Some(old) = data.get(key) {
+            check(&old.to_storage_entry()).inspect_err(|_| {
+                log::warn!(
+                    "refuse to replace key: {}, type: {} -> {}",
+                    key,
+                    old.type_name,
+                    info.type_name
+                );
+            })?;
+        }
+        let old = data.insert(key.to_string(), DataEntry::new(value, info));
+        Ok(old.map(DataEntry::into_storage_entry))
     }
 
     /// Get the value with the given key.
-    fn get(&self, key: &str) -> Option<ArcValueType> {
+    fn get(&self, key: &str) -> Option<StorageEntry> {
         let data = self.data.lock();
         let v = data.get(key);
         // println_color!(32, "get key: {}, value: {:?}", key, v.is_some());
 
-        v.cloned()
+        v.map(DataEntry::to_storage_entry)
     }
 
     /// Remove the value with the given key.
     ///
     /// If the key exists, the value will be removed and returned.
     /// Otherwise, `None` will be returned.
-    fn remove(&self, key: &str) -> Option<ArcValueType> {
+    fn remove(&self, key: &str) -> Option<StorageEntry> {
         let mut data = self.data.lock();
 
         // println_color!(31, "remove key: {}", key);
-        data.remove(key)
+        data.remove(key).map(DataEntry::into_storage_entry)
+    }
+
+    /// Remove the value with the given key if `check` accepts it.
+    fn remove_if(
+        &self,
+        key: &str,
+        check: &dyn Fn(&StorageEntry, usize) -> StorageResult<()>,
+    ) -> StorageResult<Option<StorageEntry>> {
+        let mut data = self.data.lock();
+        let entry = match data.get(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let strong_count = Arc::strong_count(&entry.value);
+        // the clone only lives during the check, the count above excludes it
+        check(&entry.to_storage_entry(), strong_count)?;
+        Ok(data.remove(key).map(DataEntry::into_storage_entry))
+    }
+
+    /// Register a migration for the value with the given key.
+    ///
+    /// The migrations are run by [`migrate_domain_database`] during the update.
+    fn register_migration(&self, key: &str, migration: Box<dyn StorageMigration, CustomStorge>) {
+        self.migrations
+            .lock()
+            .entry(key.to_string())
+            .or_default()
+            .push(migration);
+    }
+
+    /// Apply the writes of a transaction under one lock.
+    fn commit(
+        &self,
+        ops: &mut dyn Iterator<Item = WriteOp<'_>>,
+        replaced: &mut dyn FnMut(StorageEntry),
+    ) {
+        let mut data = self.data.lock();
+        for op in ops {
+            let old = match op {
+                WriteOp::Insert { key, value, info } => {
+                    data.insert(key.to_string(), DataEntry::new(value, info))
+                }
+                WriteOp::Remove { key } => data.remove(key),
+            };
+            if let Some(old) = old {
+                replaced(old.into_storage_entry());
+            }
+        }
+    }
+
+    /// Set the function which saves the value to a checkpoint image.
+    fn set_checkpoint(&self, key: &str, save: Option<SaveFn>) -> bool {
+        match self.data.lock().get_mut(key) {
+            Some(entry) => {
+                entry.save = save;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pass the restored record of the key to `f` and forget it.
+    fn take_restored(&self, key: &str, f: &mut dyn FnMut(ValueInfo<'_>, &[u8])) {
+        let record = self.restored.lock().remove(key);
+        if let Some(record) = record {
+            let info = ValueInfo {
+                type_hash: record.type_hash,
+                type_name: &record.type_name,
+                version: record.version,
+            };
+            f(info, &record.data);
+        }
+    }
+
+    /// Call `f` for every key which starts with `prefix`, in key order.
+    ///
+    /// The data map is locked while `f` runs, so `f` must not access the storage.
+    fn for_each(&self, prefix: &str, f: &mut dyn FnMut(&str, &EntryMeta<'_>)) {
+        let data = self.data.lock();
+        data.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .for_each(|(key, entry)| f(key, &entry.meta()));
     }
 }
 
@@ -170,6 +467,8 @@ impl SendAllocator for DomainDataHeap {}
 
 pub static DOMAIN_DATA_ALLOCATOR: &'static dyn SendAllocator = &DomainDataHeap;
 static DATA_BASE_MANAGER: Mutex<DomainDataMapManager> = Mutex::new(DomainDataMapManager::new());
+/// The entries of the moved data maps, by the domain they were moved to.
+static UPDATE_SNAPSHOTS: Mutex<BTreeMap<u64, DataSnapshot>> = Mutex::new(BTreeMap::new());
 
 /// Create a new domain data map with the given domain id.
 pub fn create_domain_database(domain_id: u64) {
@@ -185,9 +484,16 @@ pub fn get_domain_database(domain_id: u64) -> Option<Box<DomainDataMap>> {
     res
 }
 
+/// Return the number of entries in the data map of the given domain.
+pub fn domain_database_len(domain_id: u64) -> usize {
+    let manager = DATA_BASE_MANAGER.lock();
+    manager.get(domain_id).map_or(0, |data_map| data_map.len())
+}
+
 /// Remove the domain data map with the given domain id.
 #[allow(unused)]
 pub fn remove_domain_database(domain_id: u64) -> Option<Box<DomainDataMap>> {
+    drop(take_update_snapshot(domain_id));
     let mut manager = DATA_BASE_MANAGER.lock();
     let res = manager.remove(domain_id).map(Box::new);
     log::info!("remove domain database for domain_id: {}", domain_id);
@@ -195,8 +501,74 @@ pub fn remove_domain_database(domain_id: u64) -> Option<Box<DomainDataMap>> {
 }
 
 /// Move the domain data map from the source domain to the target domain.
+///
+/// The entries are copied before the move, so before the `main` of the
+/// target domain writes to them. An update which is aborted rolls back to
+/// them, see [`take_update_snapshot`].
 pub fn move_domain_database(from: u64, to: u64) {
     let mut manager = DATA_BASE_MANAGER.lock();
+    let snapshot = manager.get(from).map(DomainDataMap::snapshot);
     manager.move_domain(from, to);
+    drop(manager);
     // println_color!(32, "move domain database from {} to {}", from, to);
+    let mut snapshots = UPDATE_SNAPSHOTS.lock();
+    // the snapshot of the previous move of the data map is not needed anymore
+    let stale = snapshots.remove(&from);
+    if let Some(snapshot) = snapshot {
+        snapshots.insert(to, snapshot);
+    }
+    drop(snapshots);
+    // the values are dropped without the lock, they may use the storage
+    drop(stale);
+}
+
+/// Take the entries the data map had before it was moved to the given
+/// domain, see [`move_domain_database`].
+///
+/// The update of a domain takes it before the migration and drops it when
+/// it is done. Until then the snapshot holds a clone of every value.
+pub fn take_update_snapshot(domain_id: u64) -> Option<DataSnapshot> {
+    UPDATE_SNAPSHOTS.lock().remove(&domain_id)
+}
+
+/// The entries of a data map before a domain update, see
+/// [`take_update_snapshot`].
+pub struct DataSnapshot(BTreeMap<String, DataEntry>);
+
+/// Copy the entries of the data map of the given domain.
+///
+/// A domain update uses the copy taken when the data map was moved, see
+/// [`take_update_snapshot`].
+pub fn snapshot_domain_database(domain_id: u64) -> Option<DataSnapshot> {
+    get_domain_database(domain_id).map(|data_map| data_map.snapshot())
+}
+
+/// Put the entries of the snapshot back into the data map of the given
+/// domain, the values written since the snapshot are dropped.
+pub fn rollback_domain_database(domain_id: u64, snapshot: DataSnapshot) {
+    if let Some(data_map) = get_domain_database(domain_id) {
+        data_map.rollback(snapshot);
+    }
+}
+
+/// Run the migrations registered by the domain which owns the data map now.
+///
+/// It is called during the update after the database has been moved to the
+/// new domain and before the new domain is initialized. If it fails, the
+/// update should be aborted.
+pub fn migrate_domain_database(domain_id: u64) -> StorageResult<usize> {
+    match get_domain_database(domain_id) {
+        Some(data_map) => data_map.migrate(),
+        None => Ok(0),
+    }
+}
+
+/// Return a readable dump of the data map of the given domain.
+///
+/// It shows the state a domain will hand over to its successor.
+pub fn dump_domain_database(domain_id: u64) -> Option<String> {
+    let data_map = get_domain_database(domain_id)?;
+    let mut out = String::new();
+    data_map.dump(&mut out).ok()?;
+    Some(out)
 }
@@ -0,0 +1,433 @@
+//! Detect the domains which hang and reload them.
+//!
+//! Every proxy counts its calls in flight with its `PerCpuCounter` and keeps
+//! the start time of each call in its [`CallWatch`], so it knows the age of
+//! the oldest call in flight. The [`Supervisor`] declares a domain hung when
+//! that age exceeds the deadline of the domain, and unhealthy when
+//! `Basic::health_check` fails, and reloads it with `sys_reload_domain`, or
+//! fails it, as the restart policy of the domain decides, see
+//! [`crate::restart`].
+//!
+//! A call stuck in a domain never returns, so the supervisor abandons it
+//! before the domain is reloaded: the proxy does not wait for the abandoned
+//! calls when it replaces the domain, and keeps the old domain alive while
+//! they run its code.
+//!
+//! The recovery takes the sleeping lock of the proxy and loads the image of
+//! the domain, so the kernel runs [`Supervisor::check_stalls`] periodically
+//! from a task of its own, not from the timer interrupt. It runs
+//! [`Supervisor::check_health`] from another task: a health check calls into
+//! the domain and hangs with it, the stall check does not and finds it.
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use domain_meta::{
+    domain_info::{RestartOutcome, RestartReason, RestartRecord},
+    AlienResult,
+};
+use spin::Mutex;
+
+use crate::{
+    now_ns,
+    restart::{self, RestartDecision},
+};
+
+/// The deadline of a domain which has none set, 5 seconds.
+pub const DEFAULT_DEADLINE_NS: u64 = 5_000_000_000;
+
+/// The number of calls in flight a [`CallWatch`] keeps the start time of.
+pub const WATCHED_CALLS: usize = 32;
+
+/// How long an update waits for the calls in flight of the old domain, the
+/// deadline of a domain which has none set.
+pub const CALL_WAIT_NS: u64 = DEFAULT_DEADLINE_NS;
+
+/// The bit of a slot which marks the call as abandoned.
+const ABANDONED: u64 = 1 << 63;
+
+/// The start times of the calls a proxy has in flight.
+///
+/// A call takes a free slot with its start time when it enters the domain
+/// and frees it when it returns. The calls beyond [`WATCHED_CALLS`] are
+/// counted by the `PerCpuCounter` of the proxy but not watched.
+///
+/// The abandoned calls are counted per domain: every domain the proxy
+/// swaps in starts a new epoch with [`CallWatch::retire`], the calls
+/// abandoned in an old domain stay in flight but do not count for the new
+/// one. [`CallWatch::abandon`] must only be called by one supervisor.
+#[derive(Debug, Default)]
+pub struct CallWatch {
+    slots: [AtomicU64; WATCHED_CALLS],
+    /// The epoch each abandoned call was abandoned in.
+    epochs: [AtomicU32; WATCHED_CALLS],
+    calls: AtomicU64,
+    /// The abandoned calls in flight of all epochs.
+    abandoned: AtomicUsize,
+    /// The current epoch in the high half, the number of abandoned calls in
+    /// flight of it in the low half.
+    epoch: AtomicU64,
+}
+
+impl CallWatch {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { AtomicU64::new(0) }; WATCHED_CALLS],
+            epochs: [const { AtomicU32::new(0) }; WATCHED_CALLS],
+            calls: AtomicU64::new(0),
+            abandoned: AtomicUsize::new(0),
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a call which enters the domain and return its slot.
+    #[inline(always)]
+    pub fn enter(&self) -> usize {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        // 0 is a free slot
+        let start = now_ns().max(1) & !ABANDONED;
+        self.slots
+            .iter()
+            .position(|slot| {
+                slot.compare_exchange(0, start, Ordering::SeqCst, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .unwrap_or(WATCHED_CALLS)
+    }
+
+    /// Return the number of calls which entered the domain.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Record that the call in `slot` returned.
+    #[inline(always)]
+    pub fn exit(&self, slot: usize) {
+        let Some(start) = self.slots.get(slot) else {
+            return;
+        };
+        if start.swap(0, Ordering::SeqCst) & ABANDONED != 0 {
+            self.release(self.epochs[slot].load(Ordering::SeqCst));
+            self.abandoned.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Return the age of the oldest call in flight in nanoseconds, 0 if
+    /// there is none. The abandoned calls are not counted.
+    pub fn oldest_ns(&self) -> u64 {
+        let now = now_ns();
+        self.slots
+            .iter()
+            .map(|slot| slot.load(Ordering::SeqCst))
+            .filter(|start| *start != 0 && *start & ABANDONED == 0)
+            .map(|start| now.saturating_sub(start))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Abandon the calls in flight which are older than `age_ns` and return
+    /// the number of them.
+    pub fn abandon(&self, age_ns: u64) -> usize {
+        let now = now_ns();
+        let epoch = (self.epoch.load(Ordering::SeqCst) >> 32) as u32;
+        let mut count = 0;
+        for (slot, call_epoch) in self.slots.iter().zip(self.epochs.iter()) {
+            let start = slot.load(Ordering::SeqCst);
+            if start == 0 || start & ABANDONED != 0 || now.saturating_sub(start) <= age_ns {
+                continue;
+            }
+            call_epoch.store(epoch, Ordering::SeqCst);
+            // count it first, `exit` must never see more abandoned calls
+            // than are counted
+            if !self.acquire(epoch) {
+                // the domain is replaced meanwhile
+                break;
+            }
+            self.abandoned.fetch_add(1, Ordering::SeqCst);
+            match slot.compare_exchange(
+                start,
+                start | ABANDONED,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => count += 1,
+                // the call returned meanwhile
+                Err(_) => {
+                    self.release(epoch);
+                    self.abandoned.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        }
+        count
+    }
+
+    /// Count an abandoned call of the epoch, return false if the epoch is
+    /// over.
+    fn acquire(&self, epoch: u32) -> bool {
+        self.epoch
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                ((state >> 32) as u32 == epoch).then_some(state + 1)
+            })
+            .is_ok()
+    }
+
+    /// Uncount an abandoned call of the epoch, nothing if the epoch is over.
+    fn release(&self, epoch: u32) {
+        let _ = self
+            .epoch
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                ((state >> 32) as u32 == epoch).then_some(state - 1)
+            });
+    }
+
+    /// Return the number of abandoned calls which are still in flight, in
+    /// the current domain and in the domains replaced before.
+    ///
+    /// An update waits until the calls in flight of the proxy are no more
+    /// than it.
+    pub fn abandoned(&self) -> usize {
+        self.abandoned.load(Ordering::SeqCst)
+    }
+
+    /// Return the number of abandoned calls which are still in flight in the
+    /// current domain.
+    ///
+    /// An update keeps the old domain if it is not 0.
+    pub fn abandoned_in_domain(&self) -> usize {
+        self.epoch.load(Ordering::SeqCst) as u32 as usize
+    }
+
+    /// Start the epoch of a new domain and return the number of abandoned
+    /// calls which are still in flight in the old one.
+    ///
+    /// It is called when the proxy swaps the domain.
+    pub fn retire(&self) -> usize {
+        let state = self
+            .epoch
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                let epoch = ((state >> 32) as u32).wrapping_add(1);
+                Some((epoch as u64) << 32)
+            })
+            .unwrap();
+        state as u32 as usize
+    }
+
+    /// Wait until the calls in flight of the proxy are no more than the
+    /// abandoned ones, `in_flight` returns the calls in flight.
+    ///
+    /// Return false if some still run after [`CALL_WAIT_NS`], e.g. a stuck
+    /// call beyond [`WATCHED_CALLS`] which can not be abandoned.
+    pub fn wait_calls(&self, in_flight: impl Fn() -> usize) -> bool {
+        let start = now_ns();
+        while in_flight() > self.abandoned() {
+            if now_ns().saturating_sub(start) > CALL_WAIT_NS {
+                return false;
+            }
+            core::hint::spin_loop();
+        }
+        true
+    }
+}
+
+/// A proxy the [`Supervisor`] watches, `gproxy` implements it for the
+/// proxies of the traits with `Basic`.
+pub trait Supervised: Send + Sync {
+    /// Return the age of the oldest call in flight, see
+    /// [`CallWatch::oldest_ns`].
+    fn stalled_ns(&self) -> u64;
+    /// Abandon the calls older than `age_ns`, see [`CallWatch::abandon`].
+    fn abandon_calls(&self, age_ns: u64) -> usize;
+    /// Return the number of calls into the domain, see [`CallWatch::calls`].
+    fn calls(&self) -> u64;
+    /// Call `Basic::health_check` of the domain.
+    fn health_check(&self) -> AlienResult<()>;
+    /// Replace the domain by its empty implementation.
+    fn replace_with_empty(&self) -> AlienResult<()>;
+}
+
+struct Watched {
+    proxy: Arc<dyn Supervised>,
+    deadline_ns: u64,
+    /// The domain hangs and is not recovered yet.
+    hung: bool,
+    /// The time the domain is reloaded after its backoff.
+    pending: Option<(u64, RestartReason)>,
+}
+
+/// Reload the domains which hang or fail their health check.
+///
+/// The calls of a hung domain which are older than its deadline are
+/// abandoned, they stay in flight. The domain is watched again once it is
+/// reloaded or failed, a call which gets stuck later is a new hang.
+pub struct Supervisor {
+    /// Reload the domain with the name, it is `sys_reload_domain` of the
+    /// kernel.
+    reload: fn(&str) -> AlienResult<()>,
+    /// Record the restart in the `DomainInfo` of the kernel.
+    record: fn(&str, RestartRecord),
+    domains: Mutex<BTreeMap<String, Watched>>,
+}
+
+impl Supervisor {
+    pub fn new(reload: fn(&str) -> AlienResult<()>, record: fn(&str, RestartRecord)) -> Self {
+        Self {
+            reload,
+            record,
+            domains: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Watch the domain with [`DEFAULT_DEADLINE_NS`].
+    pub fn watch(&self, name: &str, proxy: Arc<dyn Supervised>) {
+        self.domains.lock().insert(
+            name.into(),
+            Watched {
+                proxy,
+                deadline_ns: DEFAULT_DEADLINE_NS,
+                hung: false,
+                pending: None,
+            },
+        );
+    }
+
+    pub fn unwatch(&self, name: &str) -> bool {
+        self.domains.lock().remove(name).is_some()
+    }
+
+    /// Set the time a call of the domain may take before it is declared
+    /// hung, return false if the domain is not watched.
+    pub fn set_deadline(&self, name: &str, deadline_ns: u64) -> bool {
+        match self.domains.lock().get_mut(name) {
+            Some(watched) => {
+                watched.deadline_ns = deadline_ns;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return the number of calls into the watched domain.
+    pub fn calls(&self, name: &str) -> Option<u64> {
+        self.domains.lock().get(name).map(|w| w.proxy.calls())
+    }
+
+    /// Reload the domains whose calls in flight are older than their
+    /// deadline and the domains whose backoff is over, return the number of
+    /// reloaded domains.
+    ///
+    /// It does not call into the domains.
+    pub fn check_stalls(&self) -> usize {
+        let now = now_ns();
+        let mut failed = Vec::new();
+        let mut due = Vec::new();
+        for (name, watched) in self.domains.lock().iter_mut() {
+            if let Some((time, reason)) = watched.pending {
+                if time <= now {
+                    watched.pending = None;
+                    due.push((name.clone(), reason));
+                }
+                continue;
+            }
+            if watched.hung {
+                continue;
+            }
+            let stalled_ns = watched.proxy.stalled_ns();
+            if stalled_ns > watched.deadline_ns {
+                // the stuck calls never return, the reload must not wait
+                // for them
+                let abandoned = watched.proxy.abandon_calls(watched.deadline_ns);
+                log::warn!("domain [{}] abandon {} calls", name, abandoned);
+                watched.hung = true;
+                failed.push((name.clone(), RestartReason::Hung { stalled_ns }));
+            }
+        }
+        let reloaded = failed
+            .iter()
+            .filter(|(name, reason)| self.on_failure(name, *reason))
+            .count();
+        reloaded
+            + due
+                .iter()
+                .filter(|(name, reason)| self.reload(name, *reason))
+                .count()
+    }
+
+    /// Call the health check of the domains which do not hang or wait for
+    /// their backoff, handle the ones which fail like
+    /// [`Supervisor::check_stalls`] and return the number of reloaded
+    /// domains.
+    pub fn check_health(&self) -> usize {
+        let domains = self
+            .domains
+            .lock()
+            .iter()
+            .filter(|(_, watched)| !watched.hung && watched.pending.is_none())
+            .map(|(name, watched)| (name.clone(), watched.proxy.clone()))
+            .collect::<Vec<_>>();
+        domains
+            .iter()
+            .filter(|(name, proxy)| match proxy.health_check() {
+                Ok(()) => false,
+                Err(e) => self.on_failure(name, RestartReason::Unhealthy(e)),
+            })
+            .count()
+    }
+
+    /// Reload the domain now or after its backoff, or fail it, as its
+    /// restart policy decides. Return true if it is reloaded now.
+    fn on_failure(&self, name: &str, reason: RestartReason) -> bool {
+        log::error!("domain [{}] {}", name, reason);
+        match restart::on_failure(name) {
+            RestartDecision::Restart { delay_ns: 0 } => self.reload(name, reason),
+            RestartDecision::Restart { delay_ns } => {
+                if let Some(watched) = self.domains.lock().get_mut(name) {
+                    watched.pending = Some((now_ns().saturating_add(delay_ns), reason));
+                }
+                false
+            }
+            RestartDecision::Fail => {
+                let proxy = self.domains.lock().get(name).map(|w| w.proxy.clone());
+                match proxy.map(|proxy| proxy.replace_with_empty()) {
+                    Some(Ok(())) => self.record(name, reason, RestartOutcome::Failed),
+                    Some(Err(e)) => log::error!("failed to empty domain [{}]: {:?}", name, e),
+                    None => {}
+                }
+                self.recovered(name);
+                false
+            }
+        }
+    }
+
+    fn reload(&self, name: &str, reason: RestartReason) -> bool {
+        log::warn!("reload domain [{}]", name);
+        let reloaded = match (self.reload)(name) {
+            Ok(()) => {
+                self.record(name, reason, RestartOutcome::Reloaded);
+                true
+            }
+            Err(e) => {
+                log::error!("failed to reload domain [{}]: {:?}", name, e);
+                false
+            }
+        };
+        self.recovered(name);
+        reloaded
+    }
+
+    /// Watch the domain for hangs again after its recovery, a failed reload
+    /// is retried when a call gets stuck again.
+    fn recovered(&self, name: &str) {
+        if let Some(watched) = self.domains.lock().get_mut(name) {
+            watched.hung = false;
+        }
+    }
+
+    fn record(&self, name: &str, reason: RestartReason, outcome: RestartOutcome) {
+        let record = RestartRecord {
+            time_ns: now_ns(),
+            reason,
+            outcome,
+        };
+        (self.record)(name, record);
+    }
+}
@@ -0,0 +1,99 @@
+//! Save the storage of a domain to an image and restore it.
+
+mod common;
+
+use common::{init_storage, DOMAIN_ID};
+use domain_manager::checkpoint::{
+    checkpoint_domain, parse_checkpoint, restore_domain, CheckpointError,
+};
+use storage::{Checkpoint, ImageWriter, StorageError, StorageKey, StorageResult};
+
+macro_rules! counter {
+    ($name:ident) => {
+        #[derive(Debug, PartialEq)]
+        struct $name {
+            n: u64,
+        }
+
+        impl Checkpoint for $name {
+            fn save(&self, w: &mut dyn ImageWriter) {
+                w.write(&self.n.to_le_bytes());
+            }
+
+            fn restore(data: &[u8]) -> StorageResult<Self> {
+                let n = data.try_into().map_err(|_| StorageError::TypeMismatch {
+                    expected: stringify!($name),
+                })?;
+                Ok($name {
+                    n: u64::from_le_bytes(n),
+                })
+            }
+        }
+    };
+}
+
+counter!(Counter);
+// the same layout under another name is another type
+counter!(Ticks);
+
+const COUNTER: StorageKey<Counter> = StorageKey::with_version("counter", 2);
+const PLAIN: StorageKey<Ticks> = StorageKey::new("plain");
+
+#[test]
+fn roundtrip() {
+    init_storage();
+    storage::insert_checkpoint(&COUNTER, Counter { n: 5 }).unwrap();
+    storage::insert(&PLAIN, Ticks { n: 1 }).unwrap();
+    let image = checkpoint_domain(DOMAIN_ID).unwrap();
+
+    let parsed = parse_checkpoint(&image).unwrap();
+    let entry = parsed.entries.iter().find(|e| e.key == "counter").unwrap();
+    assert!(entry.saved);
+    assert_eq!(entry.version, 2);
+    assert_eq!(entry.type_hash, COUNTER.value_info().type_hash);
+    assert_eq!(entry.data, 5u64.to_le_bytes());
+    let entry = parsed.entries.iter().find(|e| e.key == "plain").unwrap();
+    assert!(!entry.saved);
+
+    storage::remove(&COUNTER).unwrap();
+    // only the saved values can be restored
+    assert_eq!(restore_domain(DOMAIN_ID, &image), Ok(1));
+    assert!(storage::restore(&PLAIN).unwrap().is_none());
+    let restored = storage::restore(&COUNTER).unwrap().unwrap();
+    assert_eq!(*restored, Counter { n: 5 });
+    assert_eq!(*storage::get(&COUNTER).unwrap().unwrap(), Counter { n: 5 });
+    // a record is claimed once
+    assert!(storage::restore(&COUNTER).unwrap().is_none());
+
+    // the type and the version are checked
+    restore_domain(DOMAIN_ID, &image).unwrap();
+    let other_type = StorageKey::<Ticks>::with_version("counter", 2);
+    assert!(matches!(
+        storage::restore(&other_type),
+        Err(StorageError::TypeMismatch { .. })
+    ));
+    restore_domain(DOMAIN_ID, &image).unwrap();
+    let other_version = StorageKey::<Counter>::with_version("counter", 3);
+    assert_eq!(
+        storage::restore(&other_version).unwrap_err(),
+        StorageError::VersionMismatch {
+            expected: 3,
+            found: 2
+        }
+    );
+}
+
+#[test]
+fn bad_images() {
+    assert_eq!(
+        parse_checkpoint(b"DOMSTAT0").unwrap_err(),
+        CheckpointError::BadMagic
+    );
+    let mut image = b"DOMSTAT2".to_vec();
+    image.extend_from_slice(&1u32.to_le_bytes());
+    image.extend_from_slice(&100u32.to_le_bytes());
+    assert_eq!(
+        parse_checkpoint(&image).unwrap_err(),
+        CheckpointError::Truncated
+    );
+}
@@ -0,0 +1,22 @@
+//! The storage of a domain in a test process.
+//!
+//! The statics of `storage` are the statics of the process, so a test binary
+//! has one domain whose storage is the data map of [`DOMAIN_ID`].
+
+use std::sync::Once;
+
+use domain_manager::storage_heap::{
+    create_domain_database, get_domain_database, DOMAIN_DATA_ALLOCATOR,
+};
+
+pub const DOMAIN_ID: u64 = 1;
+
+/// Give the storage the data map of [`DOMAIN_ID`], once per process.
+pub fn init_storage() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        storage::init_data_allocator(DOMAIN_DATA_ALLOCATOR);
+        create_domain_database(DOMAIN_ID);
+        storage::init_database(get_domain_database(DOMAIN_ID).unwrap());
+    });
+}
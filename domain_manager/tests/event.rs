@@ -0,0 +1,123 @@
+//! Subscribe to the lifecycle events of domains.
+//!
+//! The subscriptions are shared by the tests of the process, so every test
+//! filters the events by a name of its own.
+
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use domain_manager::event::{
+    domain_unloaded, dropped_events, init_catch_unwind, move_domain_subscriptions, poll, publish,
+    remove_domain_subscriptions, subscribe, unsubscribe, EventError, EVENT_QUEUE_LEN,
+};
+use domain_meta::{
+    event::{DomainEvent, DomainEventKind, EventDelivery, EventFilter},
+    DomainTypeRaw,
+};
+
+fn event(kind: DomainEventKind, domain_id: u64, name: &str) -> DomainEvent {
+    DomainEvent::new(kind, DomainTypeRaw::BlkDeviceDomain, domain_id, name)
+}
+
+fn ignore(_: &DomainEvent) {}
+
+#[test]
+fn queue_gets_the_events_of_the_name() {
+    let queue = subscribe(1, EventFilter::Name("queue-blk"), EventDelivery::Queue);
+    publish(event(DomainEventKind::Created, 2, "queue-blk"));
+    publish(event(DomainEventKind::Created, 3, "queue-other"));
+    publish(event(DomainEventKind::Panicked, 2, "queue-blk"));
+    assert_eq!(
+        poll(queue),
+        Ok(Some(event(DomainEventKind::Created, 2, "queue-blk")))
+    );
+    assert_eq!(
+        poll(queue),
+        Ok(Some(event(DomainEventKind::Panicked, 2, "queue-blk")))
+    );
+    assert_eq!(poll(queue), Ok(None));
+    assert_eq!(dropped_events(queue), Ok(0));
+
+    unsubscribe(queue).unwrap();
+    assert_eq!(poll(queue), Err(EventError::NoSubscription));
+    assert_eq!(unsubscribe(queue), Err(EventError::NoSubscription));
+}
+
+#[test]
+fn callback_has_no_queue() {
+    let callback = subscribe(
+        1,
+        EventFilter::Name("callback-blk"),
+        EventDelivery::Callback(ignore),
+    );
+    assert_eq!(poll(callback), Err(EventError::NotQueue));
+    assert_eq!(dropped_events(callback), Err(EventError::NotQueue));
+    unsubscribe(callback).unwrap();
+}
+
+#[test]
+fn full_queue_drops_the_oldest() {
+    let queue = subscribe(1, EventFilter::Name("full-blk"), EventDelivery::Queue);
+    for domain_id in 0..EVENT_QUEUE_LEN as u64 + 2 {
+        publish(event(DomainEventKind::Created, domain_id, "full-blk"));
+    }
+    assert_eq!(dropped_events(queue), Ok(2));
+    assert_eq!(poll(queue).unwrap().unwrap().domain_id, 2);
+    unsubscribe(queue).unwrap();
+}
+
+static PANICS: AtomicUsize = AtomicUsize::new(0);
+
+fn panic_on_event(_: &DomainEvent) {
+    PANICS.fetch_add(1, Ordering::SeqCst);
+    panic!("callback panics");
+}
+
+#[test]
+fn panicking_callback_is_unsubscribed() {
+    init_catch_unwind(|f| catch_unwind(AssertUnwindSafe(f)).is_ok());
+    let callback = subscribe(
+        1,
+        EventFilter::Name("panic-blk"),
+        EventDelivery::Callback(panic_on_event),
+    );
+    publish(event(DomainEventKind::Created, 2, "panic-blk"));
+    publish(event(DomainEventKind::Created, 2, "panic-blk"));
+    assert_eq!(PANICS.load(Ordering::SeqCst), 1);
+    assert_eq!(unsubscribe(callback), Err(EventError::NoSubscription));
+}
+
+#[test]
+fn update_keeps_the_queues_of_the_domain() {
+    let queue = subscribe(20, EventFilter::Name("update-blk"), EventDelivery::Queue);
+    let callback = subscribe(
+        20,
+        EventFilter::Name("update-blk"),
+        EventDelivery::Callback(ignore),
+    );
+    publish(event(DomainEventKind::Created, 2, "update-blk"));
+    move_domain_subscriptions(20, 21);
+    assert_eq!(unsubscribe(callback), Err(EventError::NoSubscription));
+    assert_eq!(
+        poll(queue),
+        Ok(Some(event(DomainEventKind::Created, 2, "update-blk")))
+    );
+
+    remove_domain_subscriptions(21);
+    assert_eq!(poll(queue), Err(EventError::NoSubscription));
+}
+
+#[test]
+fn unloaded_domain_loses_its_subscriptions() {
+    let own = subscribe(30, EventFilter::Name("unload-blk"), EventDelivery::Queue);
+    let peer = subscribe(31, EventFilter::Name("unload-blk"), EventDelivery::Queue);
+    domain_unloaded(DomainTypeRaw::BlkDeviceDomain, 30, "unload-blk");
+    assert_eq!(poll(own), Err(EventError::NoSubscription));
+    assert_eq!(
+        poll(peer),
+        Ok(Some(event(DomainEventKind::Unloaded, 30, "unload-blk")))
+    );
+    unsubscribe(peer).unwrap();
+}
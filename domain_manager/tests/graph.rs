@@ -0,0 +1,98 @@
+//! Order the restarts of dependent domains.
+
+use domain_manager::graph::{
+    add_dependency, cascade_reload, set_cascade, CascadeError, DependencyGraph, GraphError,
+};
+
+fn position(order: &[String], name: &str) -> usize {
+    order.iter().position(|n| n == name).unwrap()
+}
+
+/// `cache` and `fatfs` use `blk`, `vfs` uses both of them, `net` is alone.
+fn graph() -> DependencyGraph {
+    let mut graph = DependencyGraph::new();
+    assert!(graph.add_dependency("cache", "blk"));
+    assert!(graph.add_dependency("fatfs", "blk"));
+    assert!(graph.add_dependency("vfs", "cache"));
+    assert!(graph.add_dependency("vfs", "fatfs"));
+    assert!(!graph.add_dependency("vfs", "fatfs"));
+    assert!(!graph.add_dependency("net", "net"));
+    graph
+}
+
+#[test]
+fn restart_order() {
+    let graph = graph();
+    let order = graph.restart_order("blk").unwrap();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0], "blk");
+    assert!(position(&order, "cache") < position(&order, "vfs"));
+    assert!(position(&order, "fatfs") < position(&order, "vfs"));
+
+    assert_eq!(graph.restart_order("vfs").unwrap(), ["vfs"]);
+    assert_eq!(graph.restart_order("net").unwrap(), ["net"]);
+}
+
+#[test]
+fn startup_order() {
+    let mut graph = graph();
+    graph.add_dependency("net", "blk");
+    let order = graph.startup_order().unwrap();
+    assert_eq!(order.len(), 5);
+    for (dependent, dependency) in [
+        ("cache", "blk"),
+        ("fatfs", "blk"),
+        ("vfs", "cache"),
+        ("vfs", "fatfs"),
+        ("net", "blk"),
+    ] {
+        assert!(position(&order, dependency) < position(&order, dependent));
+    }
+}
+
+#[test]
+fn cascade_targets() {
+    let mut graph = graph();
+    assert!(graph.cascade_targets("blk").unwrap().is_empty());
+    graph.set_cascade("cache", true);
+    graph.set_cascade("vfs", true);
+    // `fatfs` does not cascade, it keeps the old `blk`.
+    assert_eq!(graph.cascade_targets("blk").unwrap(), ["cache", "vfs"]);
+    assert_eq!(graph.cascade_targets("fatfs").unwrap(), ["vfs"]);
+}
+
+#[test]
+fn cycle() {
+    let mut graph = graph();
+    graph.add_dependency("blk", "vfs");
+    assert!(matches!(
+        graph.restart_order("blk"),
+        Err(GraphError::Cycle(names)) if names.len() == 4
+    ));
+    assert!(graph.startup_order().is_err());
+    graph.remove_domain("vfs");
+    assert!(graph.startup_order().is_ok());
+}
+
+#[test]
+fn cascade_reload_stops_at_the_first_error() {
+    add_dependency("cache", "blk");
+    add_dependency("vfs", "cache");
+    add_dependency("fatfs", "blk");
+    set_cascade("cache", true);
+    set_cascade("vfs", true);
+
+    let mut reloaded = Vec::new();
+    let count = cascade_reload("blk", |name| {
+        reloaded.push(name.to_string());
+        Ok::<_, ()>(())
+    });
+    assert_eq!(count.unwrap(), 2);
+    assert_eq!(reloaded, ["cache", "vfs"]);
+
+    let res = cascade_reload("blk", |name| if name == "vfs" { Err(7) } else { Ok(()) });
+    assert!(matches!(
+        res,
+        Err(CascadeError::Reload { domain, reloaded: 1, error: 7 }) if domain == "vfs"
+    ));
+}
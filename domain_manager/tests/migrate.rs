@@ -0,0 +1,89 @@
+//! Migrate the storage to a new domain and roll back an aborted update.
+
+#![feature(allocator_api)]
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{init_storage, DOMAIN_ID};
+use domain_manager::storage_heap::{
+    create_domain_database, get_domain_database, migrate_domain_database, move_domain_database,
+    rollback_domain_database, take_update_snapshot,
+};
+use storage::{
+    CustomStorge, DomainDataStorage, StorageEntry, StorageError, StorageKey, StorageResult,
+};
+
+struct ConfigV1 {
+    blocks: u32,
+}
+
+#[derive(Debug, PartialEq)]
+struct ConfigV2 {
+    blocks: u64,
+    cache: bool,
+}
+
+fn to_v2(old: &ConfigV1) -> StorageResult<ConfigV2> {
+    Ok(ConfigV2 {
+        blocks: old.blocks as u64,
+        cache: true,
+    })
+}
+
+fn fail(_: &ConfigV1) -> StorageResult<ConfigV2> {
+    Err(StorageError::MigrationFailed { from: 1, to: 2 })
+}
+
+#[test]
+fn migrations() {
+    init_storage();
+    let v1 = StorageKey::<ConfigV1>::with_version("config", 1);
+    let v2 = StorageKey::<ConfigV2>::with_version("config", 2);
+    storage::insert(&v1, ConfigV1 { blocks: 8 }).unwrap();
+
+    // a failed migration keeps the old value
+    unsafe { storage::register_migration(&v2, 1, fail) };
+    assert!(migrate_domain_database(DOMAIN_ID).is_err());
+    assert_eq!(storage::get(&v1).unwrap().unwrap().blocks, 8);
+
+    unsafe { storage::register_migration(&v2, 1, to_v2) };
+    assert_eq!(migrate_domain_database(DOMAIN_ID), Ok(1));
+    assert_eq!(
+        *storage::get(&v2).unwrap().unwrap(),
+        ConfigV2 {
+            blocks: 8,
+            cache: true
+        }
+    );
+    // the migrations are consumed
+    assert_eq!(migrate_domain_database(DOMAIN_ID), Ok(0));
+}
+
+#[test]
+fn aborted_update_rolls_back_to_the_moved_state() {
+    init_storage();
+    let (old_id, new_id) = (10, 11);
+    create_domain_database(old_id);
+    let data_map = get_domain_database(old_id).unwrap();
+    let key = StorageKey::<u64>::new("count");
+    let insert = |value: u64| {
+        let check = |entry: &StorageEntry| entry.check_type::<u64>();
+        let value = Arc::new_in(value, CustomStorge);
+        data_map.insert(key.name(), value, key.value_info(), &check)
+    };
+    insert(1).unwrap();
+
+    // the new domain writes to the storage in its main, before the update
+    move_domain_database(old_id, new_id);
+    insert(2).unwrap();
+    let snapshot = take_update_snapshot(new_id).unwrap();
+
+    move_domain_database(new_id, old_id);
+    drop(take_update_snapshot(old_id));
+    rollback_domain_database(old_id, snapshot);
+    let entry = data_map.get(key.name()).unwrap();
+    assert_eq!(*entry.downcast(&key).unwrap(), 1);
+    assert!(take_update_snapshot(new_id).is_none());
+}
@@ -0,0 +1,38 @@
+//! List the keys of the domain storage and split them into namespaces.
+
+mod common;
+
+use common::init_storage;
+use storage::StorageKey;
+
+#[test]
+fn namespaces_do_not_collide() {
+    init_storage();
+    let key = StorageKey::<u32>::new("inode");
+    let fs = storage::namespace("fs");
+    let cache = fs.namespace("cache");
+    fs.insert(&key, 1).unwrap();
+    cache.insert(&key, 2).unwrap();
+    storage::insert(&key, 3).unwrap();
+
+    assert_eq!(*fs.get(&key).unwrap().unwrap(), 1);
+    assert_eq!(*cache.get(&key).unwrap().unwrap(), 2);
+    assert_eq!(*storage::get(&key).unwrap().unwrap(), 3);
+    assert_eq!(cache.prefix(), "fs/cache/");
+    assert_eq!(fs.keys(), ["cache/inode", "inode"]);
+    assert_eq!(cache.keys(), ["inode"]);
+    assert_eq!(
+        storage::keys_with_prefix("fs/"),
+        ["fs/cache/inode", "fs/inode"]
+    );
+
+    let info = fs.metadata("inode").unwrap();
+    assert_eq!(info.key, "fs/inode");
+    assert_eq!(info.type_name, "u32");
+    // no clone of the value is held outside the storage
+    assert_eq!(info.strong_count, 1);
+
+    assert_eq!(*cache.remove(&key).unwrap().unwrap(), 2);
+    assert!(cache.keys().is_empty());
+    assert_eq!(*fs.get(&key).unwrap().unwrap(), 1);
+}
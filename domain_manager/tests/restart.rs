@@ -0,0 +1,109 @@
+//! Decide whether a failed domain is restarted.
+//!
+//! The timer of `domain_manager` reads a clock of the test thread, it stays
+//! at 0 unless the test sets it, so every failure is within the window of the
+//! policy.
+
+use std::cell::Cell;
+
+use domain_manager::{
+    init_timer,
+    restart::{is_failed, on_failure, policy, remove, reset, set_policy, RestartDecision},
+};
+use domain_meta::domain_info::RestartPolicy;
+
+thread_local! {
+    static NOW: Cell<u64> = const { Cell::new(0) };
+}
+
+fn now() -> u64 {
+    NOW.with(Cell::get)
+}
+
+fn set_now(ns: u64) {
+    init_timer(now);
+    NOW.with(|now| now.set(ns));
+}
+
+fn restart(delay_ns: u64) -> RestartDecision {
+    RestartDecision::Restart { delay_ns }
+}
+
+#[test]
+fn backoff_then_fail() {
+    set_policy(
+        "blk",
+        RestartPolicy::OnFailure {
+            max_restarts: 4,
+            window_ns: 1_000_000,
+            backoff_ns: 100,
+            max_backoff_ns: 500,
+        },
+    );
+    for delay_ns in [100, 200, 400, 500] {
+        assert_eq!(on_failure("blk"), restart(delay_ns));
+        assert!(!is_failed("blk"));
+    }
+    assert_eq!(on_failure("blk"), RestartDecision::Fail);
+    assert!(is_failed("blk"));
+    assert_eq!(on_failure("blk"), RestartDecision::Fail);
+
+    // An updated domain starts over with its policy.
+    reset("blk");
+    assert!(!is_failed("blk"));
+    assert_eq!(on_failure("blk"), restart(100));
+}
+
+#[test]
+fn backoff_saturates() {
+    set_policy(
+        "net",
+        RestartPolicy::OnFailure {
+            max_restarts: 100,
+            window_ns: u64::MAX,
+            backoff_ns: 3,
+            max_backoff_ns: u64::MAX,
+        },
+    );
+    for _ in 0..70 {
+        on_failure("net");
+    }
+    assert_eq!(on_failure("net"), restart(u64::MAX));
+}
+
+#[test]
+fn never_and_always() {
+    set_policy("fatfs", RestartPolicy::Never);
+    assert_eq!(on_failure("fatfs"), RestartDecision::Fail);
+    assert!(is_failed("fatfs"));
+
+    assert_eq!(policy("cache"), RestartPolicy::Always);
+    for _ in 0..10 {
+        assert_eq!(on_failure("cache"), restart(0));
+    }
+    remove("fatfs");
+    assert!(!is_failed("fatfs"));
+    assert_eq!(policy("fatfs"), RestartPolicy::Always);
+}
+
+#[test]
+fn failures_leave_the_window() {
+    set_policy(
+        "vfs",
+        RestartPolicy::OnFailure {
+            max_restarts: 2,
+            window_ns: 1000,
+            backoff_ns: 10,
+            max_backoff_ns: 1000,
+        },
+    );
+    set_now(0);
+    assert_eq!(on_failure("vfs"), restart(10));
+    set_now(500);
+    assert_eq!(on_failure("vfs"), restart(20));
+    // the failure at 0 is out of the window, one is left in it
+    set_now(1200);
+    assert_eq!(on_failure("vfs"), restart(20));
+    set_now(1400);
+    assert_eq!(on_failure("vfs"), RestartDecision::Fail);
+}
@@ -0,0 +1,97 @@
+//! Fill the statistics of `DomainInfo` and write them out.
+
+mod common;
+
+use common::{init_storage, DOMAIN_ID};
+use domain_manager::{graph::add_dependency, stats::refresh_domain_info};
+use domain_meta::{
+    domain_info::{
+        DomainDataInfo, DomainFileInfo, DomainInfo, RestartOutcome, RestartReason, RestartRecord,
+    },
+    DomainTypeRaw,
+};
+use storage::StorageKey;
+
+fn info() -> DomainInfo {
+    let mut info = DomainInfo::new();
+    info.ty_list.insert(
+        DomainTypeRaw::BlkDeviceDomain,
+        vec![DomainFileInfo::new("blk".into(), 4096)],
+    );
+    info.domain_list.insert(
+        DOMAIN_ID,
+        DomainDataInfo::new(
+            "blk-1".into(),
+            DomainTypeRaw::BlkDeviceDomain,
+            DomainFileInfo::new("blk".into(), 4096),
+            2_000_000,
+        ),
+    );
+    info
+}
+
+#[test]
+fn refresh_counts_storage_and_calls() {
+    init_storage();
+    storage::insert(&StorageKey::<u64>::new("stats/a"), 1).unwrap();
+    storage::insert(&StorageKey::<u64>::new("stats/b"), 2).unwrap();
+    add_dependency("cache-1", "blk-1");
+
+    let mut info = info();
+    refresh_domain_info(&mut info, |name| (name == "blk-1").then_some(7));
+    let data = &info.domain_list[&DOMAIN_ID];
+    assert_eq!(data.storage_entries, 2);
+    assert_eq!(data.calls, 7);
+    assert_eq!(data.private_pages, 0);
+    assert_eq!(data.shared_objects, 0);
+    assert_eq!(info.dependents_of("blk-1"), ["cache-1"]);
+
+    // a proxy which is not watched keeps the count
+    refresh_domain_info(&mut info, |_| None);
+    assert_eq!(info.domain_list[&DOMAIN_ID].calls, 7);
+}
+
+#[test]
+fn restarts_and_json() {
+    let mut info = info();
+    assert_eq!(info.domain_list[&DOMAIN_ID].uptime_ns(5_000_000), 3_000_000);
+    info.record_restart(
+        "blk-1",
+        RestartRecord {
+            time_ns: 3_000_000,
+            reason: RestartReason::Hung {
+                stalled_ns: 1_000_000,
+            },
+            outcome: RestartOutcome::Reloaded,
+        },
+    );
+    info.record_restart(
+        "blk-1",
+        RestartRecord {
+            time_ns: 4_000_000,
+            reason: RestartReason::Panicked,
+            outcome: RestartOutcome::Failed,
+        },
+    );
+    let data = &info.domain_list[&DOMAIN_ID];
+    assert_eq!(data.restart_count, 1);
+    assert!(data.failed);
+    assert_eq!(data.last_restart().unwrap().reason, RestartReason::Panicked);
+    assert!(info
+        .to_string()
+        .contains("  - Last restart: panicked at 4 ms, failed\n"));
+
+    assert_eq!(
+        info.to_json(),
+        concat!(
+            r#"{"types":[{"type":"BlkDeviceDomain","files":[{"name":"blk","size":4096}]}],"#,
+            r#""domains":[{"id":1,"name":"blk-1","type":"BlkDeviceDomain","#,
+            r#""file":{"name":"blk","size":4096},"created_ns":2000000,"panic_count":0,"#,
+            r#""restart_count":1,"failed":true,"restarts":["#,
+            r#"{"time_ns":3000000,"reason":"hung","stalled_ns":1000000,"outcome":"reloaded"},"#,
+            r#"{"time_ns":4000000,"reason":"panicked","outcome":"failed"}],"#,
+            r#""private_pages":0,"shared_objects":0,"shared_bytes":0,"storage_entries":0,"#,
+            r#""calls":0}],"dependencies":{}}"#,
+        )
+    );
+}
@@ -0,0 +1,75 @@
+//! Check the type and the version of the values in the domain storage.
+
+mod common;
+
+use common::init_storage;
+use storage::{StorageError, StorageKey};
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    blocks: u64,
+}
+
+#[test]
+fn type_hash_is_per_type() {
+    let config = StorageKey::<Config>::new("config").value_info();
+    assert_eq!(
+        config.type_hash,
+        StorageKey::<Config>::with_version("other", 3)
+            .value_info()
+            .type_hash
+    );
+    assert_ne!(
+        config.type_hash,
+        StorageKey::<u64>::new("config").value_info().type_hash
+    );
+    assert_ne!(
+        StorageKey::<u32>::new("n").value_info().type_hash,
+        StorageKey::<u64>::new("n").value_info().type_hash
+    );
+}
+
+#[test]
+fn get_checks_the_type_and_the_version() {
+    init_storage();
+    let key = StorageKey::<Config>::with_version("typed/config", 1);
+    assert!(storage::insert(&key, Config { blocks: 8 })
+        .unwrap()
+        .is_none());
+    assert_eq!(storage::get(&key).unwrap().unwrap().blocks, 8);
+
+    let other_type = StorageKey::<u64>::with_version("typed/config", 1);
+    assert!(matches!(
+        storage::get(&other_type),
+        Err(StorageError::TypeMismatch { .. })
+    ));
+    let other_version = StorageKey::<Config>::with_version("typed/config", 2);
+    assert_eq!(
+        storage::get(&other_version).unwrap_err(),
+        StorageError::VersionMismatch {
+            expected: 2,
+            found: 1
+        }
+    );
+}
+
+#[test]
+fn insert_keeps_a_value_of_another_type() {
+    init_storage();
+    let key = StorageKey::<Config>::new("typed/kept");
+    storage::insert(&key, Config { blocks: 1 }).unwrap();
+
+    let other_type = StorageKey::<u64>::new("typed/kept");
+    assert!(matches!(
+        storage::insert(&other_type, 2),
+        Err(StorageError::TypeMismatch { .. })
+    ));
+    assert_eq!(storage::get(&key).unwrap().unwrap().blocks, 1);
+
+    // a value of the same type is replaced and returned
+    let old = storage::insert(&key, Config { blocks: 3 })
+        .unwrap()
+        .unwrap();
+    assert_eq!(old.blocks, 1);
+    assert_eq!(storage::get(&key).unwrap().unwrap().blocks, 3);
+}
@@ -0,0 +1,71 @@
+//! Take the values out of the domain storage.
+
+mod common;
+
+use common::init_storage;
+use storage::{StorageError, StorageKey};
+
+#[test]
+fn remove_keeps_the_clones_valid() {
+    init_storage();
+    let key = StorageKey::<String>::new("remove");
+    storage::insert(&key, "blk".to_string()).unwrap();
+    let clone = storage::get(&key).unwrap().unwrap();
+    let removed = storage::remove(&key).unwrap().unwrap();
+    assert!(storage::get(&key).unwrap().is_none());
+    assert_eq!(*clone, "blk");
+    assert_eq!(*removed, "blk");
+}
+
+#[test]
+fn take_refuses_a_shared_value() {
+    init_storage();
+    let key = StorageKey::<String>::new("take");
+    storage::insert(&key, "blk".to_string()).unwrap();
+    let clone = storage::get(&key).unwrap().unwrap();
+    assert_eq!(
+        storage::take(&key),
+        Err(StorageError::Shared { strong_count: 2 })
+    );
+    drop(clone);
+    assert_eq!(storage::take(&key).unwrap().unwrap(), "blk");
+    assert!(storage::take(&key).unwrap().is_none());
+}
+
+#[test]
+fn take_when_unique_waits_for_the_clones() {
+    init_storage();
+    let key = StorageKey::<String>::new("take_when_unique");
+    storage::insert(&key, "blk".to_string()).unwrap();
+    let mut clones = vec![
+        storage::get(&key).unwrap().unwrap(),
+        storage::get(&key).unwrap().unwrap(),
+    ];
+    let mut counts = Vec::new();
+    let value = storage::take_when_unique(&key, 5, |strong_count| {
+        counts.push(strong_count);
+        clones.pop();
+        true
+    });
+    assert_eq!(value.unwrap().unwrap(), "blk");
+    assert_eq!(counts, [3, 2]);
+}
+
+#[test]
+fn take_when_unique_gives_up() {
+    init_storage();
+    let key = StorageKey::<String>::new("leaked");
+    storage::insert(&key, "blk".to_string()).unwrap();
+    let _clone = storage::get(&key).unwrap().unwrap();
+    let mut waits = 0;
+    let res = storage::take_when_unique(&key, 3, |_| {
+        waits += 1;
+        true
+    });
+    assert_eq!(res, Err(StorageError::Shared { strong_count: 2 }));
+    assert_eq!(waits, 2);
+    // a refused wait stops at once
+    let res = storage::take_when_unique(&key, 3, |_| false);
+    assert_eq!(res, Err(StorageError::Shared { strong_count: 2 }));
+    assert!(storage::get(&key).unwrap().is_some());
+}
@@ -0,0 +1,54 @@
+//! Apply the writes of a transaction at once or not at all.
+
+mod common;
+
+use common::init_storage;
+use storage::StorageKey;
+
+const FREE: StorageKey<u64> = StorageKey::new("free");
+const USED: StorageKey<u64> = StorageKey::new("used");
+
+#[test]
+fn commit_and_abort() {
+    init_storage();
+    storage::insert(&FREE, 10).unwrap();
+    storage::insert(&USED, 0).unwrap();
+
+    let res = storage::transaction(|tx| {
+        tx.insert(&FREE, 7);
+        tx.insert(&USED, 3);
+        // the staged writes are visible in the transaction only
+        assert_eq!(*tx.get(&FREE).unwrap().unwrap(), 7);
+        assert_eq!(*storage::get(&FREE).unwrap().unwrap(), 10);
+        Ok::<_, ()>(())
+    });
+    assert!(res.is_ok());
+    assert_eq!(*storage::get(&FREE).unwrap().unwrap(), 7);
+    assert_eq!(*storage::get(&USED).unwrap().unwrap(), 3);
+
+    let res = storage::transaction(|tx| {
+        tx.remove(&FREE);
+        tx.insert(&USED, 10);
+        assert!(tx.get(&FREE).unwrap().is_none());
+        Err("out of space")
+    });
+    assert_eq!(res, Err::<(), _>("out of space"));
+    assert_eq!(*storage::get(&FREE).unwrap().unwrap(), 7);
+    assert_eq!(*storage::get(&USED).unwrap().unwrap(), 3);
+}
+
+#[test]
+fn namespace_transaction_writes_the_namespaced_keys() {
+    init_storage();
+    let fs = storage::namespace("fs");
+    fs.insert(&FREE, 1).unwrap();
+    let res = fs.transaction(|tx| {
+        assert_eq!(*tx.get(&FREE).unwrap().unwrap(), 1);
+        tx.insert(&FREE, 2);
+        assert!(tx.is_staged("free"));
+        Ok::<_, ()>(())
+    });
+    assert!(res.is_ok());
+    assert_eq!(*fs.get(&FREE).unwrap().unwrap(), 2);
+    assert_eq!(storage::keys_with_prefix("fs/"), ["fs/free"]);
+}
@@ -0,0 +1,206 @@
+//! Detect hung and unhealthy domains and recover them.
+//!
+//! The timer of `domain_manager` reads a clock of the test thread, and every
+//! test watches domains with names of its own, so the restart policies and
+//! records of the tests do not mix.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use domain_manager::{
+    init_timer,
+    restart::set_policy,
+    watchdog::{CallWatch, Supervised, Supervisor},
+};
+use domain_meta::{
+    domain_info::{RestartOutcome, RestartPolicy, RestartReason, RestartRecord},
+    AlienError, AlienResult,
+};
+
+thread_local! {
+    static NOW: Cell<u64> = const { Cell::new(0) };
+}
+
+fn now() -> u64 {
+    NOW.with(Cell::get)
+}
+
+fn set_now(ns: u64) {
+    init_timer(now);
+    NOW.with(|now| now.set(ns));
+}
+
+/// A proxy whose calls are entered by the test.
+#[derive(Default)]
+struct Proxy {
+    watch: CallWatch,
+    health: Option<AlienError>,
+    empty: AtomicUsize,
+}
+
+impl Supervised for Proxy {
+    fn stalled_ns(&self) -> u64 {
+        self.watch.oldest_ns()
+    }
+
+    fn abandon_calls(&self, age_ns: u64) -> usize {
+        self.watch.abandon(age_ns)
+    }
+
+    fn calls(&self) -> u64 {
+        self.watch.calls()
+    }
+
+    fn health_check(&self) -> AlienResult<()> {
+        self.health.map_or(Ok(()), Err)
+    }
+
+    fn replace_with_empty(&self) -> AlienResult<()> {
+        self.empty.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+static RELOADS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static RECORDS: Mutex<Vec<(String, RestartRecord)>> = Mutex::new(Vec::new());
+
+fn reload(name: &str) -> AlienResult<()> {
+    RELOADS.lock().unwrap().push(name.into());
+    Ok(())
+}
+
+fn record(name: &str, record: RestartRecord) {
+    RECORDS.lock().unwrap().push((name.into(), record));
+}
+
+fn reloads(name: &str) -> usize {
+    RELOADS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|n| *n == name)
+        .count()
+}
+
+fn records(name: &str) -> Vec<RestartRecord> {
+    RECORDS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(n, _)| n == name)
+        .map(|(_, record)| *record)
+        .collect()
+}
+
+#[test]
+fn abandoned_calls_are_counted_per_domain() {
+    let watch = CallWatch::new();
+    set_now(100);
+    let stuck = watch.enter();
+    set_now(1100);
+    let fresh = watch.enter();
+    assert_eq!(watch.calls(), 2);
+    assert_eq!(watch.oldest_ns(), 1000);
+
+    assert_eq!(watch.abandon(500), 1);
+    assert_eq!(watch.oldest_ns(), 0);
+    assert_eq!(watch.abandoned(), 1);
+    assert_eq!(watch.abandoned_in_domain(), 1);
+    watch.exit(fresh);
+
+    // the domain is replaced, the stuck call still runs the old one
+    assert_eq!(watch.retire(), 1);
+    assert_eq!(watch.abandoned_in_domain(), 0);
+    assert_eq!(watch.abandoned(), 1);
+    watch.exit(stuck);
+    assert_eq!(watch.abandoned(), 0);
+    assert_eq!(watch.abandoned_in_domain(), 0);
+}
+
+#[test]
+fn hung_domain_is_reloaded_once() {
+    let supervisor = Supervisor::new(reload, record);
+    let proxy = Arc::new(Proxy::default());
+    set_now(100);
+    supervisor.watch("hung-blk", proxy.clone());
+    assert!(supervisor.set_deadline("hung-blk", 1000));
+    proxy.watch.enter();
+    assert_eq!(supervisor.calls("hung-blk"), Some(1));
+
+    set_now(1100);
+    assert_eq!(supervisor.check_stalls(), 0);
+    set_now(2100);
+    assert_eq!(supervisor.check_stalls(), 1);
+    assert_eq!(reloads("hung-blk"), 1);
+    assert_eq!(
+        records("hung-blk"),
+        [RestartRecord {
+            time_ns: 2100,
+            reason: RestartReason::Hung { stalled_ns: 2000 },
+            outcome: RestartOutcome::Reloaded,
+        }]
+    );
+
+    // the stuck call is abandoned and is no new hang
+    set_now(10_000);
+    assert_eq!(supervisor.check_stalls(), 0);
+    assert_eq!(reloads("hung-blk"), 1);
+    assert!(supervisor.unwatch("hung-blk"));
+    assert!(!supervisor.set_deadline("hung-blk", 1000));
+}
+
+#[test]
+fn hung_domain_is_reloaded_after_its_backoff() {
+    set_policy(
+        "backoff-blk",
+        RestartPolicy::OnFailure {
+            max_restarts: 4,
+            window_ns: u64::MAX,
+            backoff_ns: 500,
+            max_backoff_ns: 500,
+        },
+    );
+    let supervisor = Supervisor::new(reload, record);
+    let proxy = Arc::new(Proxy::default());
+    set_now(0);
+    supervisor.watch("backoff-blk", proxy.clone());
+    supervisor.set_deadline("backoff-blk", 1000);
+    proxy.watch.enter();
+
+    set_now(2000);
+    assert_eq!(supervisor.check_stalls(), 0);
+    set_now(2499);
+    assert_eq!(supervisor.check_stalls(), 0);
+    assert_eq!(reloads("backoff-blk"), 0);
+    set_now(2500);
+    assert_eq!(supervisor.check_stalls(), 1);
+    assert_eq!(reloads("backoff-blk"), 1);
+}
+
+#[test]
+fn unhealthy_domain_is_failed_by_its_policy() {
+    set_policy("sick-blk", RestartPolicy::Never);
+    let supervisor = Supervisor::new(reload, record);
+    let proxy = Arc::new(Proxy {
+        health: Some(AlienError::EIO),
+        ..Default::default()
+    });
+    set_now(0);
+    supervisor.watch("sick-blk", proxy.clone());
+    assert_eq!(supervisor.check_health(), 0);
+    assert_eq!(reloads("sick-blk"), 0);
+    assert_eq!(proxy.empty.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        records("sick-blk"),
+        [RestartRecord {
+            time_ns: 0,
+            reason: RestartReason::Unhealthy(AlienError::EIO),
+            outcome: RestartOutcome::Failed,
+        }]
+    );
+}
@@ -0,0 +1,377 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    vec::Vec,
+};
+use core::fmt::{Display, Write};
+
+use crate::{AlienError, DomainTypeRaw};
+
+/// The number of restarts [`DomainDataInfo::restart_history`] keeps, the
+/// oldest are dropped first.
+pub const RESTART_HISTORY_LEN: usize = 16;
+
+#[derive(Debug, Default)]
+pub struct DomainInfo {
+    pub ty_list: BTreeMap<DomainTypeRaw, Vec<DomainFileInfo>>,
+    pub domain_list: BTreeMap<u64, DomainDataInfo>,
+    /// The domains every domain looked up by name, a copy of the graph in
+    /// `domain_manager::graph` which the kernel refreshes.
+    pub dependencies: BTreeMap<String, Vec<String>>,
+}
+
+impl DomainInfo {
+    pub fn new() -> Self {
+        Self {
+            ty_list: BTreeMap::new(),
+            domain_list: BTreeMap::new(),
+            dependencies: BTreeMap::new(),
+        }
+    }
+
+    /// Return the domains `name` depends on.
+    pub fn dependencies_of(&self, name: &str) -> &[String] {
+        self.dependencies
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return the domains which depend on `name`.
+    pub fn dependents_of(&self, name: &str) -> Vec<&str> {
+        self.dependencies
+            .iter()
+            .filter(|(_, deps)| deps.iter().any(|d| d == name))
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+
+    /// Record a restart of the domains with the name.
+    pub fn record_restart(&mut self, name: &str, record: RestartRecord) {
+        for data in self.domain_list.values_mut().filter(|d| d.name == name) {
+            match record.outcome {
+                RestartOutcome::Reloaded => data.restart_count += 1,
+                RestartOutcome::Failed => data.failed = true,
+            }
+            if data.restart_history.len() == RESTART_HISTORY_LEN {
+                data.restart_history.pop_front();
+            }
+            data.restart_history.push_back(record);
+        }
+    }
+
+    /// Return the info as JSON.
+    ///
+    /// The keys are stable, new keys may be added. Times are nanoseconds
+    /// since boot, types and errors are their names.
+    ///
+    /// ```text
+    /// {"types":[{"type":..,"files":[{"name":..,"size":..}]}],
+    ///  "domains":[{"id":..,"name":..,"type":..,"file":{"name":..,"size":..},
+    ///    "created_ns":..,"panic_count":..,"restart_count":..,"failed":..,
+    ///    "restarts":[{"time_ns":..,"reason":..,"stalled_ns":..,
+    ///      "error":..,"outcome":..}],
+    ///    "private_pages":..,"shared_objects":..,"shared_bytes":..,
+    ///    "storage_entries":..,"calls":..}],
+    ///  "dependencies":{"name":["name"]}}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out).unwrap();
+        out
+    }
+
+    /// Write the JSON of [`DomainInfo::to_json`].
+    pub fn write_json(&self, w: &mut dyn Write) -> core::fmt::Result {
+        w.write_str("{\"types\":[")?;
+        for (i, (ty, files)) in self.ty_list.iter().enumerate() {
+            comma(w, i)?;
+            write!(w, "{{\"type\":\"{:?}\",\"files\":[", ty)?;
+            for (j, file) in files.iter().enumerate() {
+                comma(w, j)?;
+                file.write_json(w)?;
+            }
+            w.write_str("]}")?;
+        }
+        w.write_str("],\"domains\":[")?;
+        for (i, (id, data)) in self.domain_list.iter().enumerate() {
+            comma(w, i)?;
+            data.write_json(*id, w)?;
+        }
+        w.write_str("],\"dependencies\":{")?;
+        for (i, (name, deps)) in self.dependencies.iter().enumerate() {
+            comma(w, i)?;
+            write_str(w, name)?;
+            w.write_str(":[")?;
+            for (j, dep) in deps.iter().enumerate() {
+                comma(w, j)?;
+                write_str(w, dep)?;
+            }
+            w.write_str("]")?;
+        }
+        w.write_str("}}")
+    }
+}
+
+fn comma(w: &mut dyn Write, index: usize) -> core::fmt::Result {
+    if index != 0 {
+        w.write_char(',')?;
+    }
+    Ok(())
+}
+
+/// Write a JSON string.
+fn write_str(w: &mut dyn Write, s: &str) -> core::fmt::Result {
+    w.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => w.write_char(c)?,
+        }
+    }
+    w.write_char('"')
+}
+
+impl Display for DomainInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (ty, files) in self.ty_list.iter() {
+            writeln!(f, "Domain type: {}", ty)?;
+            for file in files.iter() {
+                writeln!(f, "  - {}: {} bytes", file.name, file.size)?;
+            }
+        }
+        for (id, data) in self.domain_list.iter() {
+            writeln!(f, "Domain ID: {}", id)?;
+            writeln!(f, "  - Name: {}", data.name)?;
+            writeln!(f, "  - Type: {:?}", data.ty)?;
+            writeln!(f, "  - Created: {} ms", data.created_ns / 1_000_000)?;
+            writeln!(f, "  - Panic count: {}", data.panic_count)?;
+            writeln!(f, "  - Restart count: {}", data.restart_count)?;
+            if let Some(record) = data.last_restart() {
+                writeln!(f, "  - Last restart: {}", record)?;
+            }
+            if data.failed {
+                writeln!(f, "  - Failed permanently")?;
+            }
+            writeln!(f, "  - File: {}", data.file_info.name)?;
+            writeln!(f, "  - Size: {} bytes", data.file_info.size)?;
+            writeln!(f, "  - Private pages: {}", data.private_pages)?;
+            writeln!(
+                f,
+                "  - Shared heap: {} objects, {} bytes",
+                data.shared_objects, data.shared_bytes
+            )?;
+            writeln!(f, "  - Storage entries: {}", data.storage_entries)?;
+            writeln!(f, "  - Calls: {}", data.calls)?;
+        }
+        for (name, deps) in self.dependencies.iter() {
+            if !deps.is_empty() {
+                writeln!(f, "Domain {} depends on: {}", name, deps.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DomainDataInfo {
+    pub name: String,
+    pub ty: DomainTypeRaw,
+    pub panic_count: usize,
+    /// The number of times the domain was reloaded after a failure.
+    pub restart_count: usize,
+    /// The last restarts, the newest at the back.
+    pub restart_history: VecDeque<RestartRecord>,
+    /// The restart policy gave up, the proxy runs the empty domain.
+    pub failed: bool,
+    pub file_info: DomainFileInfo,
+    /// The time the domain was created in nanoseconds since boot.
+    pub created_ns: u64,
+    /// The pages the domain allocated for its private heap.
+    pub private_pages: usize,
+    /// The shared heap objects the domain owns and their size.
+    pub shared_objects: usize,
+    pub shared_bytes: usize,
+    /// The entries of the domain storage.
+    pub storage_entries: usize,
+    /// The calls into the domain through its proxy.
+    pub calls: u64,
+}
+
+impl DomainDataInfo {
+    pub fn new(
+        name: String,
+        ty: DomainTypeRaw,
+        file_info: DomainFileInfo,
+        created_ns: u64,
+    ) -> Self {
+        Self {
+            name,
+            ty,
+            panic_count: 0,
+            restart_count: 0,
+            restart_history: VecDeque::new(),
+            failed: false,
+            file_info,
+            created_ns,
+            private_pages: 0,
+            shared_objects: 0,
+            shared_bytes: 0,
+            storage_entries: 0,
+            calls: 0,
+        }
+    }
+
+    pub fn last_restart(&self) -> Option<&RestartRecord> {
+        self.restart_history.back()
+    }
+
+    /// Return the time since the domain was created.
+    pub fn uptime_ns(&self, now_ns: u64) -> u64 {
+        now_ns.saturating_sub(self.created_ns)
+    }
+
+    fn write_json(&self, id: u64, w: &mut dyn Write) -> core::fmt::Result {
+        write!(w, "{{\"id\":{},\"name\":", id)?;
+        write_str(w, &self.name)?;
+        write!(w, ",\"type\":\"{:?}\",\"file\":", self.ty)?;
+        self.file_info.write_json(w)?;
+        write!(
+            w,
+            ",\"created_ns\":{},\"panic_count\":{},\"restart_count\":{},\"failed\":{},\"restarts\":[",
+            self.created_ns, self.panic_count, self.restart_count, self.failed
+        )?;
+        for (i, record) in self.restart_history.iter().enumerate() {
+            comma(w, i)?;
+            record.write_json(w)?;
+        }
+        write!(
+            w,
+            "],\"private_pages\":{},\"shared_objects\":{},\"shared_bytes\":{},\"storage_entries\":{},\"calls\":{}}}",
+            self.private_pages, self.shared_objects, self.shared_bytes, self.storage_entries, self.calls
+        )
+    }
+}
+
+/// What the kernel does when a domain panics or the supervisor finds it
+/// hung or unhealthy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// Reload the domain every time.
+    #[default]
+    Always,
+    /// Fail the domain at the first failure.
+    Never,
+    /// Reload the domain up to `max_restarts` times within `window_ns`, fail
+    /// it at the next failure.
+    ///
+    /// The n-th reload within the window waits `backoff_ns * 2^(n-1)`, at
+    /// most `max_backoff_ns`.
+    OnFailure {
+        max_restarts: u32,
+        window_ns: u64,
+        backoff_ns: u64,
+        max_backoff_ns: u64,
+    },
+}
+
+/// What the kernel did after a failure of a domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartOutcome {
+    Reloaded,
+    /// The domain is replaced by its empty implementation.
+    Failed,
+}
+
+impl RestartOutcome {
+    fn name(&self) -> &'static str {
+        match self {
+            RestartOutcome::Reloaded => "reloaded",
+            RestartOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// Why a domain was restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartReason {
+    /// A call into the domain panicked.
+    Panicked,
+    /// The oldest call in flight was this old.
+    Hung { stalled_ns: u64 },
+    /// `Basic::health_check` returned the error.
+    Unhealthy(AlienError),
+}
+
+impl Display for RestartReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RestartReason::Panicked => write!(f, "panicked"),
+            RestartReason::Hung { stalled_ns } => {
+                write!(f, "hung for {} ms", stalled_ns / 1_000_000)
+            }
+            RestartReason::Unhealthy(e) => write!(f, "failed its health check: {:?}", e),
+        }
+    }
+}
+
+/// A restart in [`DomainDataInfo::restart_history`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartRecord {
+    /// The time of the restart in nanoseconds since boot.
+    pub time_ns: u64,
+    pub reason: RestartReason,
+    pub outcome: RestartOutcome,
+}
+
+impl RestartRecord {
+    fn write_json(&self, w: &mut dyn Write) -> core::fmt::Result {
+        write!(w, "{{\"time_ns\":{},\"reason\":", self.time_ns)?;
+        match self.reason {
+            RestartReason::Panicked => w.write_str("\"panicked\"")?,
+            RestartReason::Hung { stalled_ns } => {
+                write!(w, "\"hung\",\"stalled_ns\":{}", stalled_ns)?
+            }
+            RestartReason::Unhealthy(e) => write!(w, "\"unhealthy\",\"error\":\"{:?}\"", e)?,
+        }
+        write!(w, ",\"outcome\":\"{}\"}}", self.outcome.name())
+    }
+}
+
+impl Display for RestartRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} at {} ms, {}",
+            self.reason,
+            self.time_ns / 1_000_000,
+            self.outcome.name()
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DomainFileInfo {
+    pub name: String,
+    pub size: usize,
+}
+
+impl DomainFileInfo {
+    pub fn new(name: String, size: usize) -> Self {
+        Self { name, size }
+    }
+    pub fn from((name, size): (String, usize)) -> Self {
+        Self { name, size }
+    }
+
+    fn write_json(&self, w: &mut dyn Write) -> core::fmt::Result {
+        w.write_str("{\"name\":")?;
+        write_str(w, &self.name)?;
+        write!(w, ",\"size\":{}}}", self.size)
+    }
+}
@@ -0,0 +1,99 @@
+//! The lifecycle events of domains.
+//!
+//! A domain subscribes with [`EventFilter`] and [`EventDelivery`] and gets a
+//! [`DomainEvent`] when a matching domain changes, e.g. to resolve a handle
+//! from `get_domain` again after the domain is updated. The events are
+//! copied and carry no heap memory, so they can cross the domain boundary.
+
+use core::fmt::{Display, Formatter};
+
+use crate::DomainTypeRaw;
+
+/// The length of the name in a [`DomainEvent`], like the identifier of a
+/// created domain.
+pub const EVENT_NAME_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainEventKind {
+    /// A domain image is registered with a name.
+    Registered,
+    /// An instance of a domain is created.
+    Created,
+    /// A call into the domain panicked.
+    Panicked,
+    /// The domain is reloaded from its image after a panic.
+    Reloaded,
+    /// The domain is replaced by another image.
+    Updated,
+    /// The domain is unloaded.
+    Unloaded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DomainEvent {
+    pub kind: DomainEventKind,
+    pub ty: DomainTypeRaw,
+    /// The id of the domain, the id of the new domain for `Updated`.
+    pub domain_id: u64,
+    name: [u8; EVENT_NAME_LEN],
+    name_len: u8,
+}
+
+impl DomainEvent {
+    /// The name is truncated to [`EVENT_NAME_LEN`] bytes.
+    pub fn new(kind: DomainEventKind, ty: DomainTypeRaw, domain_id: u64, name: &str) -> Self {
+        let mut len = name.len().min(EVENT_NAME_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut buf = [0; EVENT_NAME_LEN];
+        buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self {
+            kind,
+            ty,
+            domain_id,
+            name: buf,
+            name_len: len as u8,
+        }
+    }
+
+    /// Return the name the domain is registered or created with.
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or_default()
+    }
+}
+
+impl Display for DomainEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:?} {}({}) {:?}",
+            self.kind,
+            self.name(),
+            self.domain_id,
+            self.ty
+        )
+    }
+}
+
+/// The domains a subscription gets the events of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFilter<'a> {
+    /// The domain with the name, the name is copied by the kernel.
+    Name(&'a str),
+    /// The domains of the type.
+    Type(DomainTypeRaw),
+    All,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EventDelivery {
+    /// Call the function when the event happens.
+    ///
+    /// It runs on the task which causes the event, it must not block or call
+    /// the domain which the event is about.
+    Callback(fn(&DomainEvent)),
+    /// Keep the events until the domain polls them. The oldest events are
+    /// dropped if the domain does not poll.
+    Queue,
+}
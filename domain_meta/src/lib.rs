@@ -0,0 +1,98 @@
+//! The types the kernel and the domains both use to describe domains.
+//!
+//! `corelib` and `interface` re-export them for the domains, the kernel side
+//! crates like `domain_manager` use them without the domain interfaces.
+#![no_std]
+
+extern crate alloc;
+
+use core::fmt::Display;
+
+use pconst::LinuxErrno;
+
+pub mod domain_info;
+pub mod event;
+
+pub type AlienError = LinuxErrno;
+pub type AlienResult<T> = Result<T, LinuxErrno>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd)]
+pub enum DomainTypeRaw {
+    FsDomain = 1,
+    BlkDeviceDomain = 2,
+    CacheBlkDeviceDomain = 3,
+    RtcDomain = 4,
+    GpuDomain = 5,
+    InputDomain = 6,
+    VfsDomain = 7,
+    UartDomain = 8,
+    PLICDomain = 9,
+    TaskDomain = 10,
+    SysCallDomain = 11,
+    ShadowBlockDomain = 12,
+    BufUartDomain = 13,
+    NetDeviceDomain = 14,
+    BufInputDomain = 15,
+    EmptyDeviceDomain = 16,
+    DevFsDomain = 17,
+    SchedulerDomain = 18,
+    LogDomain = 19,
+    NetDomain = 20,
+}
+
+impl Display for DomainTypeRaw {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DomainTypeRaw::FsDomain => write!(f, "FsDomain"),
+            DomainTypeRaw::BlkDeviceDomain => write!(f, "BlkDeviceDomain"),
+            DomainTypeRaw::CacheBlkDeviceDomain => write!(f, "CacheBlkDeviceDomain"),
+            DomainTypeRaw::RtcDomain => write!(f, "RtcDomain"),
+            DomainTypeRaw::GpuDomain => write!(f, "GpuDomain"),
+            DomainTypeRaw::InputDomain => write!(f, "InputDomain"),
+            DomainTypeRaw::VfsDomain => write!(f, "VfsDomain"),
+            DomainTypeRaw::UartDomain => write!(f, "UartDomain"),
+            DomainTypeRaw::PLICDomain => write!(f, "PLICDomain"),
+            DomainTypeRaw::TaskDomain => write!(f, "TaskDomain"),
+            DomainTypeRaw::SysCallDomain => write!(f, "SysCallDomain"),
+            DomainTypeRaw::ShadowBlockDomain => write!(f, "ShadowBlockDomain"),
+            DomainTypeRaw::BufUartDomain => write!(f, "BufUartDomain"),
+            DomainTypeRaw::NetDeviceDomain => write!(f, "NetDeviceDomain"),
+            DomainTypeRaw::BufInputDomain => write!(f, "BufInputDomain"),
+            DomainTypeRaw::EmptyDeviceDomain => write!(f, "EmptyDeviceDomain"),
+            DomainTypeRaw::DevFsDomain => write!(f, "DevFsDomain"),
+            DomainTypeRaw::SchedulerDomain => write!(f, "SchedulerDomain"),
+            DomainTypeRaw::LogDomain => write!(f, "LogDomain"),
+            DomainTypeRaw::NetDomain => write!(f, "NetDomain"),
+        }
+    }
+}
+
+impl TryFrom<u8> for DomainTypeRaw {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(DomainTypeRaw::FsDomain),
+            2 => Ok(DomainTypeRaw::BlkDeviceDomain),
+            3 => Ok(DomainTypeRaw::CacheBlkDeviceDomain),
+            4 => Ok(DomainTypeRaw::RtcDomain),
+            5 => Ok(DomainTypeRaw::GpuDomain),
+            6 => Ok(DomainTypeRaw::InputDomain),
+            7 => Ok(DomainTypeRaw::VfsDomain),
+            8 => Ok(DomainTypeRaw::UartDomain),
+            9 => Ok(DomainTypeRaw::PLICDomain),
+            10 => Ok(DomainTypeRaw::TaskDomain),
+            11 => Ok(DomainTypeRaw::SysCallDomain),
+            12 => Ok(DomainTypeRaw::ShadowBlockDomain),
+            13 => Ok(DomainTypeRaw::BufUartDomain),
+            14 => Ok(DomainTypeRaw::NetDeviceDomain),
+            15 => Ok(DomainTypeRaw::BufInputDomain),
+            16 => Ok(DomainTypeRaw::EmptyDeviceDomain),
+            17 => Ok(DomainTypeRaw::DevFsDomain),
+            18 => Ok(DomainTypeRaw::SchedulerDomain),
+            19 => Ok(DomainTypeRaw::LogDomain),
+            20 => Ok(DomainTypeRaw::NetDomain),
+            _ => Err(()),
+        }
+    }
+}
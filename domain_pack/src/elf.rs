@@ -0,0 +1,75 @@
+//! Add a section to an ELF64 little endian file.
+//!
+//! The section data, a new `.shstrtab` and a new section header table are
+//! appended to the file, the old string table and section headers stay in
+//! place but are no longer referenced.
+
+const SHDR_SIZE: usize = 64;
+const SHT_PROGBITS: u32 = 1;
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn align8(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(8), 0);
+}
+
+/// Return a copy of the ELF file with a non-allocated section `name`.
+pub fn add_section(elf: &[u8], name: &str, content: &[u8]) -> Result<Vec<u8>, String> {
+    if elf.len() < 64 || elf[4] != 2 || elf[5] != 1 {
+        return Err("only 64-bit little endian elf files are supported".into());
+    }
+    let shoff = u64_at(elf, 0x28) as usize;
+    let shentsize = u16_at(elf, 0x3a) as usize;
+    let shnum = u16_at(elf, 0x3c) as usize;
+    let shstrndx = u16_at(elf, 0x3e) as usize;
+    if shentsize != SHDR_SIZE || shnum == 0 || shstrndx >= shnum || shnum >= 0xff00 {
+        return Err("unsupported section header table".into());
+    }
+    let headers = elf
+        .get(shoff..shoff + shnum * SHDR_SIZE)
+        .ok_or("section header table is out of the file")?;
+    let strtab_header = &headers[shstrndx * SHDR_SIZE..(shstrndx + 1) * SHDR_SIZE];
+    let strtab_offset = u64_at(strtab_header, 0x18) as usize;
+    let strtab_size = u64_at(strtab_header, 0x20) as usize;
+    let strtab = elf
+        .get(strtab_offset..strtab_offset + strtab_size)
+        .ok_or(".shstrtab is out of the file")?;
+
+    let mut out = elf.to_vec();
+    align8(&mut out);
+    let content_offset = out.len();
+    out.extend_from_slice(content);
+
+    let new_strtab_offset = out.len();
+    out.extend_from_slice(strtab);
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    let new_strtab_size = out.len() - new_strtab_offset;
+
+    align8(&mut out);
+    let new_shoff = out.len();
+    out.extend_from_slice(headers);
+    let strtab_entry = new_shoff + shstrndx * SHDR_SIZE;
+    out[strtab_entry + 0x18..strtab_entry + 0x20]
+        .copy_from_slice(&(new_strtab_offset as u64).to_le_bytes());
+    out[strtab_entry + 0x20..strtab_entry + 0x28]
+        .copy_from_slice(&(new_strtab_size as u64).to_le_bytes());
+
+    let mut header = [0u8; SHDR_SIZE];
+    header[0x00..0x04].copy_from_slice(&(strtab_size as u32).to_le_bytes());
+    header[0x04..0x08].copy_from_slice(&SHT_PROGBITS.to_le_bytes());
+    header[0x18..0x20].copy_from_slice(&(content_offset as u64).to_le_bytes());
+    header[0x20..0x28].copy_from_slice(&(content.len() as u64).to_le_bytes());
+    header[0x30..0x38].copy_from_slice(&8u64.to_le_bytes());
+    out.extend_from_slice(&header);
+
+    out[0x28..0x30].copy_from_slice(&(new_shoff as u64).to_le_bytes());
+    out[0x3c..0x3e].copy_from_slice(&((shnum + 1) as u16).to_le_bytes());
+    Ok(out)
+}
@@ -0,0 +1,309 @@
+//! Check a domain ELF file against the loader and package it.
+//!
+//! ```text
+//! domain-pack target/fs.elf -o fs.bin --ty FsDomain --dep vfs --compress zstd --sign key.bin
+//! ```
+mod elf;
+
+use std::{fmt::Write as _, fs, io::Write, path::PathBuf, process::exit};
+
+use interface::{abi_fingerprint, DomainTypeRaw, INTERFACE_VERSION};
+use loader::{
+    check_image_for, decompress, image_hash, verify_image, ImageInfo, Machine, Manifest, MmioRange,
+    VerifyPolicy, SIGNATURE_MAGIC,
+};
+use manifest::MANIFEST_SECTION;
+
+const USAGE: &str = "\
+usage: domain-pack <domain.elf> [options]
+
+options:
+    -o, --output <file>        write the image, the ELF file is only checked without it
+    --machine <riscv64|x86_64> the machine of the kernel, riscv64 by default
+    --ty <type>                the domain type, e.g. FsDomain, a manifest is injected
+                               if the ELF file has none
+    --dep <name>               a domain this domain needs
+    --mmio <start:size>        a MMIO range the domain needs
+    --irq <irq>                an interrupt the domain needs
+    --domain-version <version> the domain version in the injected manifest
+    --compress <lz4|zstd>      compress the image
+    --sign <key>               sign the image with the Ed25519 key in the file, the
+                               file holds the 32 bytes seed or the 64 bytes key pair";
+
+#[derive(Debug, Clone, Copy)]
+enum Compress {
+    Lz4,
+    Zstd,
+}
+
+#[derive(Debug)]
+struct Args {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    machine: Machine,
+    ty: Option<String>,
+    deps: Vec<String>,
+    mmio: Vec<MmioRange>,
+    irqs: Vec<u32>,
+    domain_version: String,
+    compress: Option<Compress>,
+    sign: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut input = None;
+        let mut res = Args {
+            input: PathBuf::new(),
+            output: None,
+            machine: Machine::RISC_V,
+            ty: None,
+            deps: vec![],
+            mmio: vec![],
+            irqs: vec![],
+            domain_version: "0.1.0".into(),
+            compress: None,
+            sign: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "-o" | "--output" => res.output = Some(value()?.into()),
+                "--machine" => {
+                    res.machine = match value()?.as_str() {
+                        "riscv64" => Machine::RISC_V,
+                        "x86_64" => Machine::X86_64,
+                        m => return Err(format!("unknown machine {}", m)),
+                    }
+                }
+                "--ty" => res.ty = Some(value()?),
+                "--dep" => res.deps.push(value()?),
+                "--mmio" => res.mmio.push(parse_mmio(&value()?)?),
+                "--irq" => res.irqs.push(parse_int(&value()?)? as u32),
+                "--domain-version" => res.domain_version = value()?,
+                "--compress" => {
+                    res.compress = match value()?.as_str() {
+                        "lz4" => Some(Compress::Lz4),
+                        "zstd" => Some(Compress::Zstd),
+                        c => return Err(format!("unknown compression {}", c)),
+                    }
+                }
+                "--sign" => res.sign = Some(value()?.into()),
+                "-h" | "--help" => return Err(USAGE.into()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ if input.is_none() => input = Some(arg.into()),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+        res.input = input.ok_or(USAGE)?;
+        Ok(res)
+    }
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+fn parse_int(s: &str) -> Result<u64> {
+    let res = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => s.replace('_', "").parse(),
+    };
+    res.map_err(|e| format!("bad number {}: {}", s, e))
+}
+
+fn parse_mmio(s: &str) -> Result<MmioRange> {
+    let (start, size) = s.split_once(':').ok_or("expected start:size")?;
+    Ok(MmioRange {
+        start: parse_int(start)?,
+        size: parse_int(size)?,
+    })
+}
+
+fn domain_type(name: &str) -> Result<DomainTypeRaw> {
+    (1..=u8::MAX)
+        .filter_map(|raw| DomainTypeRaw::try_from(raw).ok())
+        .find(|ty| ty.to_string() == name)
+        .ok_or_else(|| format!("unknown domain type {}", name))
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+/// Check the manifest against the interface the tool is built with.
+fn check_manifest(manifest: &Manifest, warnings: &mut Vec<String>) -> Result<()> {
+    let ty = DomainTypeRaw::try_from(manifest.ty)
+        .map_err(|_| format!("unknown domain type {} in manifest", manifest.ty))?;
+    if manifest.interface_version != INTERFACE_VERSION {
+        warnings.push(format!(
+            "manifest interface version {} != {}",
+            manifest.interface_version, INTERFACE_VERSION
+        ));
+    }
+    if manifest.abi_fingerprint != abi_fingerprint(ty) {
+        return Err(format!(
+            "ABI fingerprint of {} is {:#x}, the interface has {:#x}, rebuild the domain",
+            ty,
+            manifest.abi_fingerprint,
+            abi_fingerprint(ty)
+        ));
+    }
+    Ok(())
+}
+
+/// Inject the manifest if the ELF file has none and check the result.
+fn prepare_elf(
+    args: &Args,
+    elf: Vec<u8>,
+    warnings: &mut Vec<String>,
+) -> Result<(Vec<u8>, ImageInfo)> {
+    let info = check_image_for(&elf, args.machine)
+        .map_err(|e| format!("the loader refuses the domain: {}", e))?;
+    let elf = match (&info.manifest, &args.ty) {
+        (Some(manifest), Some(ty)) if manifest.ty != domain_type(ty)? as u8 => {
+            return Err(format!(
+                "the domain already has a manifest of type {}",
+                manifest.ty
+            ));
+        }
+        (Some(_), _) => elf,
+        (None, Some(ty)) => {
+            let ty = domain_type(ty)?;
+            let manifest = Manifest {
+                ty: ty as u8,
+                interface_version: INTERFACE_VERSION,
+                abi_fingerprint: abi_fingerprint(ty),
+                domain_version: args.domain_version.clone(),
+                deps: args.deps.clone(),
+                mmio: args.mmio.clone(),
+                irqs: args.irqs.clone(),
+            };
+            elf::add_section(&elf, MANIFEST_SECTION, &manifest.encode())?
+        }
+        (None, None) => {
+            warnings.push("no manifest, the kernel can not check the ABI".into());
+            elf
+        }
+    };
+    let info = check_image_for(&elf, args.machine)
+        .map_err(|e| format!("bad image after packaging: {}", e))?;
+    if let Some(manifest) = &info.manifest {
+        check_manifest(manifest, warnings)?;
+    }
+    match info.entry_symbol.as_deref() {
+        Some("main") => {}
+        Some(name) => warnings.push(format!("the entry point is {}, not main", name)),
+        None => warnings.push("no symbol at the entry point".into()),
+    }
+    Ok((elf, info))
+}
+
+fn compress(elf: &[u8], compress: Compress) -> Result<Vec<u8>> {
+    let data = match compress {
+        Compress::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            encoder.write_all(elf).map_err(|e| e.to_string())?;
+            encoder.finish().map_err(|e| e.to_string())?
+        }
+        Compress::Zstd => zstd::encode_all(elf, 19).map_err(|e| e.to_string())?,
+    };
+    // the loader must get the same ELF file back
+    match decompress(&data, elf.len()) {
+        Ok(res) if res.as_ref() == elf => Ok(data),
+        _ => Err("the loader can not decompress the image".into()),
+    }
+}
+
+fn sign(payload: Vec<u8>, key_path: &PathBuf) -> Result<(Vec<u8>, [u8; 32])> {
+    let key = fs::read(key_path).map_err(|e| format!("{}: {}", key_path.display(), e))?;
+    let key_pair = match key.len() {
+        32 => ed25519_compact::KeyPair::from_seed(ed25519_compact::Seed::from_slice(&key).unwrap()),
+        64 => ed25519_compact::KeyPair::from_slice(&key).map_err(|e| e.to_string())?,
+        _ => return Err("the key must be a 32 bytes seed or a 64 bytes key pair".into()),
+    };
+    let signature = key_pair.sk.sign(&payload, None);
+    let len = payload.len() as u64;
+    let mut image = payload;
+    image.extend_from_slice(signature.as_ref());
+    image.extend_from_slice(&len.to_le_bytes());
+    image.extend_from_slice(SIGNATURE_MAGIC);
+    let public_key = *key_pair.pk;
+    verify_image(&image, &VerifyPolicy::Ed25519(vec![public_key])).map_err(|e| e.to_string())?;
+    Ok((image, public_key))
+}
+
+fn report(args: &Args, info: &ImageInfo, elf_len: usize, payload: &[u8]) {
+    println!("domain:      {}", args.input.display());
+    println!("machine:     {:?}", info.machine);
+    println!(
+        "entry:       {:#x} ({})",
+        info.entry,
+        info.entry_symbol.as_deref().unwrap_or("?")
+    );
+    println!("area size:   {:#x}", info.area_size);
+    for seg in info.segments.iter() {
+        println!(
+            "segment:     {:#x}-{:#x} {:?}",
+            seg.range.start, seg.range.end, seg.flags
+        );
+    }
+    println!("relocations: {}", info.relocations);
+    match &info.manifest {
+        Some(m) => {
+            let ty = DomainTypeRaw::try_from(m.ty).map_or("?".into(), |ty| ty.to_string());
+            println!(
+                "manifest:    {} v{} interface {}",
+                ty, m.domain_version, m.interface_version
+            );
+            println!("abi:         {:#018x}", m.abi_fingerprint);
+            println!("deps:        {:?}", m.deps);
+            println!("mmio:        {:x?}", m.mmio);
+            println!("irqs:        {:?}", m.irqs);
+        }
+        None => println!("manifest:    none"),
+    }
+    println!("elf size:    {}", elf_len);
+    if let Some(compress) = args.compress {
+        println!("compressed:  {} ({:?})", payload.len(), compress);
+    }
+    // the hash allowlist policy hashes the image without the signature
+    println!("sha256:      {}", hex(&image_hash(payload)));
+}
+
+fn run(args: &Args) -> Result<()> {
+    let elf = fs::read(&args.input).map_err(|e| format!("{}: {}", args.input.display(), e))?;
+    let mut warnings = vec![];
+    let (elf, info) = prepare_elf(args, elf, &mut warnings)?;
+    let payload = match args.compress {
+        Some(c) => compress(&elf, c)?,
+        None => elf.clone(),
+    };
+    report(args, &info, elf.len(), &payload);
+    let (image, public_key) = match &args.sign {
+        Some(key) => {
+            let (image, pk) = sign(payload, key)?;
+            (image, Some(pk))
+        }
+        None => (payload, None),
+    };
+    if let Some(pk) = public_key {
+        println!("signed by:   {}", hex(&pk));
+    }
+    for warning in warnings.iter() {
+        println!("warning:     {}", warning);
+    }
+    if let Some(output) = &args.output {
+        fs::write(output, &image).map_err(|e| format!("{}: {}", output.display(), e))?;
+        println!("written:     {} ({} bytes)", output.display(), image.len());
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = Args::parse(std::env::args().skip(1)).and_then(|args| run(&args)) {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,110 @@
+//! A fake domain for every trait of `interface`.
+//!
+//! The methods are the ones `gproxy` generates for the empty domains: `init`
+//! succeeds and the others fail with `ENOSYS`. A test gives them to the
+//! domain under test with [`MockCore::add_domain`](crate::MockCore::add_domain),
+//! or wraps one in a struct to fake the methods it needs.
+
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+macro_rules! fake_domain {
+    ($name:ident, $ty:ident, [$($empty:ident),+] $(, $device:ident)?) => {
+        #[doc = concat!("A fake [`", stringify!($ty), "`].")]
+        #[derive(Debug)]
+        pub struct $name {
+            domain_id: u64,
+        }
+
+        impl $name {
+            pub fn new(domain_id: u64) -> Self {
+                Self { domain_id }
+            }
+
+            /// Return the domain as the [`DomainType`] `get_domain` returns.
+            pub fn domain_type(self) -> DomainType {
+                DomainType::$ty(Arc::new(self))
+            }
+        }
+
+        impl Basic for $name {
+            fn domain_id(&self) -> u64 {
+                self.domain_id
+            }
+        }
+
+        $(fake_domain!(@$device $name);)?
+        $(interface::$empty!($name);)+
+    };
+    (@device $name:ident) => {
+        impl DeviceBase for $name {
+            fn handle_irq(&self) -> AlienResult<()> {
+                Ok(())
+            }
+        }
+    };
+}
+
+fake_domain!(
+    FakeBlkDevice,
+    BlkDeviceDomain,
+    [impl_empty_for_BlkDeviceDomain],
+    device
+);
+fake_domain!(
+    FakeBufInput,
+    BufInputDomain,
+    [impl_empty_for_BufInputDomain],
+    device
+);
+fake_domain!(
+    FakeBufUart,
+    BufUartDomain,
+    [impl_empty_for_BufUartDomain],
+    device
+);
+fake_domain!(
+    FakeCacheBlkDevice,
+    CacheBlkDeviceDomain,
+    [impl_empty_for_CacheBlkDeviceDomain],
+    device
+);
+fake_domain!(
+    FakeEmptyDevice,
+    EmptyDeviceDomain,
+    [impl_empty_for_EmptyDeviceDomain]
+);
+fake_domain!(FakeFs, FsDomain, [impl_empty_for_FsDomain]);
+fake_domain!(
+    FakeDevFs,
+    DevFsDomain,
+    [impl_empty_for_FsDomain, impl_empty_for_DevFsDomain]
+);
+fake_domain!(FakeGpu, GpuDomain, [impl_empty_for_GpuDomain], device);
+fake_domain!(FakeInput, InputDomain, [impl_empty_for_InputDomain], device);
+fake_domain!(FakeLog, LogDomain, [impl_empty_for_LogDomain]);
+fake_domain!(FakeNet, NetDomain, [impl_empty_for_NetDomain], device);
+fake_domain!(
+    FakeNetDevice,
+    NetDeviceDomain,
+    [impl_empty_for_NetDeviceDomain],
+    device
+);
+fake_domain!(FakePlic, PLICDomain, [impl_empty_for_PLICDomain]);
+fake_domain!(FakeRtc, RtcDomain, [impl_empty_for_RtcDomain], device);
+fake_domain!(
+    FakeScheduler,
+    SchedulerDomain,
+    [impl_empty_for_SchedulerDomain]
+);
+fake_domain!(
+    FakeShadowBlock,
+    ShadowBlockDomain,
+    [impl_empty_for_ShadowBlockDomain],
+    device
+);
+fake_domain!(FakeSysCall, SysCallDomain, [impl_empty_for_SysCallDomain]);
+fake_domain!(FakeTask, TaskDomain, [impl_empty_for_TaskDomain]);
+fake_domain!(FakeUart, UartDomain, [impl_empty_for_UartDomain], device);
+fake_domain!(FakeVfs, VfsDomain, [impl_empty_for_VfsDomain]);
@@ -0,0 +1,268 @@
+//! Test a domain in a process on the host.
+//!
+//! [`MockCore`] is a [`CoreFunction`] on top of the hosted backend which
+//! records the console output and the pages of every domain, the shared heap
+//! and the storage are the ones of `domain_manager`. [`fake`] has a domain
+//! for every trait of `interface` and [`prelude`] has what the proxies of
+//! `gproxy` expect from the kernel, so a test can put a domain behind its
+//! proxy:
+//!
+//! ```ignore
+//! #![feature(box_into_inner)]
+//! use domain_test::prelude::*;
+//!
+//! gen_for_BlkDeviceDomain!();
+//!
+//! let domain = domain_test::run_domain(1, blk::main);
+//! let proxy = BlkDomainProxy::new(domain, DomainLoader::empty());
+//! assert_eq!(proxy.get_capacity(), Ok(4096));
+//! drop(proxy);
+//! assert!(domain_test::mock_core().console().contains("blk init"));
+//! domain_test::assert_no_leaks(1);
+//! ```
+//!
+//! The statics of a domain are the statics of the process, so every test
+//! binary runs one domain.
+
+pub mod fake;
+pub mod prelude;
+mod vm;
+
+use std::{
+    alloc::{alloc_zeroed, dealloc, Layout},
+    any::Any,
+    collections::BTreeMap,
+    ops::Range,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use corelib::{
+    domain_info::RestartPolicy,
+    event::{DomainEvent, EventDelivery, EventFilter},
+    AlienResult, CoreFunction,
+};
+use domain_manager::{resource::DOMAIN_RESOURCE, sheap::shared_data_count, FRAME_BITS, FRAME_SIZE};
+pub use hosted::DomainMain;
+use interface::{DomainType, DomainTypeRaw};
+use task_meta::{OperationResult, TaskOperation, TlsTemplate};
+pub use vm::HostVmOps;
+
+/// A [`CoreFunction`] which records what the domain does.
+///
+/// The calls it does not record go to [`hosted::HostedCore`].
+pub struct MockCore {
+    console: Mutex<String>,
+    /// The size of the pages a domain allocates, keyed by the address.
+    allocations: Mutex<BTreeMap<usize, usize>>,
+}
+
+static MOCK_CORE: LazyLock<MockCore> = LazyLock::new(|| MockCore {
+    console: Mutex::new(String::new()),
+    allocations: Mutex::new(BTreeMap::new()),
+});
+
+/// Return the [`MockCore`] of the process.
+pub fn mock_core() -> &'static MockCore {
+    &MOCK_CORE
+}
+
+impl MockCore {
+    /// Return the console output of the domain.
+    pub fn console(&self) -> String {
+        self.console.lock().unwrap().clone()
+    }
+
+    /// Return the console output of the domain and clear it.
+    pub fn take_console(&self) -> String {
+        core::mem::take(&mut *self.console.lock().unwrap())
+    }
+
+    /// Return the number of pages the domain holds.
+    pub fn pages(&self, domain_id: u64) -> usize {
+        DOMAIN_RESOURCE.lock().pages(domain_id)
+    }
+
+    /// Make `domain` visible to `get_domain` of the domain under test.
+    pub fn add_domain(&self, name: &str, domain: DomainType) {
+        hosted::hosted_core().add_domain(name, domain);
+    }
+
+    /// Deliver a lifecycle event to the subscriptions of the domain under
+    /// test, e.g. to act as if a peer panicked.
+    pub fn publish_event(&self, event: DomainEvent) {
+        domain_manager::event::publish(event);
+    }
+}
+
+fn page_layout(n: usize) -> Layout {
+    Layout::from_size_align(n * FRAME_SIZE, FRAME_SIZE).unwrap()
+}
+
+/// Free the pages of a domain, the proxies use it to recycle a domain.
+pub fn free_frames(addr: *mut u8, n: usize) {
+    MOCK_CORE
+        .allocations
+        .lock()
+        .unwrap()
+        .remove(&(addr as usize));
+    unsafe { dealloc(addr, page_layout(n)) }
+}
+
+impl CoreFunction for MockCore {
+    fn sys_alloc_pages(&self, domain_id: u64, n: usize) -> *mut u8 {
+        let p = unsafe { alloc_zeroed(page_layout(n)) };
+        if !p.is_null() {
+            self.allocations.lock().unwrap().insert(p as usize, n);
+            DOMAIN_RESOURCE
+                .lock()
+                .insert_page_map(domain_id, (p as usize >> FRAME_BITS, n));
+        }
+        p
+    }
+
+    fn sys_free_pages(&self, domain_id: u64, p: *mut u8, n: usize) {
+        let size = self.allocations.lock().unwrap().remove(&(p as usize));
+        assert_eq!(size, Some(n), "free pages {:p} which are not allocated", p);
+        DOMAIN_RESOURCE
+            .lock()
+            .free_page_map(domain_id, p as usize >> FRAME_BITS);
+        unsafe { dealloc(p, page_layout(n)) }
+    }
+
+    fn sys_write_console(&self, s: &str) {
+        self.console.lock().unwrap().push_str(s);
+    }
+
+    fn sys_backtrace(&self, domain_id: u64) {
+        hosted::hosted_core().sys_backtrace(domain_id)
+    }
+
+    fn sys_symbolize(&self, addr: usize, buf: &mut [u8]) -> AlienResult<usize> {
+        hosted::hosted_core().sys_symbolize(addr, buf)
+    }
+
+    fn sys_tls_template(&self, domain_id: u64) -> Option<TlsTemplate> {
+        hosted::hosted_core().sys_tls_template(domain_id)
+    }
+
+    fn sys_init_array(&self, domain_id: u64) -> Option<Range<usize>> {
+        hosted::hosted_core().sys_init_array(domain_id)
+    }
+
+    fn sys_trampoline_addr(&self) -> usize {
+        0
+    }
+
+    fn sys_kernel_satp(&self) -> usize {
+        0
+    }
+
+    fn sys_trap_from_user(&self) -> usize {
+        0
+    }
+
+    fn sys_trap_to_user(&self) -> usize {
+        0
+    }
+
+    fn blk_crash_trick(&self) -> bool {
+        false
+    }
+
+    fn sys_get_domain(&self, domain_id: u64, name: &str) -> Option<DomainType> {
+        hosted::hosted_core().sys_get_domain(domain_id, name)
+    }
+
+    fn sys_create_domain(
+        &self,
+        domain_file_name: &str,
+        identifier: &mut [u8],
+    ) -> AlienResult<DomainType> {
+        hosted::hosted_core().sys_create_domain(domain_file_name, identifier)
+    }
+
+    fn sys_register_domain(
+        &self,
+        ident: &str,
+        ty: DomainTypeRaw,
+        data: &[u8],
+        policy: RestartPolicy,
+    ) -> AlienResult<()> {
+        hosted::hosted_core().sys_register_domain(ident, ty, data, policy)
+    }
+
+    fn sys_set_restart_policy(&self, name: &str, policy: RestartPolicy) -> AlienResult<()> {
+        hosted::hosted_core().sys_set_restart_policy(name, policy)
+    }
+
+    fn sys_update_domain(
+        &self,
+        old_domain_name: &str,
+        new_domain_name: &str,
+        ty: DomainTypeRaw,
+    ) -> AlienResult<()> {
+        hosted::hosted_core().sys_update_domain(old_domain_name, new_domain_name, ty)
+    }
+
+    fn sys_reload_domain(&self, domain_name: &str) -> AlienResult<()> {
+        hosted::hosted_core().sys_reload_domain(domain_name)
+    }
+
+    fn sys_subscribe_domain_event(
+        &self,
+        domain_id: u64,
+        filter: EventFilter<'_>,
+        delivery: EventDelivery,
+    ) -> AlienResult<u64> {
+        hosted::hosted_core().sys_subscribe_domain_event(domain_id, filter, delivery)
+    }
+
+    fn sys_unsubscribe_domain_event(&self, subscription: u64) -> AlienResult<()> {
+        hosted::hosted_core().sys_unsubscribe_domain_event(subscription)
+    }
+
+    fn sys_poll_domain_event(&self, subscription: u64) -> AlienResult<Option<DomainEvent>> {
+        hosted::hosted_core().sys_poll_domain_event(subscription)
+    }
+
+    fn vaddr_to_paddr_in_kernel(&self, vaddr: usize) -> AlienResult<usize> {
+        hosted::hosted_core().vaddr_to_paddr_in_kernel(vaddr)
+    }
+
+    fn task_op(&self, op: TaskOperation) -> AlienResult<OperationResult> {
+        hosted::hosted_core().task_op(op)
+    }
+
+    fn checkout_shared_data(&self) -> AlienResult<()> {
+        hosted::hosted_core().checkout_shared_data()
+    }
+
+    fn domain_info(&self) -> AlienResult<Arc<dyn Any + Send + Sync>> {
+        hosted::hosted_core().domain_info()
+    }
+}
+
+/// Call the `main` of a domain with [`MockCore`].
+pub fn run_domain<T: ?Sized>(domain_id: u64, main: DomainMain<T>) -> Box<T> {
+    hosted::run_domain_with(mock_core(), domain_id, main)
+}
+
+/// Return the number of shared heap objects the domain still owns.
+pub fn shared_heap_leaks(domain_id: u64) -> usize {
+    shared_data_count(domain_id)
+}
+
+/// Panic if the domain still owns shared heap objects or pages.
+///
+/// Call it after the domain and the values it returned are dropped.
+pub fn assert_no_leaks(domain_id: u64) {
+    let objects = shared_heap_leaks(domain_id);
+    let pages = mock_core().pages(domain_id);
+    assert!(
+        objects == 0 && pages == 0,
+        "domain {} leaks {} shared heap objects and {} pages",
+        domain_id,
+        objects,
+        pages
+    );
+}
@@ -0,0 +1,117 @@
+//! The names the code generated by `gproxy` uses.
+//!
+//! In the kernel they come from its own modules, a test imports this module
+//! and expands the proxy with e.g. `gen_for_BlkDeviceDomain!()`. The proxies
+//! use `Box::into_inner`, the crate of the test needs
+//! `#![feature(box_into_inner)]`.
+
+pub use core::{net::SocketAddrV4, ops::Range};
+use std::sync::atomic::{AtomicUsize, Ordering};
+pub use std::{any::Any, thread::yield_now};
+
+pub use corelib::{AlienError, AlienResult};
+pub use domain_manager::{
+    event::move_domain_subscriptions,
+    resource::{abandon_domain_resource, free_domain_resource},
+    sheap::FreeShared,
+    storage_heap::{
+        migrate_domain_database, move_domain_database, rollback_domain_database,
+        take_update_snapshot,
+    },
+    watchdog::{CallWatch, Supervised},
+};
+pub use interface::*;
+pub use pconst::{
+    epoll::EpollEvent,
+    io::{PollEvents, RtcTime, SeekFrom},
+    net::{Domain, ShutdownFlag, SocketAddrIn, SocketType},
+};
+pub use shared_heap::{DBox, DVec, SharedData};
+pub use spin::{Mutex, Mutex as SleepMutex, Once, RwLock};
+pub use task_meta::TaskSchedulingInfo;
+pub use vfscore::{fstype::FileSystemFlags, inode::InodeAttr, superblock::SuperType, utils::*};
+
+use crate::HostVmOps;
+pub use crate::{fake::*, free_frames};
+
+pub type DomainLoader = loader::DomainLoader<HostVmOps>;
+
+/// The domain behind a proxy.
+///
+/// A reader holds a read lock instead of a grace period, an update waits for
+/// the readers.
+#[derive(Debug)]
+pub struct RcuData<T> {
+    data: RwLock<Box<T>>,
+}
+
+impl<T> RcuData<T> {
+    pub fn new(data: Box<T>) -> Self {
+        Self {
+            data: RwLock::new(data),
+        }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.data.read())
+    }
+
+    pub fn read_directly<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.read(f)
+    }
+
+    /// Replace the data and return the old one.
+    pub fn update(&self, data: Box<T>) -> Box<T> {
+        core::mem::replace(&mut *self.data.write(), data)
+    }
+
+    pub fn update_directly(&self, data: Box<T>) -> Box<T> {
+        self.update(data)
+    }
+}
+
+/// The number of calls in the domain, one counter for the whole process.
+#[derive(Debug, Default)]
+pub struct PerCpuCounter {
+    count: AtomicUsize,
+}
+
+impl PerCpuCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inc(&self) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn dec(&self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn all(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+/// Build a proxy, the kernel creates the proxies of its domains with it.
+pub trait ProxyBuilder {
+    type T;
+    fn build(domain: Self::T, domain_loader: DomainLoader) -> AlienResult<Self>
+    where
+        Self: Sized;
+    fn build_empty(domain_loader: DomainLoader) -> Self;
+    fn init_by_box(&self, argv: Box<dyn Any + Send + Sync>) -> AlienResult<()>;
+}
+
+/// Measure a stage of a domain update, the tests do not print it.
+pub struct TimeTick;
+
+impl TimeTick {
+    pub fn new(_name: &str) -> Self {
+        Self
+    }
+}
+
+/// Every thread sees the flag of a proxy at once, there is nothing to wait for.
+pub fn sync_cpus() {}
@@ -0,0 +1,78 @@
+use std::{
+    alloc::{alloc_zeroed, dealloc, Layout},
+    any::Any,
+};
+
+use domain_manager::FRAME_SIZE;
+use loader::{DomainArea, DomainVmOps};
+use memory_addr::VirtAddr;
+
+/// A domain area in the memory of the process.
+#[derive(Debug)]
+struct HostArea {
+    start: usize,
+    size: usize,
+}
+
+impl HostArea {
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size.max(FRAME_SIZE), FRAME_SIZE).unwrap()
+    }
+}
+
+impl DomainArea for HostArea {
+    fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.start as *const u8, self.size) }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn as_mut_slice(&self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.start as *mut u8, self.size) }
+    }
+
+    fn start_virtual_address(&self) -> VirtAddr {
+        VirtAddr::from(self.start)
+    }
+
+    fn any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// The [`DomainVmOps`] of the `DomainLoader` the proxies hold.
+///
+/// The process can not change the permissions of its memory, the areas stay
+/// readable and writable.
+pub struct HostVmOps;
+
+impl DomainVmOps for HostVmOps {
+    fn map_domain_area(size: usize) -> Box<dyn DomainArea> {
+        let start = unsafe { alloc_zeroed(HostArea::layout(size)) };
+        assert!(
+            !start.is_null(),
+            "failed to map a domain area of {:#x}",
+            size
+        );
+        Box::new(HostArea {
+            start: start as usize,
+            size,
+        })
+    }
+
+    fn unmap_domain_area(area: Box<dyn DomainArea>) {
+        let area = area.any().downcast::<HostArea>().unwrap();
+        unsafe { dealloc(area.start as *mut u8, HostArea::layout(area.size)) }
+    }
+
+    fn set_memory_x(_start: usize, _pages: usize) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn set_memory_ro(_start: usize, _pages: usize) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn set_memory_nx(_start: usize, _pages: usize) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
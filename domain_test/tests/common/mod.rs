@@ -0,0 +1,30 @@
+//! The `main` of the block device domains under test.
+
+use corelib::CoreFunction;
+use domain_test::prelude::*;
+use shared_heap::SharedHeapAlloc;
+use storage::StorageArg;
+
+/// A block device domain which [`blk_main`] creates.
+pub trait BlkDomain: BlkDeviceDomain + Sized + 'static {
+    fn create(domain_id: u64) -> Self;
+}
+
+impl BlkDomain for FakeBlkDevice {
+    fn create(domain_id: u64) -> Self {
+        FakeBlkDevice::new(domain_id)
+    }
+}
+
+/// Initialize the domain like the `main` of `domain_main` and create `D`.
+pub fn blk_main<D: BlkDomain>(
+    core: &'static dyn CoreFunction,
+    domain_id: u64,
+    heap: &'static dyn SharedHeapAlloc,
+    _storage: StorageArg,
+) -> Box<dyn BlkDeviceDomain> {
+    corelib::init(core);
+    shared_heap::init(heap, domain_id);
+    corelib::write_console("blk main\n");
+    Box::new(D::create(domain_id))
+}
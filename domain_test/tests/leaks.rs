@@ -0,0 +1,19 @@
+//! Report the shared heap objects a domain leaks.
+
+mod common;
+
+use common::blk_main;
+use domain_test::prelude::*;
+
+const DOMAIN_ID: u64 = 1;
+
+#[test]
+#[should_panic(expected = "domain 1 leaks 1 shared heap objects and 0 pages")]
+fn leaked_shared_data_is_reported() {
+    let domain = domain_test::run_domain(DOMAIN_ID, blk_main::<FakeBlkDevice>);
+    assert_eq!(domain_test::mock_core().console(), "blk main\n");
+    assert_eq!(domain.get_capacity(), Err(AlienError::ENOSYS));
+    core::mem::forget(DVec::new(0u8, 16));
+    drop(domain);
+    domain_test::assert_no_leaks(DOMAIN_ID);
+}
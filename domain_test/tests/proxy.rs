@@ -0,0 +1,95 @@
+//! Call a domain through its proxy.
+#![feature(box_into_inner)]
+
+mod common;
+
+use common::{blk_main, BlkDomain};
+use domain_test::prelude::*;
+
+gen_for_BlkDeviceDomain!();
+
+const DOMAIN_ID: u64 = 1;
+const BLOCK_SIZE: usize = 512;
+const CAPACITY: u64 = 4096;
+
+/// A block device whose blocks are filled with their number.
+#[derive(Debug)]
+struct MemBlk;
+
+impl Basic for MemBlk {
+    fn domain_id(&self) -> u64 {
+        shared_heap::domain_id()
+    }
+}
+
+impl DeviceBase for MemBlk {
+    fn handle_irq(&self) -> AlienResult<()> {
+        Ok(())
+    }
+}
+
+impl BlkDeviceDomain for MemBlk {
+    fn init(&self, device_info: &Range<usize>) -> AlienResult<()> {
+        corelib::write_console(&format!("blk init {:#x?}\n", device_info));
+        Ok(())
+    }
+
+    fn read_block(&self, block: u32, mut data: DVec<u8>) -> AlienResult<DVec<u8>> {
+        data.as_mut_slice().fill(block as u8);
+        Ok(data)
+    }
+
+    fn write_block(&self, _block: u32, data: &DVec<u8>) -> AlienResult<usize> {
+        Ok(data.len())
+    }
+
+    fn get_capacity(&self) -> AlienResult<u64> {
+        Ok(CAPACITY)
+    }
+
+    fn flush(&self) -> AlienResult<()> {
+        Ok(())
+    }
+}
+
+impl BlkDomain for MemBlk {
+    fn create(_domain_id: u64) -> Self {
+        MemBlk
+    }
+}
+
+#[test]
+fn proxy_calls_the_domain() {
+    let domain = domain_test::run_domain(DOMAIN_ID, blk_main::<MemBlk>);
+    let proxy = BlkDomainProxy::new(domain, DomainLoader::empty());
+    assert_eq!(proxy.init(&(0..0x1000)), Ok(()));
+    assert!(domain_test::mock_core().take_console().contains("blk init"));
+    assert_eq!(proxy.domain_id(), DOMAIN_ID);
+    assert_eq!(proxy.get_capacity(), Ok(CAPACITY));
+
+    let data = proxy.read_block(3, DVec::new(0, BLOCK_SIZE)).unwrap();
+    assert!(data.as_slice().iter().all(|b| *b == 3));
+    assert_eq!(proxy.write_block(3, &data), Ok(BLOCK_SIZE));
+    drop(data);
+    assert_eq!(domain_test::shared_heap_leaks(DOMAIN_ID), 0);
+
+    // Every call returned, none is stalled.
+    assert!(Supervised::calls(&proxy) > 0);
+    assert_eq!(Supervised::stalled_ns(&proxy), 0);
+
+    // A failed domain answers every call with ENOSYS and leaves nothing
+    // behind.
+    assert_eq!(proxy.replace_with_empty(), Ok(()));
+    assert_eq!(proxy.get_capacity(), Err(AlienError::ENOSYS));
+    domain_test::assert_no_leaks(DOMAIN_ID);
+}
+
+#[test]
+fn build_checks_the_interface() {
+    // An empty loader has no manifest to check the domain against.
+    let domain: Box<dyn BlkDeviceDomain> = Box::new(FakeBlkDevice::new(2));
+    assert!(matches!(
+        BlkDomainProxy::build(domain, DomainLoader::empty()),
+        Err(AlienError::EINVAL)
+    ));
+}
@@ -0,0 +1,131 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{ItemTrait, PathArguments, TraitItem, Type, TypeParamBound};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter()
+        .fold(hash, |hash, b| (hash ^ *b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Remove the whitespace, the spacing of tokens may differ between versions
+/// of proc_macro2.
+fn normalize(tokens: impl ToTokens) -> String {
+    tokens
+        .to_token_stream()
+        .to_string()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// The name of the const which holds the fingerprint, e.g. `FS_DOMAIN_ABI_FINGERPRINT`.
+pub fn fingerprint_ident(trait_name: &Ident) -> Ident {
+    let mut name = String::new();
+    for (i, c) in trait_name.to_string().chars().enumerate() {
+        if c.is_uppercase() && i != 0 && !name.ends_with('_') {
+            let prev = trait_name.to_string().chars().nth(i - 1).unwrap();
+            if prev.is_lowercase() {
+                name.push('_');
+            }
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name.push_str("_ABI_FINGERPRINT");
+    Ident::new(&name, trait_name.span())
+}
+
+/// The supertraits which are not part of the interface, they come from `core`
+/// and `downcast-rs` and do not change with it.
+const FOREIGN_TRAITS: &[&str] = &[
+    "Send",
+    "Sync",
+    "Sized",
+    "Unpin",
+    "Debug",
+    "Any",
+    "DowncastSync",
+];
+
+/// Return the fingerprint consts of the supertraits of the interface.
+///
+/// Every supertrait of the interface is a `#[proxy]` or an
+/// `#[abi_fingerprint]` trait, its const is named after it and is imported
+/// with it.
+fn supertrait_fingerprints(trait_def: &ItemTrait) -> Vec<TokenStream> {
+    trait_def
+        .supertraits
+        .iter()
+        .filter_map(|bound| match bound {
+            TypeParamBound::Trait(bound) => Some(&bound.path),
+            _ => None,
+        })
+        .filter(|path| {
+            let last = path.segments.last().unwrap();
+            !FOREIGN_TRAITS.contains(&last.ident.to_string().as_str())
+        })
+        .map(|path| {
+            let mut path = path.clone();
+            let last = path.segments.last_mut().unwrap();
+            last.ident = fingerprint_ident(&last.ident);
+            last.arguments = PathArguments::None;
+            path.to_token_stream()
+        })
+        .collect()
+}
+
+/// Hash the trait name, the supertraits, the init argument and the signature
+/// of every method in order.
+///
+/// The order of the methods is the order of the vtable slots, so moving a
+/// method changes the fingerprint as well. The layout of the argument types
+/// is not covered. The methods of the supertraits are covered by their
+/// fingerprints, see [`def_abi_fingerprint`].
+fn abi_fingerprint(source: Option<&Type>, trait_def: &ItemTrait) -> u64 {
+    let mut hash = fnv1a(FNV_OFFSET, trait_def.ident.to_string().as_bytes());
+    hash = fnv1a(hash, normalize(&trait_def.supertraits).as_bytes());
+    if let Some(source) = source {
+        hash = fnv1a(hash, normalize(source).as_bytes());
+    }
+    for item in trait_def.items.iter() {
+        if let TraitItem::Fn(method) = item {
+            hash = fnv1a(hash, normalize(&method.sig).as_bytes());
+        }
+    }
+    hash
+}
+
+/// Define the fingerprint const of the trait.
+///
+/// The fingerprints of the supertraits are folded into the hash of the trait
+/// by the compiler, so a change of `Basic` changes the fingerprint of every
+/// interface.
+pub fn def_abi_fingerprint(source: Option<&Type>, trait_def: &ItemTrait) -> TokenStream {
+    let ident = fingerprint_ident(&trait_def.ident);
+    let fingerprint = abi_fingerprint(source, trait_def);
+    let supertraits = supertrait_fingerprints(trait_def);
+    let doc = format!(
+        "The ABI fingerprint of [`{}`], it changes when a method signature changes.",
+        trait_def.ident
+    );
+    quote!(
+        #[doc = #doc]
+        pub const #ident: u64 = {
+            let supertraits: &[u64] = &[#(#supertraits),*];
+            let mut hash: u64 = #fingerprint;
+            let mut i = 0;
+            while i < supertraits.len() {
+                let bytes = supertraits[i].to_le_bytes();
+                let mut j = 0;
+                while j < bytes.len() {
+                    hash = (hash ^ bytes[j] as u64).wrapping_mul(#FNV_PRIME);
+                    j += 1;
+                }
+                i += 1;
+            }
+            hash
+        };
+    )
+}
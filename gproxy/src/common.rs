@@ -9,6 +9,8 @@ pub struct ResourceCode {
     pub resource_init: TokenStream,
     pub cast: TokenStream,
     pub call_once: TokenStream,
+    /// Initialize `new_domain` during an update, it evaluates to the result
+    /// of its `init`.
     pub replace_call: TokenStream,
 }
 
@@ -33,11 +35,11 @@ pub fn resource_code(proxy: &Proxy) -> ResourceCode {
             self.resource.call_once(|| argv);
         );
 
-        let s4 = quote! (
+        let s4 = quote! ({
             let resource = self.resource.get().unwrap();
             let info = resource.as_ref().downcast_ref::<#s_ty>().unwrap();
-            new_domain.init(info).unwrap();
-        );
+            new_domain.init(info)
+        });
 
         (s1, s2, s3, s4)
     } else {
@@ -45,9 +47,7 @@ pub fn resource_code(proxy: &Proxy) -> ResourceCode {
             let _ = argv;
             self.init()?;
         );
-        let s4 = quote! (
-            new_domain.init().unwrap();
-        );
+        let s4 = quote!(new_domain.init());
         (quote!(), s2, quote!(), s4)
     };
     ResourceCode {
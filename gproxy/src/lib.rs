@@ -1,3 +1,4 @@
+mod abi;
 mod common;
 mod empty_impl;
 mod rcu_impl;
@@ -12,7 +13,7 @@ use syn::{
     parse_macro_input, ItemTrait, Token, Type,
 };
 
-use crate::{rcu_impl::def_struct_rcu, rwlock_impl::def_struct_rwlock};
+use crate::{abi::def_abi_fingerprint, rcu_impl::def_struct_rcu, rwlock_impl::def_struct_rwlock};
 
 enum SyncType {
     Srcu,
@@ -87,6 +88,7 @@ pub fn proxy(
 ) -> proc_macro::TokenStream {
     let proxy = parse_macro_input!(attr as Proxy);
     let trait_def = parse_macro_input!(item as ItemTrait);
+    let fingerprint = def_abi_fingerprint(proxy.source.as_ref(), &trait_def);
     let struct_def = if proxy.sync == "SRCU" {
         def_struct_rcu(proxy, trait_def.clone())
     } else {
@@ -94,7 +96,24 @@ pub fn proxy(
     };
     quote!(
         #trait_def
+        #fingerprint
         #struct_def
     )
     .into()
 }
+
+/// Define the ABI fingerprint const of a trait which has no proxy, e.g.
+/// `Basic`, the interfaces with it as supertrait include it in theirs.
+#[proc_macro_attribute]
+pub fn abi_fingerprint(
+    _attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let trait_def = parse_macro_input!(item as ItemTrait);
+    let fingerprint = def_abi_fingerprint(None, &trait_def);
+    quote!(
+        #trait_def
+        #fingerprint
+    )
+    .into()
+}
@@ -3,6 +3,7 @@ use quote::quote;
 use syn::{ItemTrait, TraitItem, TraitItemFn};
 
 use crate::{
+    abi::fingerprint_ident,
     common::{
         collect_func_info, gen_trampoline_info, resource_code, FuncInfo, ResourceCode,
         TrampolineArg, TrampolineInfo,
@@ -23,6 +24,7 @@ pub fn def_struct_rcu(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
     let (func_code, extern_func_code) =
         impl_func(func_vec, trait_name, &ident, proxy.source.is_some());
 
+    let fingerprint_ident = fingerprint_ident(trait_name);
     let macro_ident = Ident::new(&format!("gen_for_{}", trait_name), trait_name.span());
     let impl_ident = Ident::new(&format!("impl_for_{}", trait_name), trait_name.span());
 
@@ -39,7 +41,7 @@ pub fn def_struct_rcu(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
         replace_call,
     } = resource_code(&proxy);
 
-    let prox_ext_impl = impl_prox_ext_trait(&ident, replace_call, trait_name);
+    let prox_ext_impl = impl_prox_ext_trait(&ident, replace_call, trait_name, &empty_ident);
 
     quote::quote!(
         #[macro_export]
@@ -49,6 +51,8 @@ pub fn def_struct_rcu(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
                 pub struct #ident{
                     domain: RcuData<Box<dyn #trait_name>>,
                     domain_loader: Mutex<DomainLoader>,
+                    counter: PerCpuCounter,
+                    watch: CallWatch,
                     #resource_field
                 }
                 impl #ident{
@@ -56,6 +60,8 @@ pub fn def_struct_rcu(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
                         Self{
                             domain: RcuData::new(Box::new(domain)),
                             domain_loader: Mutex::new(domain_loader),
+                            counter: PerCpuCounter::new(),
+                            watch: CallWatch::new(),
                             #resource_init
                         }
                     }
@@ -66,8 +72,13 @@ pub fn def_struct_rcu(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
 
                 impl ProxyBuilder for #ident{
                     type T = Box<dyn #trait_name>;
-                    fn build(domain: Self::T,domain_loader: DomainLoader)->Self{
-                        Self::new(domain,domain_loader)
+                    fn build(domain: Self::T,domain_loader: DomainLoader)->AlienResult<Self>{
+                        // the domain must be built against the same interface as the kernel
+                        if domain_loader.verify_abi(INTERFACE_VERSION, #fingerprint_ident).is_err() {
+                            core::mem::forget(domain);
+                            return Err(AlienError::EINVAL);
+                        }
+                        Ok(Self::new(domain,domain_loader))
                     }
                     fn build_empty(domain_loader: DomainLoader)->Self{
                         let domain = Box::new(#empty_ident::new());
@@ -115,28 +126,100 @@ fn impl_prox_ext_trait(
     proxy_name: &Ident,
     replace_call: TokenStream,
     trait_name: &Ident,
+    empty_ident: &Ident,
 ) -> TokenStream {
+    let fingerprint_ident = fingerprint_ident(trait_name);
     quote!(
         impl #proxy_name{
              pub fn replace(&self,new_domain: Box<dyn #trait_name>,loader:DomainLoader) -> AlienResult<()> {
+                // the vtable of a domain built against another interface can not be used
+                if loader.verify_abi(INTERFACE_VERSION, #fingerprint_ident).is_err() {
+                    core::mem::forget(new_domain);
+                    return Err(AlienError::EINVAL);
+                }
                 let total = TimeTick::new("Total Time");
                 let mut loader_guard = self.domain_loader.lock();
                 let old_id = self.domain_id();
-                let tick = TimeTick::new("Reinit domain without state");
-                // init the new domain before swap
-                #replace_call
+                let tick = TimeTick::new("Reinit domain with migrated state");
+                // migrate the state to the schema of the new domain, then init
+                // the new domain with it before swap
+                let new_domain_id = new_domain.domain_id();
+                // the state before the main of the new domain wrote to it
+                let snapshot = take_update_snapshot(new_domain_id);
+                let res = match migrate_domain_database(new_domain_id) {
+                    Ok(_) => #replace_call,
+                    Err(_) => Err(AlienError::EINVAL),
+                };
+                if let Err(e) = res {
+                    // abort the update, the old domain keeps running with its
+                    // state, what the new domain wrote is thrown away
+                    move_domain_database(new_domain_id, old_id);
+                    drop(take_update_snapshot(old_id));
+                    if let Some(snapshot) = snapshot {
+                        rollback_domain_database(old_id, snapshot);
+                    }
+                    core::mem::forget(new_domain);
+                    free_domain_resource(new_domain_id, FreeShared::Free, free_frames);
+                    return Err(e);
+                }
                 drop(tick);
 
-               let old_domain = self.domain.update(Box::new(new_domain));
+                // the grace period never ends while the watchdog has abandoned
+                // calls in the old domain, keep the old domain instead
+                let stuck = self.watch.abandoned_in_domain() > 0;
+                let old_domain = if stuck {
+                    self.domain.update_directly(Box::new(new_domain))
+                } else {
+                    self.domain.update(Box::new(new_domain))
+                };
+                // the calls abandoned in the old domain do not count for the new one
+                let stuck = self.watch.retire() > 0 || stuck;
 
                 let tick = TimeTick::new("Recycle resources");
-                // forget the old domain
-                // it will be dropped by the `free_domain_resource`
-                let real_domain = Box::into_inner(old_domain);
-                core::mem::forget(real_domain);
-                free_domain_resource(old_id, FreeShared::Free,free_frames);
+                move_domain_subscriptions(old_id, new_domain_id);
+                if stuck {
+                    // the readers which did not end a grace period may still
+                    // hold the box, keep it with the old domain
+                    core::mem::forget(old_domain);
+                    abandon_domain_resource(old_id);
+                    core::mem::forget(core::mem::replace(&mut *loader_guard, loader));
+                } else {
+                    // forget the old domain
+                    // it will be dropped by the `free_domain_resource`
+                    let real_domain = Box::into_inner(old_domain);
+                    core::mem::forget(real_domain);
+                    free_domain_resource(old_id, FreeShared::Free,free_frames);
+                    *loader_guard = loader;
+                }
                 drop(tick);
-                *loader_guard = loader;
+                Ok(())
+            }
+
+            /// Replace the domain by its empty implementation, every call
+            /// returns `ENOSYS` until the domain is replaced again.
+            pub fn replace_with_empty(&self) -> AlienResult<()> {
+                let _loader_guard = self.domain_loader.lock();
+                let old_id = self.domain_id();
+                if old_id == u64::MAX {
+                    // it is empty already
+                    return Ok(());
+                }
+                let empty: Box<dyn #trait_name> = Box::new(#empty_ident::new());
+                let stuck = self.watch.abandoned_in_domain() > 0;
+                let old_domain = if stuck {
+                    self.domain.update_directly(Box::new(empty))
+                } else {
+                    self.domain.update(Box::new(empty))
+                };
+                let stuck = self.watch.retire() > 0 || stuck;
+                if stuck {
+                    core::mem::forget(old_domain);
+                    abandon_domain_resource(old_id);
+                } else {
+                    let real_domain = Box::into_inner(old_domain);
+                    core::mem::forget(real_domain);
+                    free_domain_resource(old_id, FreeShared::Free,free_frames);
+                }
                 Ok(())
             }
         }
@@ -241,13 +324,18 @@ fn gen_trampoline(arg: TrampolineArg) -> TokenStream {
 
     quote! (
             #check_code
-            self.domain.read(|domain|{
+            let slot = self.watch.enter();
+            self.counter.inc();
+            let res = self.domain.read(|domain|{
                 #get_domain_id
                 #(#arg_domain_change)*
                 domain.#func_name(#(#input_argv),*).map(|r| {
                     #call_move_to
                     r
                 })
-            })
+            });
+            self.counter.dec();
+            self.watch.exit(slot);
+            res
     )
 }
@@ -3,6 +3,7 @@ use quote::quote;
 use syn::{FnArg, ItemTrait, ReturnType, TraitItem, TraitItemFn};
 
 use crate::{
+    abi::fingerprint_ident,
     common::{
         collect_func_info, gen_trampoline_info, resource_code, FuncInfo, ResourceCode,
         TrampolineArg, TrampolineInfo,
@@ -25,6 +26,7 @@ pub fn def_struct_rwlock(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
     let extern_func_code = other[0].clone();
     let inner_call_code = other[1].clone();
 
+    let fingerprint_ident = fingerprint_ident(trait_name);
     let macro_ident = Ident::new(&format!("gen_for_{}", trait_name), trait_name.span());
     let impl_ident = Ident::new(&format!("impl_for_{}", trait_name), trait_name.span());
 
@@ -46,7 +48,7 @@ pub fn def_struct_rwlock(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
     //     trait_name.span(),
     // );
 
-    let prox_ext_impl = impl_prox_ext_trait(&ident, replace_call, trait_name);
+    let prox_ext_impl = impl_prox_ext_trait(&ident, replace_call, trait_name, &empty_ident);
 
     quote::quote!(
         #[macro_export]
@@ -59,6 +61,7 @@ pub fn def_struct_rwlock(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
                     domain_loader: SleepMutex<DomainLoader>,
                     flag: core::sync::atomic::AtomicBool,
                     counter: PerCpuCounter,
+                    watch: CallWatch,
                     #resource_field
                 }
                 impl #ident{
@@ -69,6 +72,7 @@ pub fn def_struct_rwlock(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
                             domain_loader: SleepMutex::new(domain_loader),
                             flag: core::sync::atomic::AtomicBool::new(false),
                             counter: PerCpuCounter::new(),
+                            watch: CallWatch::new(),
                             #resource_init
                         }
                     }
@@ -84,8 +88,13 @@ pub fn def_struct_rwlock(proxy: Proxy, trait_def: ItemTrait) -> TokenStream {
 
                 impl ProxyBuilder for #ident{
                     type T = Box<dyn #trait_name>;
-                    fn build(domain: Self::T,domain_loader: DomainLoader)->Self{
-                        Self::new(domain,domain_loader)
+                    fn build(domain: Self::T,domain_loader: DomainLoader)->AlienResult<Self>{
+                        // the domain must be built against the same interface as the kernel
+                        if domain_loader.verify_abi(INTERFACE_VERSION, #fingerprint_ident).is_err() {
+                            core::mem::forget(domain);
+                            return Err(AlienError::EINVAL);
+                        }
+                        Ok(Self::new(domain,domain_loader))
                     }
                     fn build_empty(domain_loader: DomainLoader)->Self{
                         let domain = Box::new(#empty_ident::new());
@@ -141,10 +150,17 @@ fn impl_prox_ext_trait(
     proxy_name: &Ident,
     replace_call: TokenStream,
     trait_name: &Ident,
+    empty_ident: &Ident,
 ) -> TokenStream {
+    let fingerprint_ident = fingerprint_ident(trait_name);
     let code = quote!(
         impl #proxy_name{
             pub fn replace(&self,new_domain: Box<dyn #trait_name>,loader:DomainLoader) -> AlienResult<()> {
+                // the vtable of a domain built against another interface can not be used
+                if loader.verify_abi(INTERFACE_VERSION, #fingerprint_ident).is_err() {
+                    core::mem::forget(new_domain);
+                    return Err(AlienError::EINVAL);
+                }
                 // stage1: get the sleep lock and change to updating state
                 let mut loader_guard = self.domain_loader.lock();
                 let old_id = self.domain_id();
@@ -158,37 +174,111 @@ fn impl_prox_ext_trait(
                 // why we need to synchronize_sched here?
                 sync_cpus();
 
-                // wait if there are readers which are reading the old domain but no read lock
-                while self.all_counter() > 0 {
-                    // println!("Wait for all reader to finish");
-                    // yield_now();
-                }
+                // wait if there are readers which are reading the old domain but no read lock,
+                // the calls the watchdog abandoned never return
+                let waited = self.watch.wait_calls(|| self.all_counter());
                 drop(tick);
 
-                let tick = TimeTick::new("Reinit and state transfer");
-
-                // stage3: init the new domain before swap
+                let tick = TimeTick::new("State migration");
+                // stage3: migrate the state to the schema of the new domain,
+                // then init the new domain with it before swap
                 let new_domain_id = new_domain.domain_id();
-                #replace_call
-                drop(tick);
+                // the state before the main of the new domain wrote to it
+                let snapshot = take_update_snapshot(new_domain_id);
+                let res = if !waited {
+                    // a call the watchdog can not abandon is stuck in the old domain
+                    Err(AlienError::EBUSY)
+                } else {
+                    match migrate_domain_database(new_domain_id) {
+                        Ok(_) => {
+                            drop(tick);
+                            let _tick = TimeTick::new("Reinit and state transfer");
+                            #replace_call
+                        }
+                        Err(_) => Err(AlienError::EINVAL),
+                    }
+                };
+                if let Err(e) = res {
+                    // abort the update, the old domain keeps running with its
+                    // state, what the new domain wrote is thrown away
+                    move_domain_database(new_domain_id, old_id);
+                    drop(take_update_snapshot(old_id));
+                    if let Some(snapshot) = snapshot {
+                        rollback_domain_database(old_id, snapshot);
+                    }
+                    self.flag.store(false, core::sync::atomic::Ordering::SeqCst);
+                    core::mem::forget(new_domain);
+                    free_domain_resource(new_domain_id, FreeShared::Free, free_frames);
+                    drop(w_lock);
+                    drop(loader_guard);
+                    return Err(e);
+                }
 
                 let tick = TimeTick::new("Domain swap");
                 // stage4: swap the domain and change to normal state
                 let old_domain = self.domain.update_directly(Box::new(new_domain));
+                // the calls abandoned in the old domain do not count for the new one
+                let stuck = self.watch.retire() > 0;
                 // change to normal state
                 self.flag.store(false, core::sync::atomic::Ordering::SeqCst);
                 drop(tick);
 
                 let tick = TimeTick::new("Recycle resources");
                 // stage5: recycle all resources
-                let real_domain = Box::into_inner(old_domain);
-                core::mem::forget(real_domain);
-
-                free_domain_resource(old_id, FreeShared::NotFree(new_domain_id),free_frames);
+                move_domain_subscriptions(old_id, new_domain_id);
+                if stuck {
+                    // the abandoned calls still run the code of the old domain
+                    // and hold the box of it
+                    core::mem::forget(old_domain);
+                    abandon_domain_resource(old_id);
+                    core::mem::forget(core::mem::replace(&mut *loader_guard, loader));
+                } else {
+                    let real_domain = Box::into_inner(old_domain);
+                    core::mem::forget(real_domain);
+                    free_domain_resource(old_id, FreeShared::NotFree(new_domain_id),free_frames);
+                    *loader_guard = loader;
+                }
                 drop(tick);
 
                 // stage6: release all locks
-                *loader_guard = loader;
+                drop(w_lock);
+                drop(loader_guard);
+                Ok(())
+            }
+
+            /// Replace the domain by its empty implementation, every call
+            /// returns `ENOSYS` until the domain is replaced again.
+            pub fn replace_with_empty(&self) -> AlienResult<()> {
+                let loader_guard = self.domain_loader.lock();
+                let old_id = self.domain_id();
+                if old_id == u64::MAX {
+                    // it is empty already
+                    return Ok(());
+                }
+                let w_lock = self.lock.write();
+                self.flag.store(true, core::sync::atomic::Ordering::SeqCst);
+                sync_cpus();
+                if !self.watch.wait_calls(|| self.all_counter()) {
+                    // a call the watchdog can not abandon is stuck in the old domain
+                    self.flag.store(false, core::sync::atomic::Ordering::SeqCst);
+                    return Err(AlienError::EBUSY);
+                }
+
+                let empty: Box<dyn #trait_name> = Box::new(#empty_ident::new());
+                let old_domain = self.domain.update_directly(Box::new(empty));
+                let stuck = self.watch.retire() > 0;
+                self.flag.store(false, core::sync::atomic::Ordering::SeqCst);
+
+                if stuck {
+                    // the abandoned calls still run the code of the old domain
+                    // and hold the box of it
+                    core::mem::forget(old_domain);
+                    abandon_domain_resource(old_id);
+                } else {
+                    let real_domain = Box::into_inner(old_domain);
+                    core::mem::forget(real_domain);
+                    free_domain_resource(old_id, FreeShared::Free,free_frames);
+                }
                 drop(w_lock);
                 drop(loader_guard);
                 Ok(())
@@ -364,9 +454,11 @@ fn impl_inner_code(
         }
         #[inline(always)]
         fn #__ident_no_lock(&self, #(#fn_argv),*)#output{
+            let slot = self.watch.enter();
             self.counter.inc();
             let res = self.#__ident(#(#input_argv),*);
             self.counter.dec();
+            self.watch.exit(slot);
             res
         }
         #[cold]
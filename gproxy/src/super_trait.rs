@@ -31,11 +31,15 @@ pub fn impl_supertrait(ident: Ident, trait_def: ItemTrait, sync_ty: SyncType) ->
                         code.push(device_base)
                     }
                     "Basic" => {
-                        let (ext_code, inner_code) = match sync_ty {
-                            SyncType::Srcu => (quote!(), srcu_for_domain_id()),
-                            SyncType::Rwlock => {
-                                (lock_for_domain_id(&ident), rwlock_for_domain_id())
+                        let (ext_code, inner_code, health_code) = match sync_ty {
+                            SyncType::Srcu => {
+                                (quote!(), srcu_for_domain_id(), srcu_for_health_check())
                             }
+                            SyncType::Rwlock => (
+                                lock_for_domain_id(&ident),
+                                rwlock_for_domain_id(),
+                                rwlock_for_health_check(),
+                            ),
                         };
                         let basic = quote!(
                             #ext_code
@@ -46,6 +50,26 @@ pub fn impl_supertrait(ident: Ident, trait_def: ItemTrait, sync_ty: SyncType) ->
                                 fn is_active(&self)->bool{
                                     true
                                 }
+                                fn health_check(&self)->AlienResult<()>{
+                                    #health_code
+                                }
+                            }
+                            impl Supervised for #ident{
+                                fn stalled_ns(&self)->u64{
+                                    self.watch.oldest_ns()
+                                }
+                                fn abandon_calls(&self, age_ns: u64)->usize{
+                                    self.watch.abandon(age_ns)
+                                }
+                                fn calls(&self)->u64{
+                                    self.watch.calls()
+                                }
+                                fn health_check(&self)->AlienResult<()>{
+                                    Basic::health_check(self)
+                                }
+                                fn replace_with_empty(&self)->AlienResult<()>{
+                                    #ident::replace_with_empty(self)
+                                }
                             }
                         );
                         code.push(basic)
@@ -64,6 +88,26 @@ fn srcu_for_domain_id() -> TokenStream {
     quote!(self.domain.read(|domain| domain.domain_id()))
 }
 
+fn srcu_for_health_check() -> TokenStream {
+    quote!(
+        let slot = self.watch.enter();
+        self.counter.inc();
+        let res = self.domain.read(|domain| domain.health_check());
+        self.counter.dec();
+        self.watch.exit(slot);
+        res
+    )
+}
+
+fn rwlock_for_health_check() -> TokenStream {
+    quote!(
+        if self.flag.load(core::sync::atomic::Ordering::SeqCst) {
+            return self.__health_check_with_lock();
+        }
+        self.__health_check_no_lock()
+    )
+}
+
 fn rwlock_for_domain_id() -> TokenStream {
     quote!(
         if self.flag.load(core::sync::atomic::Ordering::SeqCst) {
@@ -91,12 +135,37 @@ fn lock_for_domain_id(ident: &Ident) -> TokenStream {
                 drop(lock);
                 r
             }
+            fn __health_check(&self)->AlienResult<()>{
+                self.domain.read_directly(|domain|domain.health_check())
+            }
+            fn __health_check_no_lock(&self)->AlienResult<()>{
+                let slot = self.watch.enter();
+                self.counter.inc();
+                let res = self.__health_check();
+                self.counter.dec();
+                self.watch.exit(slot);
+                res
+            }
+            #[cold]
+            fn __health_check_with_lock(&self)->AlienResult<()>{
+                let r_lock = self.lock.read();
+                let res = self.__health_check();
+                drop(r_lock);
+                res
+            }
         }
     )
 }
 
 fn impl_srcu_code() -> TokenStream {
-    quote!(self.domain.read(|domain| domain.handle_irq()))
+    quote!(
+        let slot = self.watch.enter();
+        self.counter.inc();
+        let res = self.domain.read(|domain| domain.handle_irq());
+        self.counter.dec();
+        self.watch.exit(slot);
+        res
+    )
 }
 
 fn impl_rwlock_code(_ident: &Ident) -> TokenStream {
@@ -114,9 +183,11 @@ fn impl_lock_code(ident: &Ident) -> TokenStream {
                 self.domain.read_directly(|domain|domain.handle_irq())
             }
             fn __handle_irq_no_lock(&self) -> AlienResult<()> {
+                let slot = self.watch.enter();
                 self.counter.inc();
                 let res = self.__handle_irq();
                 self.counter.dec();
+                self.watch.exit(slot);
                 res
             }
             #[cold]
@@ -35,6 +35,11 @@ pub fn impl_unwind_supertrait(ident: Ident, trait_def: ItemTrait) -> TokenStream
                                 fn domain_id(&self)->u64{
                                     self.0.domain_id()
                                 }
+                                fn health_check(&self)->AlienResult<()>{
+                                    basic::catch_unwind(||{
+                                        self.0.health_check()
+                                    })
+                                }
                             }
                         );
                         code.push(basic)
@@ -0,0 +1,248 @@
+//! Run a domain in a process on the host.
+//!
+//! [`HostedCore`] implements [`CoreFunction`] with std: the pages come from
+//! the allocator of the process, the tasks are threads and the timer of
+//! `arch` counts with `std::time`. A domain built with the `hosted` feature
+//! of `basic` is a library of the process, a test calls its `main` with
+//! [`run_domain`] and uses the returned domain directly.
+//!
+//! The statics of `shared_heap` and `storage` are the statics of the
+//! process, so a process runs one domain. The domains it depends on are
+//! plain Rust objects given to [`HostedCore::add_domain`].
+
+mod task;
+
+use std::{
+    alloc::{alloc_zeroed, dealloc, Layout},
+    any::Any,
+    backtrace::Backtrace,
+    collections::BTreeMap,
+    io::Write,
+    ops::Range,
+    sync::{Arc, LazyLock, Mutex, OnceLock},
+    time::Instant,
+};
+
+use corelib::{
+    domain_info::{DomainInfo, RestartPolicy},
+    event::{DomainEvent, EventDelivery, EventFilter},
+    AlienError, AlienResult, CoreFunction,
+};
+use domain_manager::{
+    event,
+    sheap::SHARED_HEAP_ALLOCATOR,
+    storage_heap::{create_domain_database, get_domain_database, DOMAIN_DATA_ALLOCATOR},
+    FRAME_SIZE,
+};
+use interface::{DomainType, DomainTypeRaw};
+use shared_heap::SharedHeapAlloc;
+use storage::StorageArg;
+use task_meta::{OperationResult, TaskOperation, TlsTemplate};
+
+/// The `main` of a domain.
+pub type DomainMain<T> =
+    fn(&'static dyn CoreFunction, u64, &'static dyn SharedHeapAlloc, StorageArg) -> Box<T>;
+
+/// The [`CoreFunction`] of a hosted domain.
+pub struct HostedCore {
+    domains: Mutex<BTreeMap<String, DomainType>>,
+    info: Arc<ksync::Mutex<DomainInfo>>,
+}
+
+static CORE: LazyLock<HostedCore> = LazyLock::new(|| HostedCore {
+    domains: Mutex::new(BTreeMap::new()),
+    info: Arc::new(ksync::Mutex::new(DomainInfo::new())),
+});
+
+/// Return the backend of the process.
+pub fn hosted_core() -> &'static HostedCore {
+    &CORE
+}
+
+impl HostedCore {
+    /// Make `domain` visible to `get_domain` of the hosted domain.
+    pub fn add_domain(&self, name: &str, domain: DomainType) {
+        self.domains
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), domain);
+    }
+
+    /// Remove a domain added by [`HostedCore::add_domain`].
+    pub fn remove_domain(&self, name: &str) -> Option<DomainType> {
+        self.domains.lock().unwrap().remove(name)
+    }
+}
+
+fn page_layout(n: usize) -> Layout {
+    Layout::from_size_align(n * FRAME_SIZE, FRAME_SIZE).unwrap()
+}
+
+fn now_ns() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+impl CoreFunction for HostedCore {
+    fn sys_alloc_pages(&self, _domain_id: u64, n: usize) -> *mut u8 {
+        unsafe { alloc_zeroed(page_layout(n)) }
+    }
+
+    fn sys_free_pages(&self, _domain_id: u64, p: *mut u8, n: usize) {
+        unsafe { dealloc(p, page_layout(n)) }
+    }
+
+    fn sys_write_console(&self, s: &str) {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(s.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn sys_backtrace(&self, domain_id: u64) {
+        eprintln!(
+            "[Domain:{}] backtrace:\n{}",
+            domain_id,
+            Backtrace::force_capture()
+        );
+    }
+
+    /// The domain is a part of the process, std resolves its symbols.
+    fn sys_symbolize(&self, _addr: usize, _buf: &mut [u8]) -> AlienResult<usize> {
+        Err(AlienError::ENOENT)
+    }
+
+    /// The thread locals of the domain are the thread locals of std.
+    fn sys_tls_template(&self, _domain_id: u64) -> Option<TlsTemplate> {
+        None
+    }
+
+    /// The loader of the host has run the constructors of the domain.
+    fn sys_init_array(&self, _domain_id: u64) -> Option<Range<usize>> {
+        None
+    }
+
+    fn sys_trampoline_addr(&self) -> usize {
+        0
+    }
+
+    fn sys_kernel_satp(&self) -> usize {
+        0
+    }
+
+    fn sys_trap_from_user(&self) -> usize {
+        0
+    }
+
+    fn sys_trap_to_user(&self) -> usize {
+        0
+    }
+
+    fn blk_crash_trick(&self) -> bool {
+        false
+    }
+
+    fn sys_get_domain(&self, domain_id: u64, name: &str) -> Option<DomainType> {
+        let domain = self.domains.lock().unwrap().get(name).cloned()?;
+        let mut info = self.info.lock();
+        let caller = info
+            .domain_list
+            .get(&domain_id)
+            .map_or_else(|| domain_id.to_string(), |data| data.name.clone());
+        domain_manager::graph::add_dependency(&caller, name);
+        info.dependencies = domain_manager::graph::dependency_map();
+        Some(domain)
+    }
+
+    fn sys_create_domain(
+        &self,
+        _domain_file_name: &str,
+        _identifier: &mut [u8],
+    ) -> AlienResult<DomainType> {
+        Err(AlienError::ENOSYS)
+    }
+
+    fn sys_register_domain(
+        &self,
+        _ident: &str,
+        _ty: DomainTypeRaw,
+        _data: &[u8],
+        _policy: RestartPolicy,
+    ) -> AlienResult<()> {
+        Err(AlienError::ENOSYS)
+    }
+
+    fn sys_set_restart_policy(&self, name: &str, policy: RestartPolicy) -> AlienResult<()> {
+        domain_manager::restart::set_policy(name, policy);
+        Ok(())
+    }
+
+    fn sys_update_domain(
+        &self,
+        _old_domain_name: &str,
+        _new_domain_name: &str,
+        _ty: DomainTypeRaw,
+    ) -> AlienResult<()> {
+        Err(AlienError::ENOSYS)
+    }
+
+    fn sys_reload_domain(&self, _domain_name: &str) -> AlienResult<()> {
+        Err(AlienError::ENOSYS)
+    }
+
+    fn sys_subscribe_domain_event(
+        &self,
+        domain_id: u64,
+        filter: EventFilter<'_>,
+        delivery: EventDelivery,
+    ) -> AlienResult<u64> {
+        Ok(event::subscribe(domain_id, filter, delivery))
+    }
+
+    fn sys_unsubscribe_domain_event(&self, subscription: u64) -> AlienResult<()> {
+        Ok(event::unsubscribe(subscription)?)
+    }
+
+    fn sys_poll_domain_event(&self, subscription: u64) -> AlienResult<Option<DomainEvent>> {
+        Ok(event::poll(subscription)?)
+    }
+
+    /// The process has no physical addresses, the address is returned as is.
+    fn vaddr_to_paddr_in_kernel(&self, vaddr: usize) -> AlienResult<usize> {
+        Ok(vaddr)
+    }
+
+    fn task_op(&self, op: TaskOperation) -> AlienResult<OperationResult> {
+        task::task_op(op)
+    }
+
+    fn checkout_shared_data(&self) -> AlienResult<()> {
+        domain_manager::sheap::checkout_shared_data();
+        Ok(())
+    }
+
+    fn domain_info(&self) -> AlienResult<Arc<dyn Any + Send + Sync>> {
+        domain_manager::stats::refresh_domain_info(&mut self.info.lock(), |_| None);
+        Ok(self.info.clone())
+    }
+}
+
+/// Call the `main` of a domain with the shared heap and the storage of the
+/// process, like the loader does for a domain of the kernel.
+pub fn run_domain<T: ?Sized>(domain_id: u64, main: DomainMain<T>) -> Box<T> {
+    run_domain_with(hosted_core(), domain_id, main)
+}
+
+/// [`run_domain`] with another [`CoreFunction`], e.g. one which wraps
+/// [`HostedCore`].
+pub fn run_domain_with<T: ?Sized>(
+    core: &'static dyn CoreFunction,
+    domain_id: u64,
+    main: DomainMain<T>,
+) -> Box<T> {
+    domain_manager::init_timer(now_ns);
+    event::init_catch_unwind(|f| std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_ok());
+    create_domain_database(domain_id);
+    let database = get_domain_database(domain_id).unwrap();
+    let storage_arg = StorageArg::new(DOMAIN_DATA_ALLOCATOR, database);
+    main(core, domain_id, SHARED_HEAP_ALLOCATOR, storage_arg)
+}
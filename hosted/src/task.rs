@@ -0,0 +1,146 @@
+//! The tasks of a hosted domain, every task is a thread of the process.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicI8, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+use corelib::{AlienError, AlienResult};
+use task_meta::{OperationResult, TaskMeta, TaskOperation};
+
+struct HostTask {
+    tid: Option<usize>,
+    /// Set by a wakeup, a wakeup before the wait is not lost.
+    woken: Mutex<bool>,
+    cond: Condvar,
+    exited: AtomicBool,
+    priority: AtomicI8,
+}
+
+impl HostTask {
+    fn new(tid: Option<usize>, priority: i8) -> Arc<Self> {
+        Arc::new(Self {
+            tid,
+            woken: Mutex::new(false),
+            cond: Condvar::new(),
+            exited: AtomicBool::new(false),
+            priority: AtomicI8::new(priority),
+        })
+    }
+
+    fn wait(&self) {
+        let mut woken = self.woken.lock().unwrap();
+        while !*woken {
+            woken = self.cond.wait(woken).unwrap();
+        }
+        *woken = false;
+    }
+
+    fn wakeup(&self) {
+        *self.woken.lock().unwrap() = true;
+        self.cond.notify_one();
+    }
+}
+
+/// The payload of the unwind which ends a task in [`TaskOperation::Exit`].
+struct TaskExit;
+
+static TASKS: Mutex<BTreeMap<usize, Arc<HostTask>>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// The task of the thread, a thread which is not created by the domain
+    /// gets a task without tid.
+    static CURRENT: RefCell<Option<Arc<HostTask>>> = const { RefCell::new(None) };
+}
+
+fn current() -> Arc<HostTask> {
+    CURRENT.with(|current| {
+        current
+            .borrow_mut()
+            .get_or_insert_with(|| HostTask::new(None, 0))
+            .clone()
+    })
+}
+
+fn create(task_meta: TaskMeta) -> AlienResult<OperationResult> {
+    let tid = task_meta.task_basic_info.tid;
+    let entry = task_meta.task_basic_info.context.ra();
+    if entry == 0 {
+        return Err(AlienError::EINVAL);
+    }
+    let task = HostTask::new(Some(tid), task_meta.scheduling_info.nice);
+    let mut tasks = TASKS.lock().unwrap();
+    if tasks.contains_key(&tid) {
+        return Err(AlienError::EEXIST);
+    }
+    tasks.insert(tid, task.clone());
+    drop(tasks);
+    // The entry of a kernel task is a function which never returns, it ends
+    // with `exit_now`.
+    let entry = unsafe { core::mem::transmute::<usize, fn()>(entry) };
+    thread::Builder::new()
+        .name(format!("task-{}", tid))
+        .spawn(move || {
+            CURRENT.with(|current| *current.borrow_mut() = Some(task.clone()));
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(entry)) {
+                if !payload.is::<TaskExit>() {
+                    eprintln!("task {} panicked", tid);
+                }
+            }
+            task.exited.store(true, Ordering::Release);
+        })
+        .map_err(|_| AlienError::ENOMEM)?;
+    // A thread has no kernel stack.
+    Ok(OperationResult::KstackTop(0))
+}
+
+fn exit() -> ! {
+    current().exited.store(true, Ordering::Release);
+    panic::resume_unwind(Box::new(TaskExit))
+}
+
+pub(crate) fn task_op(op: TaskOperation) -> AlienResult<OperationResult> {
+    let res = match op {
+        TaskOperation::Create(task_meta) => return create(task_meta),
+        TaskOperation::Wait => {
+            current().wait();
+            OperationResult::Null
+        }
+        TaskOperation::Wakeup(tid) => {
+            let task = TASKS.lock().unwrap().get(&tid).cloned();
+            task.ok_or(AlienError::ESRCH)?.wakeup();
+            OperationResult::Null
+        }
+        TaskOperation::Yield => {
+            thread::yield_now();
+            OperationResult::Null
+        }
+        TaskOperation::Exit => exit(),
+        TaskOperation::Remove(tid) => {
+            TASKS.lock().unwrap().remove(&tid);
+            OperationResult::Null
+        }
+        TaskOperation::Current => OperationResult::Current(current().tid),
+        TaskOperation::ExitOver(tid) => {
+            let tasks = TASKS.lock().unwrap();
+            let over = tasks
+                .get(&tid)
+                .is_none_or(|task| task.exited.load(Ordering::Acquire));
+            OperationResult::ExitOver(over)
+        }
+        TaskOperation::SetPriority(priority) => {
+            current().priority.store(priority, Ordering::Relaxed);
+            OperationResult::Null
+        }
+        TaskOperation::GetPriority => {
+            OperationResult::Priority(current().priority.load(Ordering::Relaxed))
+        }
+    };
+    Ok(res)
+}
@@ -3,7 +3,7 @@ use gproxy::proxy;
 use shared_heap::DVec;
 
 use super::AlienResult;
-use crate::{Basic, DeviceBase};
+use crate::{Basic, DeviceBase, BASIC_ABI_FINGERPRINT, DEVICE_BASE_ABI_FINGERPRINT};
 
 #[proxy(BufUartDomainProxy, RwLock, String)]
 pub trait BufUartDomain: DeviceBase + Basic + DowncastSync {
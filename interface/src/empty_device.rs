@@ -3,7 +3,7 @@ use gproxy::proxy;
 use shared_heap::DVec;
 
 use super::AlienResult;
-use crate::Basic;
+use crate::{Basic, BASIC_ABI_FINGERPRINT};
 
 #[proxy(EmptyDeviceDomainProxy, SRCU)]
 pub trait EmptyDeviceDomain: Basic + DowncastSync {
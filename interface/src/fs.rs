@@ -4,7 +4,7 @@ use shared_heap::{DBox, DVec};
 use vfscore::{fstype::FileSystemFlags, inode::InodeAttr, superblock::SuperType, utils::*};
 
 use super::AlienResult;
-use crate::{Basic, DirEntryWrapper, InodeID};
+use crate::{Basic, DirEntryWrapper, InodeID, BASIC_ABI_FINGERPRINT};
 
 #[proxy(FsDomainProxy, RwLock)]
 pub trait FsDomain: Basic + DowncastSync {
@@ -4,7 +4,7 @@ use downcast_rs::{impl_downcast, DowncastSync};
 use gproxy::proxy;
 
 use super::AlienResult;
-use crate::{Basic, DeviceBase};
+use crate::{Basic, DeviceBase, BASIC_ABI_FINGERPRINT, DEVICE_BASE_ABI_FINGERPRINT};
 
 #[proxy(InputDomainProxy,RwLock,Range<usize>)]
 pub trait InputDomain: DeviceBase + Basic + DowncastSync {
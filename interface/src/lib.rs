@@ -23,16 +23,20 @@ mod vfs;
 extern crate alloc;
 
 use alloc::sync::Arc;
-use core::{
-    any::Any,
-    fmt::{Debug, Display},
-};
+use core::{any::Any, fmt::Debug};
 
+pub use domain_meta::DomainTypeRaw;
 use pconst::LinuxErrno;
 
 type AlienError = LinuxErrno;
 type AlienResult<T> = Result<T, LinuxErrno>;
 
+/// The version of the domain interfaces, it is recorded in the domain manifest.
+///
+/// Increase it when a change of the interfaces breaks the old domains.
+pub const INTERFACE_VERSION: u32 = 2;
+
+#[gproxy::abi_fingerprint]
 pub trait Basic: Send + Sync + Debug + Any {
     fn domain_id(&self) -> u64;
 
@@ -44,8 +48,16 @@ pub trait Basic: Send + Sync + Debug + Any {
     fn is_active(&self) -> bool {
         false
     }
+    /// Return an error if the domain can not serve calls, the supervisor of
+    /// the kernel reloads it then.
+    ///
+    /// It must return quickly and must not call other domains.
+    fn health_check(&self) -> AlienResult<()> {
+        Ok(())
+    }
 }
 
+#[gproxy::abi_fingerprint]
 pub trait DeviceBase: Send + Sync {
     fn handle_irq(&self) -> AlienResult<()>;
 }
@@ -121,84 +133,31 @@ impl DomainType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd)]
-pub enum DomainTypeRaw {
-    FsDomain = 1,
-    BlkDeviceDomain = 2,
-    CacheBlkDeviceDomain = 3,
-    RtcDomain = 4,
-    GpuDomain = 5,
-    InputDomain = 6,
-    VfsDomain = 7,
-    UartDomain = 8,
-    PLICDomain = 9,
-    TaskDomain = 10,
-    SysCallDomain = 11,
-    ShadowBlockDomain = 12,
-    BufUartDomain = 13,
-    NetDeviceDomain = 14,
-    BufInputDomain = 15,
-    EmptyDeviceDomain = 16,
-    DevFsDomain = 17,
-    SchedulerDomain = 18,
-    LogDomain = 19,
-    NetDomain = 20,
-}
-
-impl Display for DomainTypeRaw {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match self {
-            DomainTypeRaw::FsDomain => write!(f, "FsDomain"),
-            DomainTypeRaw::BlkDeviceDomain => write!(f, "BlkDeviceDomain"),
-            DomainTypeRaw::CacheBlkDeviceDomain => write!(f, "CacheBlkDeviceDomain"),
-            DomainTypeRaw::RtcDomain => write!(f, "RtcDomain"),
-            DomainTypeRaw::GpuDomain => write!(f, "GpuDomain"),
-            DomainTypeRaw::InputDomain => write!(f, "InputDomain"),
-            DomainTypeRaw::VfsDomain => write!(f, "VfsDomain"),
-            DomainTypeRaw::UartDomain => write!(f, "UartDomain"),
-            DomainTypeRaw::PLICDomain => write!(f, "PLICDomain"),
-            DomainTypeRaw::TaskDomain => write!(f, "TaskDomain"),
-            DomainTypeRaw::SysCallDomain => write!(f, "SysCallDomain"),
-            DomainTypeRaw::ShadowBlockDomain => write!(f, "ShadowBlockDomain"),
-            DomainTypeRaw::BufUartDomain => write!(f, "BufUartDomain"),
-            DomainTypeRaw::NetDeviceDomain => write!(f, "NetDeviceDomain"),
-            DomainTypeRaw::BufInputDomain => write!(f, "BufInputDomain"),
-            DomainTypeRaw::EmptyDeviceDomain => write!(f, "EmptyDeviceDomain"),
-            DomainTypeRaw::DevFsDomain => write!(f, "DevFsDomain"),
-            DomainTypeRaw::SchedulerDomain => write!(f, "SchedulerDomain"),
-            DomainTypeRaw::LogDomain => write!(f, "LogDomain"),
-            DomainTypeRaw::NetDomain => write!(f, "NetDomain"),
-        }
-    }
-}
-
-impl TryFrom<u8> for DomainTypeRaw {
-    type Error = ();
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(DomainTypeRaw::FsDomain),
-            2 => Ok(DomainTypeRaw::BlkDeviceDomain),
-            3 => Ok(DomainTypeRaw::CacheBlkDeviceDomain),
-            4 => Ok(DomainTypeRaw::RtcDomain),
-            5 => Ok(DomainTypeRaw::GpuDomain),
-            6 => Ok(DomainTypeRaw::InputDomain),
-            7 => Ok(DomainTypeRaw::VfsDomain),
-            8 => Ok(DomainTypeRaw::UartDomain),
-            9 => Ok(DomainTypeRaw::PLICDomain),
-            10 => Ok(DomainTypeRaw::TaskDomain),
-            11 => Ok(DomainTypeRaw::SysCallDomain),
-            12 => Ok(DomainTypeRaw::ShadowBlockDomain),
-            13 => Ok(DomainTypeRaw::BufUartDomain),
-            14 => Ok(DomainTypeRaw::NetDeviceDomain),
-            15 => Ok(DomainTypeRaw::BufInputDomain),
-            16 => Ok(DomainTypeRaw::EmptyDeviceDomain),
-            17 => Ok(DomainTypeRaw::DevFsDomain),
-            18 => Ok(DomainTypeRaw::SchedulerDomain),
-            19 => Ok(DomainTypeRaw::LogDomain),
-            20 => Ok(DomainTypeRaw::NetDomain),
-            _ => Err(()),
-        }
+/// Return the ABI fingerprint of the interface trait of the domain type.
+///
+/// It is embedded in the manifest of the domain and checked by the loader.
+pub const fn abi_fingerprint(ty: DomainTypeRaw) -> u64 {
+    match ty {
+        DomainTypeRaw::FsDomain => FS_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::BlkDeviceDomain => BLK_DEVICE_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::CacheBlkDeviceDomain => CACHE_BLK_DEVICE_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::RtcDomain => RTC_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::GpuDomain => GPU_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::InputDomain => INPUT_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::VfsDomain => VFS_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::UartDomain => UART_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::PLICDomain => PLICDOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::TaskDomain => TASK_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::SysCallDomain => SYS_CALL_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::ShadowBlockDomain => SHADOW_BLOCK_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::BufUartDomain => BUF_UART_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::NetDeviceDomain => NET_DEVICE_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::BufInputDomain => BUF_INPUT_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::EmptyDeviceDomain => EMPTY_DEVICE_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::SchedulerDomain => SCHEDULER_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::LogDomain => LOG_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::NetDomain => NET_DOMAIN_ABI_FINGERPRINT,
+        DomainTypeRaw::DevFsDomain => DEV_FS_DOMAIN_ABI_FINGERPRINT,
     }
 }
 
@@ -3,7 +3,7 @@ use gproxy::proxy;
 use shared_heap::DVec;
 
 use super::AlienResult;
-use crate::Basic;
+use crate::{Basic, BASIC_ABI_FINGERPRINT};
 
 #[proxy(LogDomainProxy, SRCU)]
 pub trait LogDomain: Basic + DowncastSync {
@@ -9,7 +9,7 @@ use pconst::{
 use shared_heap::{DBox, DVec};
 
 use super::AlienResult;
-use crate::{Basic, DeviceBase};
+use crate::{Basic, DeviceBase, BASIC_ABI_FINGERPRINT, DEVICE_BASE_ABI_FINGERPRINT};
 
 pub type SocketID = usize;
 
@@ -5,7 +5,7 @@ use gproxy::proxy;
 use shared_heap::DVec;
 
 use super::AlienResult;
-use crate::Basic;
+use crate::{Basic, BASIC_ABI_FINGERPRINT};
 #[proxy(PLICDomainProxy, RwLock, PlicInfo)]
 pub trait PLICDomain: Basic + DowncastSync {
     fn init(&self, plic_info: &PlicInfo) -> AlienResult<()>;
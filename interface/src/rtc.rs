@@ -6,7 +6,7 @@ use pconst::io::RtcTime;
 use shared_heap::DBox;
 
 use super::AlienResult;
-use crate::{Basic, DeviceBase};
+use crate::{Basic, DeviceBase, BASIC_ABI_FINGERPRINT, DEVICE_BASE_ABI_FINGERPRINT};
 
 #[proxy(RtcDomainProxy,RwLock,Range<usize>)]
 pub trait RtcDomain: DeviceBase + Basic + DowncastSync {
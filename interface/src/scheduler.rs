@@ -4,7 +4,7 @@ use shared_heap::DBox;
 use task_meta::TaskSchedulingInfo;
 
 use super::AlienResult;
-use crate::Basic;
+use crate::{Basic, BASIC_ABI_FINGERPRINT};
 
 #[proxy(SchedulerDomainProxy, RwLock)]
 pub trait SchedulerDomain: Basic + DowncastSync {
@@ -3,7 +3,7 @@ use gproxy::{no_check, proxy};
 use shared_heap::DVec;
 
 use super::AlienResult;
-use crate::{Basic, DeviceBase};
+use crate::{Basic, DeviceBase, BASIC_ABI_FINGERPRINT, DEVICE_BASE_ABI_FINGERPRINT};
 
 #[proxy(ShadowBlockDomainProxy, SRCU, String)]
 pub trait ShadowBlockDomain: DeviceBase + Basic + DowncastSync {
@@ -2,7 +2,7 @@ use downcast_rs::{impl_downcast, DowncastSync};
 use gproxy::proxy;
 
 use super::AlienResult;
-use crate::Basic;
+use crate::{Basic, BASIC_ABI_FINGERPRINT};
 
 #[proxy(SysCallDomainProxy, SRCU)]
 pub trait SysCallDomain: Basic + DowncastSync {
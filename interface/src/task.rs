@@ -6,7 +6,7 @@ use pod::Pod;
 use shared_heap::{DBox, DVec};
 
 use super::AlienResult;
-use crate::{vfs::InodeID, Basic};
+use crate::{vfs::InodeID, Basic, BASIC_ABI_FINGERPRINT};
 #[proxy(TaskDomainProxy, RwLock)]
 pub trait TaskDomain: Basic + DowncastSync {
     fn init(&self) -> AlienResult<()>;
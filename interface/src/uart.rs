@@ -5,7 +5,7 @@ use gproxy::proxy;
 use shared_heap::DVec;
 
 use super::{AlienError, AlienResult};
-use crate::{Basic, DeviceBase};
+use crate::{Basic, DeviceBase, BASIC_ABI_FINGERPRINT, DEVICE_BASE_ABI_FINGERPRINT};
 #[proxy(UartDomainProxy,RwLock,Range<usize>)]
 pub trait UartDomain: DeviceBase + Basic + DowncastSync {
     fn init(&self, device_info: &Range<usize>) -> AlienResult<()>;
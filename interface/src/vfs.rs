@@ -5,7 +5,7 @@ use shared_heap::{DBox, DVec};
 use vfscore::utils::{VfsFileStat, VfsNodeType, VfsPollEvents};
 
 use super::AlienResult;
-use crate::{Basic, SocketID};
+use crate::{Basic, SocketID, BASIC_ABI_FINGERPRINT};
 pub type InodeID = u64;
 pub const VFS_ROOT_ID: InodeID = 0;
 pub const VFS_STDIN_ID: InodeID = 1;
@@ -1,10 +1,13 @@
-#![no_std]
+#![cfg_attr(not(feature = "hosted"), no_std)]
 use core::{
     cell::{RefCell, RefMut},
     ops::Deref,
 };
 
-use arch::{hart_id, interrupt_disable, interrupt_enable, is_interrupt_enable};
+#[cfg(not(feature = "hosted"))]
+use arch::hart_id;
+use arch::{interrupt_disable, interrupt_enable, is_interrupt_enable};
+#[cfg(not(feature = "hosted"))]
 use config::CPU_NUM;
 use kernel_sync::{EmptyLockAction, LockAction, TicketMutexGuard};
 
@@ -53,12 +56,25 @@ impl<T> SafeRefCell<T> {
 #[allow(clippy::declare_interior_mutable_const)]
 const DEFAULT_CPU: SafeRefCell<Cpu> = SafeRefCell::new(Cpu::new());
 
+#[cfg(not(feature = "hosted"))]
 static CPUS: [SafeRefCell<Cpu>; CPU_NUM] = [DEFAULT_CPU; CPU_NUM];
 
+#[cfg(not(feature = "hosted"))]
 fn mycpu() -> RefMut<'static, Cpu> {
     CPUS[hart_id()].0.borrow_mut()
 }
 
+#[cfg(feature = "hosted")]
+std::thread_local! {
+    // A process may run more threads than `CPU_NUM`, every thread is a cpu.
+    static CPU: &'static SafeRefCell<Cpu> = std::boxed::Box::leak(std::boxed::Box::new(DEFAULT_CPU));
+}
+
+#[cfg(feature = "hosted")]
+fn mycpu() -> RefMut<'static, Cpu> {
+    CPU.with(|cpu| cpu.0.borrow_mut())
+}
+
 pub(crate) fn push_off() {
     let old = is_interrupt_enable();
     interrupt_disable();
@@ -0,0 +1,38 @@
+use core::ops::Range;
+
+use crate::FRAME_SIZE;
+
+/// Randomize the base address of the domains within a window.
+#[derive(Debug, Clone)]
+pub struct AslrConfig {
+    /// The range of virtual addresses the domain areas are placed in.
+    pub window: Range<usize>,
+    /// The source of randomness, e.g. a hardware random number generator.
+    pub entropy: fn() -> u64,
+    /// How many random bases are tried before the loader lets the kernel
+    /// pick the address.
+    pub retries: usize,
+}
+
+impl AslrConfig {
+    pub fn new(window: Range<usize>, entropy: fn() -> u64) -> Self {
+        Self {
+            window,
+            entropy,
+            retries: 8,
+        }
+    }
+
+    /// Return a random page aligned base for an area of `size` bytes, or
+    /// `None` if the area does not fit in the window.
+    pub fn random_base(&self, size: usize) -> Option<usize> {
+        let start = self.window.start.checked_next_multiple_of(FRAME_SIZE)?;
+        let last = self.window.end.checked_sub(size)?;
+        if last < start {
+            return None;
+        }
+        let slots = (last - start) / FRAME_SIZE + 1;
+        let slot = ((self.entropy)() % slots as u64) as usize;
+        Some(start + slot * FRAME_SIZE)
+    }
+}
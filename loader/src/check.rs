@@ -0,0 +1,135 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use manifest::Manifest;
+use memory_addr::VirtAddr;
+use xmas_elf::{
+    header::Machine,
+    program::Type,
+    sections::SectionData,
+    symbol_table::{Entry, Type as SymbolType},
+    ElfFile,
+};
+
+use crate::{
+    elf_manifest,
+    error::{LoaderError, Result},
+    page_permissions, parse_elf,
+    reloc::{check_machine, relocate_dyn, NATIVE_MACHINE},
+    segment_end, segment_flags, DomainMappingFlags, DomainSegment, FRAME_SIZE,
+};
+
+/// What the loader finds in a domain ELF file, it is collected without
+/// mapping the domain.
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub machine: Machine,
+    /// The entry point, relative to the start of the domain area.
+    pub entry: usize,
+    /// The name of the function at the entry point, if the ELF file has symbols.
+    pub entry_symbol: Option<String>,
+    /// The size of the domain area.
+    pub area_size: usize,
+    /// The ranges are relative to the start of the domain area.
+    pub segments: Vec<DomainSegment>,
+    /// The number of dynamic relocations.
+    pub relocations: usize,
+    pub manifest: Option<Manifest>,
+}
+
+/// Return the name of the function at `addr`.
+fn function_at(elf: &ElfFile, addr: u64) -> Option<String> {
+    elf.section_iter()
+        .find_map(|section| match section.get_data(elf) {
+            Ok(SectionData::SymbolTable64(symbols)) => symbols
+                .iter()
+                .find(|s| s.value() == addr && s.get_type() == Ok(SymbolType::Func))
+                .and_then(|s| s.get_name(elf).ok()),
+            Ok(SectionData::DynSymbolTable64(symbols)) => symbols
+                .iter()
+                .find(|s| s.value() == addr && s.get_type() == Ok(SymbolType::Func))
+                .and_then(|s| s.get_name(elf).ok()),
+            _ => None,
+        })
+        .map(|name| name.to_string())
+}
+
+/// Check that the loader can load the ELF file of a domain.
+///
+/// The checks are the ones [`DomainLoader::load`](crate::DomainLoader::load)
+/// does: the machine, the bounds and permissions of the segments, the
+/// relocation types and symbols, and that the entry point is in an
+/// executable segment.
+pub fn check_image(elf_binary: &[u8]) -> Result<ImageInfo> {
+    check_image_for(elf_binary, NATIVE_MACHINE)
+}
+
+/// Check the ELF file of a domain for a kernel running on `machine`, tools
+/// that package domains for another machine use it.
+pub fn check_image_for(elf_binary: &[u8], machine: Machine) -> Result<ImageInfo> {
+    let elf = parse_elf(elf_binary)?;
+    check_machine(&elf, machine)?;
+    let mut segments = Vec::new();
+    for ph in elf
+        .program_iter()
+        .filter(|ph| ph.get_type() == Ok(Type::Load))
+    {
+        let overflow = LoaderError::SegmentOverflow {
+            offset: ph.offset(),
+            vaddr: ph.virtual_addr(),
+            size: ph.file_size(),
+        };
+        let in_file = ph
+            .offset()
+            .checked_add(ph.file_size())
+            .is_some_and(|end| end as usize <= elf_binary.len());
+        if ph.file_size() > ph.mem_size() || !in_file {
+            return Err(overflow);
+        }
+        let end = segment_end(&ph)?;
+        if end as usize > usize::MAX - FRAME_SIZE {
+            return Err(overflow);
+        }
+        let start = VirtAddr::from(ph.virtual_addr() as usize).align_down_4k();
+        let end = VirtAddr::from(end as usize).align_up_4k();
+        segments.push(DomainSegment {
+            range: start.as_usize()..end.as_usize(),
+            flags: segment_flags(&ph),
+        });
+    }
+    let area_size = segments
+        .iter()
+        .map(|seg| seg.range.end)
+        .max()
+        .ok_or(LoaderError::NoLoadSegment)?;
+    page_permissions(&segments, 0, area_size / FRAME_SIZE)?;
+
+    let relocations = relocate_dyn(&elf, 0)?;
+    if let Some((offset, _)) = relocations
+        .iter()
+        .find(|(offset, _)| offset + core::mem::size_of::<usize>() > area_size)
+    {
+        return Err(LoaderError::RelocationOverflow {
+            offset: *offset as u64,
+        });
+    }
+
+    let entry = elf.header.pt2.entry_point();
+    let executable = segments.iter().any(|seg| {
+        seg.flags.contains(DomainMappingFlags::EXECUTE) && seg.range.contains(&(entry as usize))
+    });
+    if !executable {
+        return Err(LoaderError::BadEntry { entry });
+    }
+    Ok(ImageInfo {
+        machine,
+        entry: entry as usize,
+        entry_symbol: function_at(&elf, entry),
+        area_size,
+        segments,
+        relocations: relocations.len(),
+        manifest: elf_manifest(&elf)?,
+    })
+}
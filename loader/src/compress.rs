@@ -0,0 +1,169 @@
+use alloc::{borrow::Cow, vec, vec::Vec};
+
+use crate::error::{LoaderError, Result};
+
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// The max distance of a LZ4 match, the window of linked blocks.
+const LZ4_WINDOW: usize = 64 * 1024;
+/// The max size of a decompressed image if the loader sets none, 64 MiB.
+pub const DEFAULT_MAX_IMAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The compression of a domain image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+}
+
+/// Which form of a compressed image the loader keeps after loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageResidency {
+    /// Keep the decompressed image, a reload does not decompress again.
+    Decompressed,
+    /// Keep only the compressed image and decompress it on every load.
+    Compressed,
+}
+
+/// Detect the compression of the image by its magic.
+pub fn detect_compression(data: &[u8]) -> Compression {
+    if data.starts_with(&LZ4_MAGIC) {
+        Compression::Lz4
+    } else if data.starts_with(&ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+/// Decompress the image, an uncompressed image is borrowed.
+///
+/// The checksums of the frames are not checked, the integrity of the image
+/// is checked by the verify policy, so the image should be verified first.
+/// Decompression fails if the output grows over `max_size`, the sizes in the
+/// headers are not trusted.
+pub fn decompress(data: &[u8], max_size: usize) -> Result<Cow<[u8]>> {
+    match detect_compression(data) {
+        Compression::None => Ok(Cow::Borrowed(data)),
+        Compression::Lz4 => lz4_frame_decompress(data, max_size).map(Cow::Owned),
+        Compression::Zstd => zstd_decompress(data, max_size).map(Cow::Owned),
+    }
+}
+
+fn too_large() -> LoaderError {
+    LoaderError::Decompress("decompressed image is too large")
+}
+
+fn truncated() -> LoaderError {
+    LoaderError::Decompress("lz4 frame is truncated")
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).ok_or_else(truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Decompress a LZ4 frame, see the LZ4 frame format description.
+fn lz4_frame_decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    let flg = *data.get(4).ok_or_else(truncated)?;
+    let bd = *data.get(5).ok_or_else(truncated)?;
+    if flg >> 6 != 0b01 {
+        return Err(LoaderError::Decompress("unsupported lz4 frame version"));
+    }
+    let block_checksum = flg & 0x10 != 0;
+    let has_content_size = flg & 0x08 != 0;
+    let has_dict_id = flg & 0x01 != 0;
+    let max_block_size = match (bd >> 4) & 0x7 {
+        4 => 64 * 1024,
+        5 => 256 * 1024,
+        6 => 1024 * 1024,
+        7 => 4 * 1024 * 1024,
+        _ => return Err(LoaderError::Decompress("bad lz4 block size")),
+    };
+    let mut pos = 6;
+    let mut content_size = 0;
+    if has_content_size {
+        let bytes = data.get(pos..pos + 8).ok_or_else(truncated)?;
+        content_size = u64::from_le_bytes(bytes.try_into().unwrap()) as usize;
+        if content_size > max_size {
+            return Err(too_large());
+        }
+        pos += 8;
+    }
+    if has_dict_id {
+        return Err(LoaderError::Decompress("lz4 dictionary is not supported"));
+    }
+    // skip the header checksum
+    pos += 1;
+
+    // the content size is not trusted, the output grows with the blocks
+    let mut out = Vec::new();
+    loop {
+        let block_size = read_u32(data, pos)?;
+        pos += 4;
+        if block_size == 0 {
+            break;
+        }
+        let uncompressed = block_size & 0x8000_0000 != 0;
+        let len = (block_size & 0x7fff_ffff) as usize;
+        let block = data.get(pos..pos + len).ok_or_else(truncated)?;
+        pos += len;
+        if uncompressed {
+            if out.len() + block.len() > max_size {
+                return Err(too_large());
+            }
+            out.extend_from_slice(block);
+        } else {
+            let start = out.len();
+            let room = max_size.saturating_sub(start);
+            if room == 0 {
+                return Err(too_large());
+            }
+            out.resize(start + max_block_size.min(room), 0);
+            let (prev, rest) = out.split_at_mut(start);
+            // linked blocks refer to the data of the previous blocks
+            let dict = &prev[prev.len().saturating_sub(LZ4_WINDOW)..];
+            let n =
+                lz4_flex::block::decompress_into_with_dict(block, rest, dict).map_err(
+                    |e| match e {
+                        lz4_flex::block::DecompressError::OutputTooSmall { .. }
+                            if room < max_block_size =>
+                        {
+                            too_large()
+                        }
+                        _ => LoaderError::Decompress("bad lz4 block"),
+                    },
+                )?;
+            out.truncate(start + n);
+        }
+        if block_checksum {
+            pos += 4;
+        }
+    }
+    if has_content_size && out.len() != content_size {
+        return Err(LoaderError::Decompress("lz4 content size mismatch"));
+    }
+    Ok(out)
+}
+
+fn zstd_decompress(mut data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    use ruzstd::io::Read;
+    let mut decoder = ruzstd::StreamingDecoder::new(&mut data)
+        .map_err(|_| LoaderError::Decompress("bad zstd frame"))?;
+    let mut out = Vec::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = decoder
+            .read(&mut buf)
+            .map_err(|_| LoaderError::Decompress("bad zstd block"))?;
+        if n == 0 {
+            break;
+        }
+        if out.len() + n > max_size {
+            return Err(too_large());
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+    Ok(out)
+}
@@ -0,0 +1,181 @@
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+
+use manifest::ManifestError;
+use xmas_elf::header::Machine;
+
+use crate::VerifyError;
+
+pub type Result<T> = core::result::Result<T, LoaderError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoaderError {
+    /// The image is not an ELF file.
+    BadMagic,
+    /// The ELF header can not be parsed.
+    BadElf(&'static str),
+    /// Only 64-bit ELF files are supported.
+    UnsupportedClass,
+    UnsupportedMachine(Machine),
+    /// The domain is built for another machine than the kernel.
+    WrongMachine {
+        expected: Machine,
+        found: Machine,
+    },
+    /// The ELF file has no `PT_LOAD` segment.
+    NoLoadSegment,
+    /// The entry point is not in an executable segment.
+    BadEntry {
+        entry: u64,
+    },
+    /// A section can not be parsed.
+    CorruptSection(&'static str),
+    UnsupportedRelocation {
+        ty: u32,
+        offset: u64,
+    },
+    /// The symbol index of a relocation is out of the symbol table.
+    BadSymbolIndex {
+        index: u32,
+        offset: u64,
+    },
+    /// A symbol is not defined by the domain.
+    UndefinedSymbol {
+        name: String,
+        offset: u64,
+    },
+    /// A relocation writes outside of the domain area.
+    RelocationOverflow {
+        offset: u64,
+    },
+    /// A segment is outside of the file or the domain area.
+    SegmentOverflow {
+        offset: u64,
+        vaddr: u64,
+        size: u64,
+    },
+    /// The alignment of the `PT_TLS` segment is not a power of two, or the
+    /// thread local block can not be allocated with it.
+    BadTlsAlign {
+        align: u64,
+        size: u64,
+    },
+    /// A page would be both writable and executable.
+    WritableExecutable {
+        vaddr: usize,
+    },
+    /// The permission of the pages can not be changed.
+    Mapping {
+        vaddr: usize,
+        pages: usize,
+        reason: &'static str,
+    },
+    /// The image does not satisfy the verify policy.
+    Verify(VerifyError),
+    /// The manifest of the image is malformed.
+    Manifest(ManifestError),
+    /// The compressed image can not be decompressed.
+    Decompress(&'static str),
+    /// The domain is built against another version of the interfaces.
+    InterfaceMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// The domain is built against another interface.
+    AbiMismatch {
+        expected: u64,
+        found: u64,
+    },
+    /// The domain is registered with another type than its manifest declares.
+    TypeMismatch {
+        expected: u8,
+        found: u8,
+    },
+}
+
+impl Display for LoaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LoaderError::BadMagic => write!(f, "not an elf file"),
+            LoaderError::BadElf(e) => write!(f, "bad elf file: {}", e),
+            LoaderError::UnsupportedClass => write!(f, "only 64-bit elf files are supported"),
+            LoaderError::UnsupportedMachine(m) => write!(f, "unsupported machine: {:?}", m),
+            LoaderError::WrongMachine { expected, found } => {
+                write!(f, "the domain is built for {:?}, not {:?}", found, expected)
+            }
+            LoaderError::NoLoadSegment => write!(f, "no loadable segment"),
+            LoaderError::BadEntry { entry } => {
+                write!(f, "entry {:#x} is not in an executable segment", entry)
+            }
+            LoaderError::CorruptSection(name) => write!(f, "corrupt section {}", name),
+            LoaderError::UnsupportedRelocation { ty, offset } => {
+                write!(f, "unsupported relocation type {} at {:#x}", ty, offset)
+            }
+            LoaderError::BadSymbolIndex { index, offset } => {
+                write!(f, "bad symbol index {} at {:#x}", index, offset)
+            }
+            LoaderError::UndefinedSymbol { name, offset } => {
+                write!(f, "undefined symbol {} at {:#x}", name, offset)
+            }
+            LoaderError::RelocationOverflow { offset } => {
+                write!(f, "relocation at {:#x} is out of the domain", offset)
+            }
+            LoaderError::BadTlsAlign { align, size } => write!(
+                f,
+                "tls segment of size {:#x} has a bad alignment {:#x}",
+                size, align
+            ),
+            LoaderError::SegmentOverflow {
+                offset,
+                vaddr,
+                size,
+            } => write!(
+                f,
+                "segment at offset {:#x}, vaddr {:#x}, size {:#x} is out of bounds",
+                offset, vaddr, size
+            ),
+            LoaderError::WritableExecutable { vaddr } => {
+                write!(f, "page {:#x} is writable and executable", vaddr)
+            }
+            LoaderError::Mapping {
+                vaddr,
+                pages,
+                reason,
+            } => write!(
+                f,
+                "failed to map {} pages at {:#x}: {}",
+                pages, vaddr, reason
+            ),
+            LoaderError::Verify(e) => write!(f, "{}", e),
+            LoaderError::Manifest(e) => write!(f, "{}", e),
+            LoaderError::Decompress(e) => write!(f, "decompress failed: {}", e),
+            LoaderError::InterfaceMismatch { expected, found } => write!(
+                f,
+                "interface version mismatch, kernel: {}, domain: {}",
+                expected, found
+            ),
+            LoaderError::AbiMismatch { expected, found } => write!(
+                f,
+                "ABI fingerprint mismatch, kernel: {:#x}, domain: {:#x}",
+                expected, found
+            ),
+            LoaderError::TypeMismatch { expected, found } => write!(
+                f,
+                "domain type mismatch, registered: {}, manifest: {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl From<VerifyError> for LoaderError {
+    fn from(e: VerifyError) -> Self {
+        LoaderError::Verify(e)
+    }
+}
+
+impl From<ManifestError> for LoaderError {
+    fn from(e: ManifestError) -> Self {
+        LoaderError::Manifest(e)
+    }
+}
@@ -1,31 +1,78 @@
 #![no_std]
 
+mod aslr;
+mod check;
+mod compress;
+mod error;
+mod reloc;
+mod share;
+mod symbol;
+mod verify;
 mod vm;
 
 extern crate alloc;
 #[macro_use]
 extern crate log;
 use alloc::{
+    borrow::Cow,
     boxed::Box,
+    collections::BTreeSet,
     string::{String, ToString},
     sync::Arc,
     vec,
     vec::Vec,
 };
 use core::{
+    alloc::Layout,
     fmt::{Debug, Formatter},
     ops::Range,
 };
 
+pub use aslr::AslrConfig;
+pub use check::{check_image, check_image_for, ImageInfo};
+pub use compress::{
+    decompress, detect_compression, Compression, ImageResidency, DEFAULT_MAX_IMAGE_SIZE,
+};
+pub use error::LoaderError;
 use log::{debug, trace};
+pub use manifest::{Manifest, ManifestError, MmioRange};
 use memory_addr::VirtAddr;
+pub use reloc::NATIVE_MACHINE;
+use spin::Mutex;
 use storage::StorageArg;
-pub use vm::{DomainArea, DomainVmOps};
-use xmas_elf::{program::Type, sections::SectionData, ElfFile};
+pub use symbol::{symbolize, symbolize_into, DomainSymbol, SymbolTable};
+pub use task_meta::TlsTemplate;
+pub use verify::{
+    image_hash, split_signed, verify_image, SignedImage, VerifyError, VerifyPolicy,
+    SIGNATURE_MAGIC, SIGNATURE_TRAILER_LEN,
+};
+pub use vm::{DomainArea, DomainMappingFlags, DomainVmOps, SharedPages};
+pub use xmas_elf::header::Machine;
+use xmas_elf::{
+    header::Class,
+    program::{ProgramHeader, Type},
+    ElfFile,
+};
 
-use crate::vm::DomainMappingFlags;
+use crate::{
+    error::Result,
+    reloc::{check_machine, relocate_dyn},
+    share::{SharedImage, SharedRun},
+    symbol::{register_symbols, unregister_symbols},
+};
 const FRAME_SIZE: usize = 4096;
-type Result<T> = core::result::Result<T, &'static str>;
+
+/// The tags of the dynamic section the loader reads.
+const DT_NULL: usize = 0;
+const DT_INIT_ARRAY: usize = 25;
+const DT_INIT_ARRAYSZ: usize = 27;
+
+/// A loaded segment of the domain and the permissions it is mapped with.
+#[derive(Debug, Clone)]
+pub struct DomainSegment {
+    pub range: Range<usize>,
+    pub flags: DomainMappingFlags,
+}
 
 pub struct DomainLoader<V: DomainVmOps> {
     entry_point: usize,
@@ -34,6 +81,21 @@ pub struct DomainLoader<V: DomainVmOps> {
     module_area: Option<Box<dyn DomainArea>>,
     ident: String,
     text_section: Range<usize>,
+    segments: Vec<DomainSegment>,
+    symbols: Option<Arc<SymbolTable>>,
+    tls: Option<TlsTemplate>,
+    init_array: Option<Range<usize>>,
+    policy: Arc<VerifyPolicy>,
+    residency: ImageResidency,
+    /// The max size of the decompressed image.
+    max_image_size: usize,
+    aslr: Option<Arc<AslrConfig>>,
+    /// `data` is the verified and decompressed image.
+    verified: bool,
+    /// The read-only pages shared by the loaders cloned from one loader.
+    shared: Arc<Mutex<Option<SharedImage<V>>>>,
+    /// The pages of this instance which are mapped from `shared`.
+    shared_pages: Vec<Range<usize>>,
     _phantom: core::marker::PhantomData<V>,
 }
 
@@ -44,6 +106,7 @@ impl<V: DomainVmOps> Debug for DomainLoader<V> {
             .field("phy_start", &self.virt_start)
             .field("ident", &self.ident)
             .field("text_section", &self.text_section)
+            .field("segments", &self.segments)
             .finish()
     }
 }
@@ -57,6 +120,17 @@ impl<V: DomainVmOps> Clone for DomainLoader<V> {
             ident: self.ident.to_string(),
             module_area: None,
             text_section: self.text_section.clone(),
+            segments: vec![],
+            symbols: None,
+            tls: None,
+            init_array: None,
+            policy: self.policy.clone(),
+            residency: self.residency,
+            max_image_size: self.max_image_size,
+            aslr: self.aslr.clone(),
+            verified: self.verified,
+            shared: self.shared.clone(),
+            shared_pages: vec![],
             _phantom: core::marker::PhantomData,
         }
     }
@@ -64,6 +138,11 @@ impl<V: DomainVmOps> Clone for DomainLoader<V> {
 
 impl<V: DomainVmOps> DomainLoader<V> {
     pub fn new(data: Arc<Vec<u8>>, ident: &str) -> Self {
+        Self::with_policy(data, ident, Arc::new(VerifyPolicy::None))
+    }
+
+    /// Create a loader which refuses images that do not satisfy the policy.
+    pub fn with_policy(data: Arc<Vec<u8>>, ident: &str, policy: Arc<VerifyPolicy>) -> Self {
         Self {
             entry_point: 0,
             data,
@@ -71,15 +150,108 @@ impl<V: DomainVmOps> DomainLoader<V> {
             ident: ident.to_string(),
             module_area: None,
             text_section: 0..0,
+            segments: vec![],
+            symbols: None,
+            tls: None,
+            init_array: None,
+            policy,
+            residency: ImageResidency::Decompressed,
+            max_image_size: DEFAULT_MAX_IMAGE_SIZE,
+            aslr: None,
+            verified: false,
+            shared: Arc::new(Mutex::new(None)),
+            shared_pages: vec![],
             _phantom: core::marker::PhantomData,
         }
     }
 
+    /// Set which form of a compressed image stays in memory after loading.
+    pub fn set_residency(&mut self, residency: ImageResidency) {
+        self.residency = residency;
+    }
+
+    /// Set the max size of the decompressed image, a compressed image which
+    /// grows over it is refused.
+    pub fn set_max_image_size(&mut self, max_image_size: usize) {
+        self.max_image_size = max_image_size;
+    }
+
+    /// Load the domain at a random address in the window of the config.
+    pub fn set_aslr(&mut self, aslr: Option<Arc<AslrConfig>>) {
+        self.aslr = aslr;
+    }
+
     /// Return the domain file info(name, size)
     pub fn domain_file_info(&self) -> (String, usize) {
         (self.ident.clone(), self.data.len())
     }
 
+    /// Return the manifest of the domain image, if it has one.
+    ///
+    /// The image is checked with the verify policy of the loader first.
+    pub fn manifest(&self) -> Result<Option<Manifest>> {
+        if self.verified {
+            return elf_manifest(&parse_elf(&self.data)?);
+        }
+        domain_manifest(&self.data, &self.policy, self.max_image_size).inspect_err(|e| {
+            error!("[{}] {}", self.ident, e);
+        })
+    }
+
+    /// Check that the domain is built against the interface the kernel expects,
+    /// `interface_version` is the `INTERFACE_VERSION` of the kernel.
+    ///
+    /// A domain without a manifest can not be checked and is accepted.
+    pub fn verify_abi(&self, interface_version: u32, expected: u64) -> Result<()> {
+        match self.manifest()? {
+            Some(manifest) if manifest.interface_version != interface_version => {
+                let e = LoaderError::InterfaceMismatch {
+                    expected: interface_version,
+                    found: manifest.interface_version,
+                };
+                error!("[{}] {}", self.ident, e);
+                Err(e)
+            }
+            Some(manifest) if manifest.abi_fingerprint != expected => {
+                let e = LoaderError::AbiMismatch {
+                    expected,
+                    found: manifest.abi_fingerprint,
+                };
+                error!("[{}] {}", self.ident, e);
+                Err(e)
+            }
+            Some(_) => Ok(()),
+            None => {
+                warn!("[{}] no manifest, skip the ABI check", self.ident);
+                Ok(())
+            }
+        }
+    }
+
+    /// Return the loaded segments, the ranges are page aligned.
+    pub fn segments(&self) -> &[DomainSegment] {
+        &self.segments
+    }
+
+    /// Return the function of the domain which contains `addr`.
+    pub fn symbolize(&self, addr: usize) -> Option<DomainSymbol> {
+        self.symbols.as_ref()?.symbolize(addr)
+    }
+
+    /// Return the thread local storage template of the loaded domain, if it
+    /// has a `PT_TLS` segment.
+    pub fn tls_template(&self) -> Option<TlsTemplate> {
+        self.tls
+    }
+
+    /// Return the address range of the constructors of the loaded domain.
+    ///
+    /// The loader does not call them, the domain runs them with
+    /// `basic::run_init_array` once its runtime is initialized.
+    pub fn init_array(&self) -> Option<Range<usize>> {
+        self.init_array.clone()
+    }
+
     pub fn empty() -> Self {
         Self::new(Arc::new(vec![]), "empty_loader")
     }
@@ -112,22 +284,15 @@ impl<V: DomainVmOps> DomainLoader<V> {
     }
 
     fn load_program(&mut self, elf: &ElfFile) -> Result<()> {
+        self.segments.clear();
         elf.program_iter()
             .filter(|ph| ph.get_type() == Ok(Type::Load))
-            .for_each(|ph| {
-                let start_vaddr = ph.virtual_addr() as usize + self.virt_start;
-                let end_vaddr = start_vaddr + ph.mem_size() as usize;
-                let mut permission = DomainMappingFlags::empty();
-                let ph_flags = ph.flags();
-                if ph_flags.is_read() {
-                    permission |= DomainMappingFlags::READ;
-                }
-                if ph_flags.is_write() {
-                    permission |= DomainMappingFlags::WRITE;
-                }
-                if ph_flags.is_execute() {
-                    permission |= DomainMappingFlags::EXECUTE;
-                }
+            .try_for_each(|ph| {
+                let (start_vaddr, end_vaddr) = (ph.virtual_addr() as usize)
+                    .checked_add(self.virt_start)
+                    .and_then(|start| Some((start, start.checked_add(ph.mem_size() as usize)?)))
+                    .ok_or_else(|| segment_overflow(&ph))?;
+                let permission = segment_flags(&ph);
                 let vaddr = VirtAddr::from(start_vaddr).align_down_4k().as_usize();
                 let end_vaddr = VirtAddr::from(end_vaddr).align_up_4k().as_usize();
                 // log::error!(
@@ -137,57 +302,373 @@ impl<V: DomainVmOps> DomainLoader<V> {
                 //     ph.mem_size(),
                 //     permission
                 // );
-                let data =
-                    &elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize];
+                let overflow = segment_overflow(&ph);
+                if ph.file_size() > ph.mem_size() {
+                    return Err(overflow);
+                }
+                let data = ph
+                    .offset()
+                    .checked_add(ph.file_size())
+                    .and_then(|end| elf.input.get(ph.offset() as usize..end as usize))
+                    .ok_or(overflow.clone())?;
                 let data_len = data.len();
                 // direct copy data to kernel space
-                let module_area = self.module_area.as_ref().unwrap();
-                let module_slice = module_area.as_mut_slice();
+                let module_slice = self.module_slice()?;
                 let copy_start = start_vaddr - self.virt_start;
-                module_slice[copy_start..copy_start + data_len].copy_from_slice(data);
+                let dst = copy_start
+                    .checked_add(data_len)
+                    .and_then(|end| module_slice.get_mut(copy_start..end))
+                    .ok_or(overflow)?;
+                self.copy_private(dst, copy_start, data);
                 // log::error!(
                 //     "copy data to {:#x}-{:#x}",
                 //     copy_start,
                 //     copy_start + data_len
                 // );
                 if permission.contains(DomainMappingFlags::EXECUTE) {
+                    if permission.contains(DomainMappingFlags::WRITE) {
+                        let e = LoaderError::WritableExecutable { vaddr };
+                        error!("[{}] {}", self.ident, e);
+                        return Err(e);
+                    }
                     self.text_section = vaddr..end_vaddr;
                 }
-            });
+                self.segments.push(DomainSegment {
+                    range: vaddr..end_vaddr,
+                    flags: permission,
+                });
+                Ok(())
+            })
+    }
+
+    fn page_permissions(&self, pages: usize) -> Result<Vec<DomainMappingFlags>> {
+        page_permissions(&self.segments, self.virt_start, pages).inspect_err(|e| {
+            error!("[{}] {}", self.ident, e);
+        })
+    }
+
+    /// Set the permissions of every segment and make `PT_GNU_RELRO` read only.
+    ///
+    /// It is called after relocation, the domain area is writable before.
+    fn protect(&self, elf: &ElfFile) -> Result<()> {
+        let area_size = self.module_slice()?.len();
+        let mut perms = self.page_permissions(area_size / FRAME_SIZE)?;
+        elf.program_iter()
+            .filter(|ph| ph.get_type() == Ok(Type::GnuRelro))
+            .try_for_each(|ph| {
+                // the end is rounded down, the rest of the page is writable data
+                let start = VirtAddr::from(ph.virtual_addr() as usize).align_up_4k();
+                let end = segment_end(&ph)?;
+                let end = VirtAddr::from(end as usize).align_down_4k();
+                let end = end.as_usize().min(area_size);
+                (start.as_usize() / FRAME_SIZE..end / FRAME_SIZE)
+                    .for_each(|page| perms[page].remove(DomainMappingFlags::WRITE));
+                Ok::<_, LoaderError>(())
+            })?;
+        let mut page = 0;
+        while page < perms.len() {
+            let flags = perms[page];
+            let count = perms[page..].iter().take_while(|f| **f == flags).count();
+            let start = self.virt_start + page * FRAME_SIZE;
+            let mapping_err = |reason| LoaderError::Mapping {
+                vaddr: start,
+                pages: count,
+                reason,
+            };
+            if !flags.contains(DomainMappingFlags::WRITE) {
+                V::set_memory_ro(start, count).map_err(mapping_err)?;
+            }
+            if flags.contains(DomainMappingFlags::EXECUTE) {
+                V::set_memory_x(start, count).map_err(mapping_err)?;
+            } else {
+                V::set_memory_nx(start, count).map_err(mapping_err)?;
+            }
+            page += count;
+        }
+        Ok(())
+    }
+    fn module_slice(&self) -> Result<&mut [u8]> {
+        let module_area = self.module_area.as_ref().ok_or(LoaderError::Mapping {
+            vaddr: 0,
+            pages: 0,
+            reason: "domain area is not mapped",
+        })?;
+        Ok(module_area.as_mut_slice())
+    }
+
+    fn is_shared_page(&self, page: usize) -> bool {
+        self.shared_pages.iter().any(|run| run.contains(&page))
+    }
+
+    /// Copy `data` to `dst` at `offset` of the domain area, except the pages
+    /// mapped from the shared image which have the data already.
+    fn copy_private(&self, dst: &mut [u8], offset: usize, data: &[u8]) {
+        if self.shared_pages.is_empty() {
+            dst.copy_from_slice(data);
+            return;
+        }
+        let mut pos = 0;
+        while pos < data.len() {
+            let page = (offset + pos) / FRAME_SIZE;
+            let len = ((page + 1) * FRAME_SIZE - (offset + pos)).min(data.len() - pos);
+            if !self.is_shared_page(page) {
+                dst[pos..pos + len].copy_from_slice(&data[pos..pos + len]);
+            }
+            pos += len;
+        }
+    }
+
+    /// Map the pages of the shared image, if another instance has loaded it.
+    fn map_shared(&mut self) -> Result<()> {
+        self.shared_pages.clear();
+        let shared = self.shared.clone();
+        let shared = shared.lock();
+        let image = match shared.as_ref() {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        let module_area = self.module_area.as_ref().ok_or(LoaderError::Mapping {
+            vaddr: 0,
+            pages: 0,
+            reason: "domain area is not mapped",
+        })?;
+        for run in image.runs.iter() {
+            let offset = run.pages.start * FRAME_SIZE;
+            V::map_shared_pages(module_area.as_ref(), offset, run.frames.as_ref()).map_err(
+                |reason| LoaderError::Mapping {
+                    vaddr: self.virt_start + offset,
+                    pages: run.pages.len(),
+                    reason,
+                },
+            )?;
+            self.shared_pages.push(run.pages.clone());
+        }
+        debug!("[{}] map {} shared pages", self.ident, image.pages());
         Ok(())
     }
-    fn relocate_dyn(&self, elf: &ElfFile) -> Result<()> {
-        if let Ok(res) = relocate_dyn(elf, self.virt_start) {
-            trace!("Relocate_dyn {} entries", res.len());
-            res.into_iter().for_each(|kv| {
-                trace!("relocate: {:#x} -> {:#x}", kv.0, kv.1);
-                let addr = kv.0;
-                unsafe { (addr as *mut usize).write(kv.1) }
+
+    /// Share the read-only pages without relocations with the later instances.
+    fn share_image(&self, relocated: &BTreeSet<usize>) {
+        let mut shared = self.shared.lock();
+        if shared.is_some() {
+            return;
+        }
+        let module_area = match self.module_area.as_ref() {
+            Some(module_area) => module_area,
+            None => return,
+        };
+        let shareable = |page: usize| {
+            let addr = self.virt_start + page * FRAME_SIZE;
+            let mut segments = self.segments.iter().filter(|seg| seg.range.contains(&addr));
+            let mut covered = false;
+            let read_only = segments.all(|seg| {
+                covered = true;
+                !seg.flags.contains(DomainMappingFlags::WRITE)
+            });
+            covered && read_only && !relocated.contains(&page)
+        };
+        let pages = module_area.as_slice().len() / FRAME_SIZE;
+        let mut runs = vec![];
+        let mut page = 0;
+        while page < pages {
+            if !shareable(page) {
+                page += 1;
+                continue;
+            }
+            let start = page;
+            while page < pages && shareable(page) {
+                page += 1;
+            }
+            match V::share_pages(module_area.as_ref(), start * FRAME_SIZE, page - start) {
+                Ok(frames) => runs.push(SharedRun {
+                    pages: start..page,
+                    frames,
+                }),
+                Err(e) => {
+                    debug!("[{}] do not share pages: {}", self.ident, e);
+                    break;
+                }
+            }
+        }
+        let image = SharedImage::new(runs);
+        debug!("[{}] share {} pages", self.ident, image.pages());
+        *shared = Some(image);
+    }
+
+    /// Write the relocations and return the pages they touch.
+    fn relocate_dyn(&self, elf: &ElfFile) -> Result<BTreeSet<usize>> {
+        let res = relocate_dyn(elf, self.virt_start).inspect_err(|e| {
+            error!("[{}] relocate failed: {}", self.ident, e);
+        })?;
+        trace!("Relocate_dyn {} entries", res.len());
+        let module_slice = self.module_slice()?;
+        const SLOT: usize = core::mem::size_of::<usize>();
+        let mut pages = BTreeSet::new();
+        res.into_iter().try_for_each(|(offset, value)| {
+            trace!("relocate: {:#x} -> {:#x}", offset + self.virt_start, value);
+            let slot = offset
+                .checked_add(SLOT)
+                .and_then(|end| module_slice.get_mut(offset..end))
+                .ok_or(LoaderError::RelocationOverflow {
+                    offset: offset as u64,
+                })?;
+            slot.copy_from_slice(&value.to_ne_bytes());
+            pages.insert(offset / FRAME_SIZE);
+            pages.insert((offset + SLOT - 1) / FRAME_SIZE);
+            Ok::<_, LoaderError>(())
+        })?;
+        trace!("Relocate_dyn done");
+        Ok(pages)
+    }
+
+    fn init_tls(&mut self, elf: &ElfFile) -> Result<()> {
+        self.tls = None;
+        let ph = match elf.program_iter().find(|ph| ph.get_type() == Ok(Type::Tls)) {
+            Some(ph) => ph,
+            None => return Ok(()),
+        };
+        let area_size = self.module_slice()?.len();
+        let end = ph.virtual_addr().checked_add(ph.mem_size());
+        if ph.file_size() > ph.mem_size() || end.map_or(true, |end| end as usize > area_size) {
+            return Err(LoaderError::SegmentOverflow {
+                offset: ph.offset(),
+                vaddr: ph.virtual_addr(),
+                size: ph.mem_size(),
             });
-            trace!("Relocate_dyn done");
         }
+        // the domain allocates the block of every task with this layout
+        let align = (ph.align() as usize).max(1);
+        if Layout::from_size_align((ph.mem_size() as usize).max(1), align).is_err() {
+            return Err(LoaderError::BadTlsAlign {
+                align: ph.align(),
+                size: ph.mem_size(),
+            });
+        }
+        let tls = TlsTemplate {
+            image: self.virt_start + ph.virtual_addr() as usize,
+            file_size: ph.file_size() as usize,
+            mem_size: ph.mem_size() as usize,
+            align,
+        };
+        debug!("[{}] tls template: {:x?}", self.ident, tls);
+        self.tls = Some(tls);
+        Ok(())
+    }
+
+    /// Find the constructors of the domain with `DT_INIT_ARRAY` and
+    /// `DT_INIT_ARRAYSZ`, the section headers may be stripped.
+    fn find_init_array(&mut self, elf: &ElfFile) -> Result<()> {
+        self.init_array = None;
+        let ph = match elf
+            .program_iter()
+            .find(|ph| ph.get_type() == Ok(Type::Dynamic))
+        {
+            Some(ph) => ph,
+            None => return Ok(()),
+        };
+        const SLOT: usize = core::mem::size_of::<usize>();
+        let start = ph.virtual_addr() as usize;
+        let dynamic = start
+            .checked_add(ph.mem_size() as usize)
+            .and_then(|end| self.module_slice().ok()?.get(start..end))
+            .ok_or_else(|| segment_overflow(&ph))?;
+        let (mut array, mut array_size) = (None, None);
+        for entry in dynamic.chunks_exact(2 * SLOT) {
+            let tag = usize::from_ne_bytes(entry[..SLOT].try_into().unwrap());
+            let value = usize::from_ne_bytes(entry[SLOT..].try_into().unwrap());
+            match tag {
+                DT_NULL => break,
+                DT_INIT_ARRAY => array = Some(value),
+                DT_INIT_ARRAYSZ => array_size = Some(value),
+                _ => {}
+            }
+        }
+        let (start, size) = match (array, array_size) {
+            (Some(start), Some(size)) if size != 0 => (start, size),
+            _ => return Ok(()),
+        };
+        let area_size = self.module_slice()?.len();
+        let end = start.checked_add(size);
+        if start % SLOT != 0 || size % SLOT != 0 || end.map_or(true, |end| end > area_size) {
+            return Err(LoaderError::CorruptSection(".init_array"));
+        }
+        let start = self.virt_start + start;
+        debug!("[{}] {} init functions", self.ident, size / SLOT);
+        self.init_array = Some(start..start + size);
         Ok(())
     }
 
     pub fn load(&mut self) -> Result<()> {
         let data = self.data.clone();
-        let elf_binary = data.as_slice();
-        const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
-        if elf_binary[0..4] != ELF_MAGIC {
-            return Err("not a elf file");
+        let payload = if self.verified {
+            data.as_slice()
+        } else {
+            verify_image(data.as_slice(), &self.policy).inspect_err(|e| {
+                error!("[{}] refuse domain image: {}", self.ident, e);
+            })?
+        };
+        let image = decompress(payload, self.max_image_size).inspect_err(|e| {
+            error!("[{}] {}", self.ident, e);
+        })?;
+        self.load_image(&image)?;
+        if let Cow::Owned(image) = image {
+            if self.residency == ImageResidency::Decompressed {
+                debug!(
+                    "[{}] keep the decompressed image, {} -> {} bytes",
+                    self.ident,
+                    payload.len(),
+                    image.len()
+                );
+                self.data = Arc::new(image);
+                self.verified = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Map the domain area, at a random address if ASLR is enabled.
+    fn map_area(&self, size: usize) -> Box<dyn DomainArea> {
+        if let Some(aslr) = self.aslr.as_ref() {
+            for _ in 0..aslr.retries {
+                let base = match aslr.random_base(size) {
+                    Some(base) => base,
+                    None => break,
+                };
+                match V::map_domain_area_at(base, size) {
+                    Ok(area) => return area,
+                    Err(e) => debug!("[{}] can not map at {:#x}: {}", self.ident, base, e),
+                }
+            }
+            warn!("[{}] no random address is available", self.ident);
         }
+        V::map_domain_area(size)
+    }
+
+    fn load_image(&mut self, elf_binary: &[u8]) -> Result<()> {
         debug!("Domain address:{:p}", elf_binary.as_ptr());
-        let elf = ElfFile::new(elf_binary)?;
+        let elf = parse_elf(elf_binary)?;
         debug!("Domain type:{:?}", elf.header.pt2.type_().as_type());
-        let end_paddr = elf
+        check_machine(&elf, NATIVE_MACHINE).inspect_err(|e| {
+            error!("[{}] {}", self.ident, e);
+        })?;
+        let mut end_paddr = None;
+        for ph in elf
             .program_iter()
             .filter(|ph| ph.get_type() == Ok(Type::Load))
-            .last()
-            .map(|x| x.virtual_addr() as usize + x.mem_size() as usize)
-            .unwrap();
+        {
+            end_paddr = end_paddr.max(Some(segment_end(&ph)?));
+        }
+        let end_paddr = end_paddr.ok_or(LoaderError::NoLoadSegment)? as usize;
+        if end_paddr > usize::MAX - FRAME_SIZE {
+            return Err(LoaderError::SegmentOverflow {
+                offset: 0,
+                vaddr: end_paddr as u64,
+                size: 0,
+            });
+        }
         let end_paddr = VirtAddr::from(end_paddr).align_up(FRAME_SIZE);
         // alloc free page to map elf
-        let module_area = V::map_domain_area(end_paddr.as_usize());
+        let module_area = self.map_area(end_paddr.as_usize());
         let region_start = module_area.start_virtual_address().as_usize();
         // log::error!(
         //     "region range:{:#x}-{:#x}",
@@ -196,19 +677,33 @@ impl<V: DomainVmOps> DomainLoader<V> {
         // );
         self.virt_start = region_start;
         self.module_area = Some(module_area);
+        self.map_shared()?;
         self.load_program(&elf)?;
-        self.relocate_dyn(&elf)?;
-        // update text section permission
-        let text_pages = (self.text_section.end - self.text_section.start) / FRAME_SIZE;
-        V::set_memory_x(self.text_section.start, text_pages)?;
-        // log::error!(
-        //     "set_memory_x range: {:#x}-{:#x}",
-        //     self.text_section.start,
-        //     self.text_section.end
-        // );
-        let entry = elf.header.pt2.entry_point() as usize + region_start;
+        let relocated = self.relocate_dyn(&elf)?;
+        self.init_tls(&elf)?;
+        self.find_init_array(&elf)?;
+        // update the permission of all segments
+        self.protect(&elf)?;
+        self.share_image(&relocated);
+        let area_end = region_start + end_paddr.as_usize();
+        let symbols = Arc::new(SymbolTable::new(&self.ident, &elf, region_start..area_end));
+        debug!("[{}] {} symbols", self.ident, symbols.len());
+        register_symbols(symbols.clone());
+        self.symbols = Some(symbols);
+        let entry = elf.header.pt2.entry_point();
+        let entry_addr = (entry as usize).checked_add(region_start);
+        let executable = entry_addr.is_some_and(|addr| {
+            self.segments.iter().any(|seg| {
+                seg.flags.contains(DomainMappingFlags::EXECUTE) && seg.range.contains(&addr)
+            })
+        });
+        if !executable {
+            let e = LoaderError::BadEntry { entry };
+            error!("[{}] {}", self.ident, e);
+            return Err(e);
+        }
         // log::error!("entry: {:#x}", entry);
-        self.entry_point = entry;
+        self.entry_point = entry_addr.unwrap();
         Ok(())
     }
 }
@@ -217,41 +712,127 @@ impl<V: DomainVmOps> Drop for DomainLoader<V> {
     fn drop(&mut self) {
         info!("drop domain loader [{}]", self.ident);
         if let Some(module_area) = self.module_area.take() {
+            if self.symbols.take().is_some() {
+                unregister_symbols(self.virt_start);
+            }
             V::unmap_domain_area(module_area)
         }
     }
 }
 
-fn relocate_dyn(elf: &ElfFile, region_start: usize) -> Result<Vec<(usize, usize)>> {
-    let data = elf
-        .find_section_by_name(".rela.dyn")
-        .map(|h| h.get_data(elf).unwrap())
-        .ok_or("corrupted .rela.dyn")?;
-    let entries = match data {
-        SectionData::Rela64(entries) => entries,
-        _ => return Err("bad .rela.dyn"),
+/// Parse the manifest of a domain image without loading it.
+///
+/// The image is checked with the policy before any of it is parsed, a
+/// compressed image is decompressed after the check, up to `max_size` bytes.
+pub fn domain_manifest(
+    data: &[u8],
+    policy: &VerifyPolicy,
+    max_size: usize,
+) -> Result<Option<Manifest>> {
+    let payload = verify_image(data, policy)?;
+    let elf_binary = decompress(payload, max_size)?;
+    elf_manifest(&parse_elf(&elf_binary)?)
+}
+
+/// Check a domain image when it is registered with the type `ty`.
+///
+/// The image is verified with the policy, and the type in its manifest must
+/// be `ty`. An image without a manifest can not be checked and is accepted.
+pub fn verify_registration(
+    data: &[u8],
+    ty: u8,
+    policy: &VerifyPolicy,
+    max_size: usize,
+) -> Result<Option<Manifest>> {
+    let manifest = domain_manifest(data, policy, max_size)?;
+    match manifest.as_ref() {
+        Some(manifest) if manifest.ty != ty => Err(LoaderError::TypeMismatch {
+            expected: ty,
+            found: manifest.ty,
+        }),
+        _ => Ok(manifest),
+    }
+}
+
+/// Parse the manifest section of an ELF file.
+fn elf_manifest(elf: &ElfFile) -> Result<Option<Manifest>> {
+    let section = match elf.find_section_by_name(manifest::MANIFEST_SECTION) {
+        Some(section) => section,
+        None => return Ok(None),
     };
-    let mut res = vec![];
-    for entry in entries.iter() {
-        match entry.get_type() {
-            RELATIVE => {
-                let value = region_start + entry.get_addend() as usize;
-                let addr = region_start + entry.get_offset() as usize;
-                res.push((addr, value))
-            }
-            t => unimplemented!("unknown type: {}", t),
-        }
+    let manifest = Manifest::parse(section.raw_data(elf)).inspect_err(|e| {
+        error!("bad domain manifest: {}", e);
+    })?;
+    Ok(Some(manifest))
+}
+
+fn segment_overflow(ph: &ProgramHeader) -> LoaderError {
+    LoaderError::SegmentOverflow {
+        offset: ph.offset(),
+        vaddr: ph.virtual_addr(),
+        size: ph.mem_size(),
     }
-    Ok(res)
 }
 
-#[cfg(target_arch = "riscv64")]
-const R_RISCV_RELATIVE: u32 = 3;
-#[cfg(target_arch = "x86_64")]
-const R_X86_64_RELATIVE: u32 = 8;
+/// Return the end of the segment in memory.
+fn segment_end(ph: &ProgramHeader) -> Result<u64> {
+    ph.virtual_addr()
+        .checked_add(ph.mem_size())
+        .ok_or_else(|| segment_overflow(ph))
+}
+
+/// Return the permission of a `PT_LOAD` segment.
+fn segment_flags(ph: &ProgramHeader) -> DomainMappingFlags {
+    let mut permission = DomainMappingFlags::empty();
+    let ph_flags = ph.flags();
+    if ph_flags.is_read() {
+        permission |= DomainMappingFlags::READ;
+    }
+    if ph_flags.is_write() {
+        permission |= DomainMappingFlags::WRITE;
+    }
+    if ph_flags.is_execute() {
+        permission |= DomainMappingFlags::EXECUTE;
+    }
+    permission
+}
 
-#[cfg(target_arch = "riscv64")]
-const RELATIVE: u32 = R_RISCV_RELATIVE;
+/// Return the permission of every page of the domain area at `base`.
+///
+/// A page shared by two segments gets the permissions of both, the pages
+/// not covered by a segment are read only.
+fn page_permissions(
+    segments: &[DomainSegment],
+    base: usize,
+    pages: usize,
+) -> Result<Vec<DomainMappingFlags>> {
+    let mut perms = vec![DomainMappingFlags::READ; pages];
+    for (i, perm) in perms.iter_mut().enumerate() {
+        let page = base + i * FRAME_SIZE;
+        let mut flags = DomainMappingFlags::empty();
+        segments
+            .iter()
+            .filter(|seg| seg.range.contains(&page))
+            .for_each(|seg| flags |= seg.flags);
+        if flags.contains(DomainMappingFlags::WRITE | DomainMappingFlags::EXECUTE) {
+            return Err(LoaderError::WritableExecutable { vaddr: page });
+        }
+        if !flags.is_empty() {
+            *perm = flags;
+        }
+    }
+    Ok(perms)
+}
 
-#[cfg(target_arch = "x86_64")]
-const RELATIVE: u32 = R_X86_64_RELATIVE;
+/// Parse the ELF file of a domain and check that the loader supports it.
+fn parse_elf(elf_binary: &[u8]) -> Result<ElfFile> {
+    const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+    if !elf_binary.starts_with(&ELF_MAGIC) {
+        return Err(LoaderError::BadMagic);
+    }
+    let elf = ElfFile::new(elf_binary).map_err(LoaderError::BadElf)?;
+    if elf.header.pt1.class() != Class::SixtyFour {
+        return Err(LoaderError::UnsupportedClass);
+    }
+    Ok(elf)
+}
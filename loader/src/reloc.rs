@@ -0,0 +1,194 @@
+use alloc::{string::ToString, vec, vec::Vec};
+
+use xmas_elf::{
+    header::Machine,
+    sections::{Rela, SectionData},
+    symbol_table::{Binding, DynEntry64, Entry},
+    ElfFile, P64,
+};
+
+use crate::error::{LoaderError, Result};
+
+/// The relocation types the loader supports for one architecture.
+struct RelocTypes {
+    none: u32,
+    abs64: u32,
+    relative: u32,
+    glob_dat: Option<u32>,
+    jump_slot: u32,
+    tls_dtpmod: u32,
+    tls_dtprel: u32,
+    /// The bias of the offsets in the thread local block, `__tls_get_addr`
+    /// adds it back.
+    dtv_offset: usize,
+}
+
+/// The machine of the kernel the loader runs in, it only loads domains built
+/// for it.
+#[cfg(target_arch = "riscv64")]
+pub const NATIVE_MACHINE: Machine = Machine::RISC_V;
+#[cfg(target_arch = "x86_64")]
+pub const NATIVE_MACHINE: Machine = Machine::X86_64;
+/// The loader has no relocations for other machines.
+#[cfg(not(any(target_arch = "riscv64", target_arch = "x86_64")))]
+pub const NATIVE_MACHINE: Machine = Machine::None;
+
+/// The module id of the thread local storage of every domain, a domain has
+/// only one module.
+pub const TLS_MODULE_ID: usize = 1;
+
+const R_RISCV: RelocTypes = RelocTypes {
+    none: 0,
+    abs64: 2,
+    relative: 3,
+    glob_dat: None,
+    jump_slot: 5,
+    tls_dtpmod: 7,
+    tls_dtprel: 9,
+    dtv_offset: 0x800,
+};
+
+const R_X86_64: RelocTypes = RelocTypes {
+    none: 0,
+    abs64: 1,
+    relative: 8,
+    glob_dat: Some(6),
+    jump_slot: 7,
+    tls_dtpmod: 16,
+    tls_dtprel: 17,
+    dtv_offset: 0,
+};
+
+fn reloc_types(machine: Machine) -> Result<RelocTypes> {
+    match machine {
+        Machine::RISC_V => Ok(R_RISCV),
+        Machine::X86_64 => Ok(R_X86_64),
+        m => Err(LoaderError::UnsupportedMachine(m)),
+    }
+}
+
+/// Check that the ELF file is built for `machine`.
+pub fn check_machine(elf: &ElfFile, machine: Machine) -> Result<()> {
+    let elf_machine = elf.header.pt2.machine().as_machine();
+    reloc_types(elf_machine)?;
+    if elf_machine != machine {
+        return Err(LoaderError::WrongMachine {
+            expected: machine,
+            found: elf_machine,
+        });
+    }
+    Ok(())
+}
+
+fn rela_entries<'a>(elf: &ElfFile<'a>, name: &'static str) -> Result<&'a [Rela<P64>]> {
+    let section = match elf.find_section_by_name(name) {
+        Some(section) => section,
+        // a domain linked without this kind of relocation has no section
+        None => return Ok(&[]),
+    };
+    match section.get_data(elf) {
+        Ok(SectionData::Rela64(entries)) => Ok(entries),
+        _ => Err(LoaderError::CorruptSection(name)),
+    }
+}
+
+fn dynsym<'a>(elf: &ElfFile<'a>) -> Result<&'a [DynEntry64]> {
+    let section = match elf.find_section_by_name(".dynsym") {
+        Some(section) => section,
+        None => return Ok(&[]),
+    };
+    match section.get_data(elf) {
+        Ok(SectionData::DynSymbolTable64(entries)) => Ok(entries),
+        _ => Err(LoaderError::CorruptSection(".dynsym")),
+    }
+}
+
+fn symbol<'a>(symbols: &'a [DynEntry64], entry: &Rela<P64>) -> Result<&'a DynEntry64> {
+    let index = entry.get_symbol_table_index();
+    let offset = entry.get_offset();
+    symbols
+        .get(index as usize)
+        .ok_or(LoaderError::BadSymbolIndex { index, offset })
+}
+
+/// Return the offset of a thread local variable in the thread local block.
+fn tls_offset(elf: &ElfFile, symbols: &[DynEntry64], entry: &Rela<P64>) -> Result<usize> {
+    // local-dynamic relocations have no symbol
+    if entry.get_symbol_table_index() == 0 {
+        return Ok(0);
+    }
+    let symbol = symbol(symbols, entry)?;
+    if symbol.shndx() == 0 {
+        let name = symbol.get_name(elf).unwrap_or("<unknown>");
+        return Err(LoaderError::UndefinedSymbol {
+            name: name.to_string(),
+            offset: entry.get_offset(),
+        });
+    }
+    Ok(symbol.value() as usize)
+}
+
+/// Return the address of the symbol after the domain is loaded at `region_start`.
+fn symbol_value(
+    elf: &ElfFile,
+    symbols: &[DynEntry64],
+    entry: &Rela<P64>,
+    region_start: usize,
+) -> Result<usize> {
+    // a relocation without a symbol only has the addend
+    if entry.get_symbol_table_index() == 0 {
+        return Ok(0);
+    }
+    let offset = entry.get_offset();
+    let symbol = symbol(symbols, entry)?;
+    if symbol.shndx() != 0 {
+        return Ok(region_start + symbol.value() as usize);
+    }
+    // the domain is linked without shared libraries, only weak symbols may be undefined
+    if symbol.get_binding() == Ok(Binding::Weak) {
+        return Ok(0);
+    }
+    let name = symbol.get_name(elf).unwrap_or("<unknown>");
+    Err(LoaderError::UndefinedSymbol {
+        name: name.to_string(),
+        offset,
+    })
+}
+
+/// Compute the relocations of `.rela.dyn` and `.rela.plt`.
+///
+/// Return the offset in the domain area to write and the value for each
+/// relocation.
+pub fn relocate_dyn(elf: &ElfFile, region_start: usize) -> Result<Vec<(usize, usize)>> {
+    let types = reloc_types(elf.header.pt2.machine().as_machine())?;
+    let symbols = dynsym(elf)?;
+    let mut res = vec![];
+    for name in [".rela.dyn", ".rela.plt"] {
+        for entry in rela_entries(elf, name)? {
+            let ty = entry.get_type();
+            let addend = entry.get_addend() as usize;
+            let value = if ty == types.none {
+                continue;
+            } else if ty == types.relative {
+                region_start.wrapping_add(addend)
+            } else if ty == types.abs64 {
+                symbol_value(elf, symbols, entry, region_start)?.wrapping_add(addend)
+            } else if ty == types.jump_slot || Some(ty) == types.glob_dat {
+                symbol_value(elf, symbols, entry, region_start)?
+            } else if ty == types.tls_dtpmod {
+                TLS_MODULE_ID
+            } else if ty == types.tls_dtprel {
+                tls_offset(elf, symbols, entry)?
+                    .wrapping_add(addend)
+                    .wrapping_sub(types.dtv_offset)
+            } else {
+                return Err(LoaderError::UnsupportedRelocation {
+                    ty,
+                    offset: entry.get_offset(),
+                });
+            };
+            res.push((entry.get_offset() as usize, value));
+        }
+    }
+    Ok(res)
+}
@@ -0,0 +1,43 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::{marker::PhantomData, ops::Range};
+
+use crate::{DomainVmOps, SharedPages};
+
+/// A run of read-only pages of a loaded image.
+pub(crate) struct SharedRun {
+    /// The pages in the domain area.
+    pub pages: Range<usize>,
+    pub frames: Box<dyn SharedPages>,
+}
+
+/// The read-only pages of an image which the instances of the image share.
+///
+/// Every instance maps the image with the same layout and the code reaches
+/// its data pc-relative, so only the writable and relocated pages differ
+/// between instances.
+pub(crate) struct SharedImage<V: DomainVmOps> {
+    pub runs: Vec<SharedRun>,
+    _phantom: PhantomData<fn() -> V>,
+}
+
+impl<V: DomainVmOps> SharedImage<V> {
+    pub fn new(runs: Vec<SharedRun>) -> Self {
+        Self {
+            runs,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Return the number of shared pages.
+    pub fn pages(&self) -> usize {
+        self.runs.iter().map(|run| run.pages.len()).sum()
+    }
+}
+
+impl<V: DomainVmOps> Drop for SharedImage<V> {
+    fn drop(&mut self) {
+        self.runs
+            .drain(..)
+            .for_each(|run| V::unshare_pages(run.frames));
+    }
+}
@@ -0,0 +1,163 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    fmt::{Display, Formatter, Write},
+    ops::Range,
+};
+
+use spin::RwLock;
+use xmas_elf::{
+    sections::SectionData,
+    symbol_table::{Entry, Type},
+    ElfFile,
+};
+
+/// The symbol tables of the loaded domains, keyed by the start of the domain area.
+static DOMAIN_SYMBOLS: RwLock<BTreeMap<usize, Arc<SymbolTable>>> = RwLock::new(BTreeMap::new());
+
+struct SymbolEntry {
+    start: usize,
+    size: u32,
+    name: Range<u32>,
+}
+
+/// The functions of a loaded domain.
+///
+/// The names are demangled and stored in one string, the addresses are the
+/// addresses after relocation.
+pub struct SymbolTable {
+    domain: String,
+    area: Range<usize>,
+    entries: Vec<SymbolEntry>,
+    names: String,
+}
+
+/// An address resolved to a function of a domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainSymbol {
+    pub domain: String,
+    pub symbol: String,
+    /// The offset of the address from the start of the function.
+    pub offset: usize,
+}
+
+impl Display for DomainSymbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "[{}] {}+{:#x}", self.domain, self.symbol, self.offset)
+    }
+}
+
+impl SymbolTable {
+    /// Collect the functions from `.symtab`, or from `.dynsym` if the domain
+    /// is stripped.
+    pub(crate) fn new(domain: &str, elf: &ElfFile, area: Range<usize>) -> Self {
+        let mut table = Self {
+            domain: domain.to_string(),
+            area: area.clone(),
+            entries: Vec::new(),
+            names: String::new(),
+        };
+        let mut add = |name: Result<&str, &str>, value: u64, size: u64| {
+            let Ok(name) = name else {
+                return;
+            };
+            let start = table.names.len() as u32;
+            let _ = write!(table.names, "{:#}", rustc_demangle::demangle(name));
+            table.entries.push(SymbolEntry {
+                start: area.start + value as usize,
+                size: size as u32,
+                name: start..table.names.len() as u32,
+            });
+        };
+        let is_func = |ty, shndx, size| ty == Ok(Type::Func) && shndx != 0 && size != 0;
+        let symtab = elf
+            .section_iter()
+            .find_map(|section| match section.get_data(elf) {
+                Ok(SectionData::SymbolTable64(symbols)) => Some(symbols),
+                _ => None,
+            });
+        if let Some(symbols) = symtab {
+            symbols
+                .iter()
+                .filter(|s| is_func(s.get_type(), s.shndx(), s.size()))
+                .for_each(|s| add(s.get_name(elf), s.value(), s.size()));
+        } else if let Some(section) = elf.find_section_by_name(".dynsym") {
+            if let Ok(SectionData::DynSymbolTable64(symbols)) = section.get_data(elf) {
+                symbols
+                    .iter()
+                    .filter(|s| is_func(s.get_type(), s.shndx(), s.size()))
+                    .for_each(|s| add(s.get_name(elf), s.value(), s.size()));
+            }
+        }
+        table.entries.sort_unstable_by_key(|e| e.start);
+        table.entries.dedup_by_key(|e| e.start);
+        table
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Return the number of functions in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the name of the function which contains `addr` and the offset
+    /// of `addr` in it.
+    pub fn lookup(&self, addr: usize) -> Option<(&str, usize)> {
+        let index = self.entries.partition_point(|e| e.start <= addr);
+        let entry = self.entries.get(index.checked_sub(1)?)?;
+        let offset = addr - entry.start;
+        if offset >= entry.size as usize {
+            return None;
+        }
+        let name = &self.names[entry.name.start as usize..entry.name.end as usize];
+        Some((name, offset))
+    }
+
+    pub(crate) fn symbolize(&self, addr: usize) -> Option<DomainSymbol> {
+        if !self.area.contains(&addr) {
+            return None;
+        }
+        self.lookup(addr).map(|(symbol, offset)| DomainSymbol {
+            domain: self.domain.clone(),
+            symbol: symbol.to_string(),
+            offset,
+        })
+    }
+}
+
+pub(crate) fn register_symbols(table: Arc<SymbolTable>) {
+    DOMAIN_SYMBOLS.write().insert(table.area.start, table);
+}
+
+pub(crate) fn unregister_symbols(area_start: usize) {
+    DOMAIN_SYMBOLS.write().remove(&area_start);
+}
+
+/// Map an address in any loaded domain to the domain, function and offset.
+///
+/// It is used by the kernel to print the backtrace of a domain.
+pub fn symbolize(addr: usize) -> Option<DomainSymbol> {
+    let symbols = DOMAIN_SYMBOLS.read();
+    let (_, table) = symbols.range(..=addr).next_back()?;
+    table.symbolize(addr)
+}
+
+/// Write [`symbolize`] of `addr` to `buf` and return the length, the name is
+/// truncated if `buf` is too small.
+pub fn symbolize_into(addr: usize, buf: &mut [u8]) -> Option<usize> {
+    let name = symbolize(addr)?.to_string();
+    let len = name.len().min(buf.len());
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+    Some(len)
+}
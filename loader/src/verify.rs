@@ -0,0 +1,115 @@
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use sha2::{Digest, Sha256};
+
+/// The magic at the end of a signed domain image.
+pub const SIGNATURE_MAGIC: &[u8; 8] = b"DOMSIG01";
+/// The length of the trailer which is appended to the payload.
+///
+/// The trailer is `signature: [u8; 64] || payload length: u64 (LE) || magic`,
+/// the signature is an Ed25519 signature over the payload. The payload is the
+/// image as it is stored, the ELF file or its compressed form.
+pub const SIGNATURE_TRAILER_LEN: usize = 64 + 8 + SIGNATURE_MAGIC.len();
+
+/// How the loader checks a domain image before it is mapped.
+#[derive(Debug, Clone)]
+pub enum VerifyPolicy {
+    /// Load every image, a signature trailer is stripped but not checked.
+    None,
+    /// The image must be signed by one of the trusted Ed25519 public keys.
+    Ed25519(Vec<[u8; 32]>),
+    /// The SHA-256 hash of the payload must be in the allowlist.
+    HashAllowlist(Vec<[u8; 32]>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The image has no signature trailer.
+    Unsigned,
+    /// The signature trailer is malformed.
+    Malformed,
+    /// The signature does not match any trusted key.
+    BadSignature,
+    /// The hash of the image is not in the allowlist.
+    UntrustedHash,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl VerifyError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerifyError::Unsigned => "domain image is not signed",
+            VerifyError::Malformed => "malformed domain signature",
+            VerifyError::BadSignature => "bad domain signature",
+            VerifyError::UntrustedHash => "domain hash is not allowed",
+        }
+    }
+}
+
+/// A signed image split at its signature trailer.
+#[derive(Debug, Clone, Copy)]
+pub struct SignedImage<'a> {
+    /// The signed data, it may be compressed.
+    pub payload: &'a [u8],
+    pub signature: &'a [u8; 64],
+}
+
+/// Split a signed image into the payload and the signature.
+///
+/// Return `None` if the image has no signature trailer.
+pub fn split_signed(data: &[u8]) -> Option<Result<SignedImage<'_>, VerifyError>> {
+    if data.len() < SIGNATURE_TRAILER_LEN || !data.ends_with(SIGNATURE_MAGIC) {
+        return None;
+    }
+    let trailer = &data[data.len() - SIGNATURE_TRAILER_LEN..];
+    let len = u64::from_le_bytes(trailer[64..72].try_into().unwrap()) as usize;
+    if len != data.len() - SIGNATURE_TRAILER_LEN {
+        return Some(Err(VerifyError::Malformed));
+    }
+    let signature = trailer[..64].try_into().unwrap();
+    Some(Ok(SignedImage {
+        payload: &data[..len],
+        signature,
+    }))
+}
+
+/// Return the SHA-256 hash of the payload of an image.
+pub fn image_hash(payload: &[u8]) -> [u8; 32] {
+    Sha256::digest(payload).into()
+}
+
+/// Check the image with the policy and return the payload in it.
+///
+/// The payload is not parsed, it may still be compressed.
+pub fn verify_image<'a>(data: &'a [u8], policy: &VerifyPolicy) -> Result<&'a [u8], VerifyError> {
+    let signed = split_signed(data).transpose()?;
+    match policy {
+        VerifyPolicy::None => Ok(signed.map_or(data, |signed| signed.payload)),
+        VerifyPolicy::Ed25519(keys) => {
+            let signed = signed.ok_or(VerifyError::Unsigned)?;
+            let signature = ed25519_compact::Signature::new(*signed.signature);
+            let trusted = keys.iter().any(|key| {
+                ed25519_compact::PublicKey::new(*key)
+                    .verify(signed.payload, &signature)
+                    .is_ok()
+            });
+            if !trusted {
+                return Err(VerifyError::BadSignature);
+            }
+            Ok(signed.payload)
+        }
+        VerifyPolicy::HashAllowlist(hashes) => {
+            let payload = signed.map_or(data, |signed| signed.payload);
+            if !hashes.contains(&image_hash(payload)) {
+                return Err(VerifyError::UntrustedHash);
+            }
+            Ok(payload)
+        }
+    }
+}
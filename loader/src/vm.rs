@@ -6,7 +6,7 @@ use memory_addr::VirtAddr;
 bitflags::bitflags! {
     /// Generic page table entry flags that indicate the corresponding mapped
     /// memory region permissions and attributes.
-    #[derive(Debug,Copy, Clone)]
+    #[derive(Debug,Copy, Clone, PartialEq, Eq)]
     pub struct DomainMappingFlags: usize {
         /// The memory is readable.
         const READ          = 1 << 0;
@@ -24,8 +24,51 @@ pub trait DomainArea: Send + Sync + Debug + Any {
     fn any(self: Box<Self>) -> Box<dyn Any>;
 }
 
+/// Frames of a domain area which can be mapped by other domain areas.
+pub trait SharedPages: Send + Sync + Debug + Any {
+    fn pages(&self) -> usize;
+    fn any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+/// The operations the kernel provides to map domains.
+///
+/// The frames of shared pages are reference counted, `unmap_domain_area`
+/// only frees a frame when neither another area nor a [`SharedPages`] uses
+/// it. A kernel which does not share pages can keep the default methods,
+/// every instance then gets its own copy.
 pub trait DomainVmOps {
     fn map_domain_area(size: usize) -> Box<dyn DomainArea>;
+    /// Map the domain area at `start`, fail if the range is in use.
+    ///
+    /// It is used to randomize the address of domains.
+    fn map_domain_area_at(
+        _start: usize,
+        _size: usize,
+    ) -> Result<Box<dyn DomainArea>, &'static str> {
+        Err("mapping at a fixed address is not supported")
+    }
     fn unmap_domain_area(area: Box<dyn DomainArea>);
+    /// Take a reference to the frames of `pages` pages at `offset` of the area.
+    fn share_pages(
+        _area: &dyn DomainArea,
+        _offset: usize,
+        _pages: usize,
+    ) -> Result<Box<dyn SharedPages>, &'static str> {
+        Err("sharing pages is not supported")
+    }
+    /// Map the shared frames at `offset` of the area in place of its own frames.
+    fn map_shared_pages(
+        _area: &dyn DomainArea,
+        _offset: usize,
+        _shared: &dyn SharedPages,
+    ) -> Result<(), &'static str> {
+        Err("sharing pages is not supported")
+    }
+    /// Drop the reference taken by [`DomainVmOps::share_pages`].
+    fn unshare_pages(_shared: Box<dyn SharedPages>) {}
     fn set_memory_x(start: usize, pages: usize) -> Result<(), &'static str>;
+    /// Remove the write permission of the pages.
+    fn set_memory_ro(start: usize, pages: usize) -> Result<(), &'static str>;
+    /// Remove the execute permission of the pages.
+    fn set_memory_nx(start: usize, pages: usize) -> Result<(), &'static str>;
 }
@@ -0,0 +1,38 @@
+//! Pick the random base of a domain area.
+
+use std::ops::Range;
+
+use loader::AslrConfig;
+
+const WINDOW: Range<usize> = 0x10_0800..0x20_0000;
+
+#[test]
+fn base_is_aligned_in_the_window() {
+    for entropy in [|| 0, || 1, || 0xff, || u64::MAX] {
+        let aslr = AslrConfig::new(WINDOW, entropy);
+        let base = aslr.random_base(0x3000).unwrap();
+        assert_eq!(base % 0x1000, 0);
+        assert!(base >= WINDOW.start && base + 0x3000 <= WINDOW.end);
+    }
+}
+
+#[test]
+fn first_and_last_slot() {
+    let aslr = AslrConfig::new(WINDOW, || 0);
+    assert_eq!(aslr.random_base(0x3000), Some(0x10_1000));
+    // 0xfd slots from 0x10_1000 to 0x1f_d000
+    let aslr = AslrConfig::new(WINDOW, || 0xfc);
+    assert_eq!(aslr.random_base(0x3000), Some(0x1f_d000));
+    let aslr = AslrConfig::new(WINDOW, || 0xfd);
+    assert_eq!(aslr.random_base(0x3000), Some(0x10_1000));
+}
+
+#[test]
+fn area_too_large_for_the_window() {
+    let aslr = AslrConfig::new(WINDOW, || 0);
+    assert_eq!(aslr.random_base(0xff000), Some(0x10_1000));
+    assert_eq!(aslr.random_base(0xff001), None);
+    assert_eq!(aslr.random_base(usize::MAX), None);
+    let aslr = AslrConfig::new(usize::MAX - 0x800..usize::MAX, || 0);
+    assert_eq!(aslr.random_base(0), None);
+}
@@ -0,0 +1,92 @@
+//! Build small domain ELF files.
+
+#![allow(dead_code)]
+
+use loader::Machine;
+
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+/// The `e_machine` and the `R_*_64` relocation type of the machine.
+fn machine_numbers(machine: Machine) -> (u16, u32) {
+    match machine {
+        Machine::X86_64 => (62, 1),
+        Machine::RISC_V => (243, 2),
+        m => panic!("no relocations for {:?}", m),
+    }
+}
+
+fn put(elf: &mut Vec<u8>, fields: &[(u64, usize)]) {
+    for (value, size) in fields {
+        elf.extend_from_slice(&value.to_le_bytes()[..*size]);
+    }
+}
+
+/// A shared object with one segment of `flags`, and one absolute relocation
+/// without a symbol which writes `0x40` at `0x100`. The entry is at `0x78`.
+pub fn elf(machine: Machine, flags: u32) -> Vec<u8> {
+    const RELA: u64 = 0x80;
+    const DYNSYM: u64 = 0x98;
+    const SHSTRTAB: u64 = 0xb0;
+    const SHOFF: u64 = 0xd0;
+    const SIZE: u64 = SHOFF + 4 * 64;
+    let names = b"\0.rela.dyn\0.dynsym\0.shstrtab\0";
+    let (e_machine, r_64) = machine_numbers(machine);
+
+    let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+    elf.resize(16, 0);
+    // type, machine, version, entry, phoff, shoff, flags, sizes and counts
+    put(
+        &mut elf,
+        &[
+            (3, 2),
+            (e_machine as u64, 2),
+            (1, 4),
+            (0x78, 8),
+            (0x40, 8),
+            (SHOFF, 8),
+            (0, 4),
+            (64, 2),
+            (56, 2),
+            (1, 2),
+            (64, 2),
+            (4, 2),
+            (3, 2),
+        ],
+    );
+    // PT_LOAD, the whole file at 0
+    put(
+        &mut elf,
+        &[
+            (1, 4),
+            (flags as u64, 4),
+            (0, 8),
+            (0, 8),
+            (0, 8),
+            (SIZE, 8),
+            (SIZE, 8),
+            (0x1000, 8),
+        ],
+    );
+    elf.resize(RELA as usize, 0);
+    put(&mut elf, &[(0x100, 8), (r_64 as u64, 8), (0x40, 8)]);
+    // the null symbol
+    elf.resize(SHSTRTAB as usize, 0);
+    elf.extend_from_slice(names);
+    elf.resize(SHOFF as usize, 0);
+    // name, type, flags, addr, offset, size, link, info, align, entsize
+    let sections = [
+        [0; 10],
+        [1, 4, 2, RELA, RELA, 24, 2, 0, 8, 24],
+        [11, 11, 2, DYNSYM, DYNSYM, 24, 3, 1, 8, 24],
+        [19, 3, 0, 0, SHSTRTAB, names.len() as u64, 0, 0, 1, 0],
+    ];
+    for section in sections {
+        let sizes = [4, 4, 8, 8, 8, 8, 4, 4, 8, 8];
+        let fields = section.into_iter().zip(sizes).collect::<Vec<_>>();
+        put(&mut elf, &fields);
+    }
+    assert_eq!(elf.len() as u64, SIZE);
+    elf
+}
@@ -0,0 +1,69 @@
+//! Decompress domain images within the size bound.
+
+use std::io::Write;
+
+use loader::{decompress, detect_compression, Compression, LoaderError};
+
+/// An image which compresses well but is not trivial.
+fn image() -> Vec<u8> {
+    (0..200_000u32)
+        .map(|i| ((i % 251) ^ (i / 1000)) as u8)
+        .collect()
+}
+
+fn lz4(data: &[u8]) -> Vec<u8> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zstd(data: &[u8]) -> Vec<u8> {
+    zstd::encode_all(data, 3).unwrap()
+}
+
+#[test]
+fn detect() {
+    let image = image();
+    assert_eq!(detect_compression(&image), Compression::None);
+    assert_eq!(detect_compression(&lz4(&image)), Compression::Lz4);
+    assert_eq!(detect_compression(&zstd(&image)), Compression::Zstd);
+}
+
+#[test]
+fn uncompressed_image_is_borrowed() {
+    let image = image();
+    let data = decompress(&image, 0).unwrap();
+    assert!(matches!(data, std::borrow::Cow::Borrowed(_)));
+}
+
+#[test]
+fn lz4_bounds() {
+    let image = image();
+    let data = lz4(&image);
+    assert!(data.len() < image.len());
+    assert_eq!(decompress(&data, image.len()).unwrap(), image);
+    assert!(matches!(
+        decompress(&data, image.len() - 1),
+        Err(LoaderError::Decompress(_))
+    ));
+    assert!(matches!(
+        decompress(&data[..data.len() / 2], image.len()),
+        Err(LoaderError::Decompress(_))
+    ));
+}
+
+#[test]
+fn zstd_bounds() {
+    let image = image();
+    let data = zstd(&image);
+    assert!(data.len() < image.len());
+    assert_eq!(decompress(&data, image.len()).unwrap(), image);
+    assert!(matches!(
+        decompress(&data, image.len() - 1),
+        Err(LoaderError::Decompress(_))
+    ));
+    assert!(matches!(
+        decompress(&data[..data.len() / 2], image.len()),
+        Err(LoaderError::Decompress(_))
+    ));
+}
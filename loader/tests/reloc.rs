@@ -0,0 +1,43 @@
+//! Check the machine and the relocations of a domain ELF file.
+
+mod common;
+
+use common::{elf, PF_R, PF_X};
+use loader::{check_image, check_image_for, LoaderError, Machine, NATIVE_MACHINE};
+
+fn image(machine: Machine) -> Vec<u8> {
+    elf(machine, PF_R | PF_X)
+}
+
+fn foreign_machine() -> Machine {
+    match NATIVE_MACHINE {
+        Machine::X86_64 => Machine::RISC_V,
+        _ => Machine::X86_64,
+    }
+}
+
+#[test]
+fn relocation_without_symbol() {
+    for machine in [Machine::X86_64, Machine::RISC_V] {
+        let info = check_image_for(&image(machine), machine).unwrap();
+        assert_eq!(info.machine, machine);
+        assert_eq!(info.relocations, 1);
+        assert_eq!(info.entry, 0x78);
+    }
+}
+
+#[test]
+fn machine_of_the_kernel() {
+    let foreign = foreign_machine();
+    assert_eq!(
+        check_image(&image(foreign)).unwrap_err(),
+        LoaderError::WrongMachine {
+            expected: NATIVE_MACHINE,
+            found: foreign,
+        }
+    );
+    assert!(check_image_for(&image(foreign), foreign).is_ok());
+    if NATIVE_MACHINE != Machine::None {
+        assert!(check_image(&image(NATIVE_MACHINE)).is_ok());
+    }
+}
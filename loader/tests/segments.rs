@@ -0,0 +1,36 @@
+//! Check the permissions of the segments of a domain.
+
+mod common;
+
+use common::{elf, PF_R, PF_W, PF_X};
+use loader::{check_image_for, DomainMappingFlags, LoaderError, Machine};
+
+#[test]
+fn segment_flags() {
+    let info = check_image_for(&elf(Machine::RISC_V, PF_R | PF_X), Machine::RISC_V).unwrap();
+    assert_eq!(info.area_size, 0x1000);
+    assert_eq!(info.segments.len(), 1);
+    assert_eq!(info.segments[0].range, 0..0x1000);
+    assert_eq!(
+        info.segments[0].flags,
+        DomainMappingFlags::READ | DomainMappingFlags::EXECUTE
+    );
+}
+
+#[test]
+fn writable_and_executable_is_refused() {
+    let elf = elf(Machine::RISC_V, PF_R | PF_W | PF_X);
+    assert!(matches!(
+        check_image_for(&elf, Machine::RISC_V),
+        Err(LoaderError::WritableExecutable { vaddr: 0 })
+    ));
+}
+
+#[test]
+fn entry_must_be_executable() {
+    let elf = elf(Machine::RISC_V, PF_R);
+    assert_eq!(
+        check_image_for(&elf, Machine::RISC_V).unwrap_err(),
+        LoaderError::BadEntry { entry: 0x78 }
+    );
+}
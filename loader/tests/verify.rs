@@ -0,0 +1,66 @@
+//! Check the signature of a domain image.
+
+use ed25519_compact::{KeyPair, Seed};
+use loader::{
+    image_hash, split_signed, verify_image, VerifyError, VerifyPolicy, SIGNATURE_MAGIC,
+    SIGNATURE_TRAILER_LEN,
+};
+
+fn sign(payload: &[u8], seed: u8) -> (Vec<u8>, [u8; 32]) {
+    let key = KeyPair::from_seed(Seed::new([seed; 32]));
+    let mut data = payload.to_vec();
+    data.extend_from_slice(key.sk.sign(payload, None).as_ref());
+    data.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    data.extend_from_slice(SIGNATURE_MAGIC);
+    (data, *key.pk)
+}
+
+#[test]
+fn split_signed_image() {
+    let payload = b"\x7fELF domain";
+    let (data, _) = sign(payload, 1);
+    let signed = split_signed(&data).unwrap().unwrap();
+    assert_eq!(signed.payload, payload);
+    assert_eq!(data.len(), payload.len() + SIGNATURE_TRAILER_LEN);
+
+    assert!(split_signed(payload).is_none());
+    let mut bad_len = data.clone();
+    bad_len[payload.len() + 64] ^= 1;
+    assert!(matches!(
+        split_signed(&bad_len),
+        Some(Err(VerifyError::Malformed))
+    ));
+}
+
+#[test]
+fn verify_policies() {
+    let payload = b"\x7fELF domain";
+    let (data, key) = sign(payload, 1);
+    let (_, other_key) = sign(payload, 2);
+
+    assert_eq!(verify_image(payload, &VerifyPolicy::None), Ok(&payload[..]));
+    assert_eq!(verify_image(&data, &VerifyPolicy::None), Ok(&payload[..]));
+
+    let trusted = VerifyPolicy::Ed25519(vec![other_key, key]);
+    assert_eq!(verify_image(&data, &trusted), Ok(&payload[..]));
+    assert_eq!(verify_image(payload, &trusted), Err(VerifyError::Unsigned));
+    let untrusted = VerifyPolicy::Ed25519(vec![other_key]);
+    assert_eq!(
+        verify_image(&data, &untrusted),
+        Err(VerifyError::BadSignature)
+    );
+    let mut tampered = data.clone();
+    tampered[0] ^= 1;
+    assert_eq!(
+        verify_image(&tampered, &trusted),
+        Err(VerifyError::BadSignature)
+    );
+
+    let allowed = VerifyPolicy::HashAllowlist(vec![image_hash(payload)]);
+    assert_eq!(verify_image(&data, &allowed), Ok(&payload[..]));
+    assert_eq!(verify_image(payload, &allowed), Ok(&payload[..]));
+    assert_eq!(
+        verify_image(&tampered, &allowed),
+        Err(VerifyError::UntrustedHash)
+    );
+}
@@ -0,0 +1,232 @@
+//! The manifest of a domain.
+//!
+//! `#[domain_main(...)]` places a [`RawManifest`] in the `.domain_manifest`
+//! section of the domain ELF, the loader parses it with [`Manifest::parse`]
+//! before any code of the domain runs. The linker script of the domain should
+//! keep the section.
+//!
+//! Layout, all integers are little endian:
+//! ```text
+//! header: magic, interface version, domain type, abi fingerprint,
+//!         domain version, body length
+//! body:
+//!     dependency count: u16, dependencies: (len: u16, name)
+//!     mmio count: u16, mmio ranges: (start: u64, size: u64)
+//!     irq count: u16, irqs: u32
+//! ```
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{Display, Formatter};
+
+/// The name of the section which holds the manifest.
+pub const MANIFEST_SECTION: &str = ".domain_manifest";
+pub const MANIFEST_MAGIC: [u8; 8] = *b"DOMMANI1";
+/// The max length of the domain version.
+pub const VERSION_LEN: usize = 32;
+const HEADER_LEN: usize = 8 + 4 + 4 + 8 + VERSION_LEN + 4;
+
+/// The manifest as it is stored in the domain.
+///
+/// The body is encoded by `domain_main` at compile time.
+#[repr(C)]
+pub struct RawManifest<const N: usize> {
+    magic: [u8; 8],
+    interface_version: [u8; 4],
+    ty: [u8; 4],
+    abi_fingerprint: [u8; 8],
+    domain_version: [u8; VERSION_LEN],
+    body_len: [u8; 4],
+    body: [u8; N],
+}
+
+impl<const N: usize> RawManifest<N> {
+    pub const fn new(
+        ty: u8,
+        interface_version: u32,
+        abi_fingerprint: u64,
+        domain_version: &str,
+        body: [u8; N],
+    ) -> Self {
+        let version = domain_version.as_bytes();
+        assert!(version.len() <= VERSION_LEN, "domain version is too long");
+        let mut domain_version = [0; VERSION_LEN];
+        let mut i = 0;
+        while i < version.len() {
+            domain_version[i] = version[i];
+            i += 1;
+        }
+        Self {
+            magic: MANIFEST_MAGIC,
+            interface_version: interface_version.to_le_bytes(),
+            ty: [ty, 0, 0, 0],
+            abi_fingerprint: abi_fingerprint.to_le_bytes(),
+            domain_version,
+            body_len: (N as u32).to_le_bytes(),
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestError {
+    BadMagic,
+    Truncated,
+    InvalidString,
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ManifestError::BadMagic => write!(f, "bad manifest magic"),
+            ManifestError::Truncated => write!(f, "manifest is truncated"),
+            ManifestError::InvalidString => write!(f, "invalid string in manifest"),
+        }
+    }
+}
+
+/// A MMIO range the domain needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmioRange {
+    pub start: u64,
+    pub size: u64,
+}
+
+/// The parsed manifest of a domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The raw value of `DomainTypeRaw`.
+    pub ty: u8,
+    pub interface_version: u32,
+    /// The ABI fingerprint of the interface trait of the domain.
+    pub abi_fingerprint: u64,
+    pub domain_version: String,
+    /// The names of the domains this domain needs.
+    pub deps: Vec<String>,
+    pub mmio: Vec<MmioRange>,
+    pub irqs: Vec<u32>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ManifestError> {
+        if self.data.len() < len {
+            return Err(ManifestError::Truncated);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, ManifestError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ManifestError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ManifestError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self, len: usize) -> Result<&'a str, ManifestError> {
+        core::str::from_utf8(self.take(len)?).map_err(|_| ManifestError::InvalidString)
+    }
+}
+
+impl Manifest {
+    /// Parse the content of the manifest section.
+    pub fn parse(data: &[u8]) -> Result<Self, ManifestError> {
+        if data.len() < HEADER_LEN {
+            return Err(ManifestError::Truncated);
+        }
+        let mut reader = Reader { data };
+        if reader.take(8)? != MANIFEST_MAGIC {
+            return Err(ManifestError::BadMagic);
+        }
+        let interface_version = reader.u32()?;
+        let ty = reader.take(4)?[0];
+        let abi_fingerprint = reader.u64()?;
+        let version = reader.take(VERSION_LEN)?;
+        let len = version.iter().position(|b| *b == 0).unwrap_or(VERSION_LEN);
+        let domain_version = core::str::from_utf8(&version[..len])
+            .map_err(|_| ManifestError::InvalidString)?
+            .into();
+        let body_len = reader.u32()? as usize;
+        let mut body = Reader {
+            data: reader.take(body_len)?,
+        };
+        let mut deps = Vec::new();
+        for _ in 0..body.u16()? {
+            let len = body.u16()? as usize;
+            deps.push(body.str(len)?.into());
+        }
+        let mut mmio = Vec::new();
+        for _ in 0..body.u16()? {
+            let start = body.u64()?;
+            let size = body.u64()?;
+            mmio.push(MmioRange { start, size });
+        }
+        let mut irqs = Vec::new();
+        for _ in 0..body.u16()? {
+            irqs.push(body.u32()?);
+        }
+        Ok(Self {
+            ty,
+            interface_version,
+            abi_fingerprint,
+            domain_version,
+            deps,
+            mmio,
+            irqs,
+        })
+    }
+
+    /// Encode the manifest, the result has the layout of [`RawManifest`].
+    pub fn encode(&self) -> Vec<u8> {
+        let deps = self.deps.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let body = encode_body(&deps, &self.mmio, &self.irqs);
+        // a longer version is cut at a char boundary, so it stays valid utf-8
+        let mut len = self.domain_version.len().min(VERSION_LEN);
+        while !self.domain_version.is_char_boundary(len) {
+            len -= 1;
+        }
+        let version = self.domain_version[..len].as_bytes();
+        let mut data = Vec::with_capacity(HEADER_LEN + body.len());
+        data.extend_from_slice(&MANIFEST_MAGIC);
+        data.extend_from_slice(&self.interface_version.to_le_bytes());
+        data.extend_from_slice(&[self.ty, 0, 0, 0]);
+        data.extend_from_slice(&self.abi_fingerprint.to_le_bytes());
+        data.extend_from_slice(version);
+        data.resize(data.len() + VERSION_LEN - version.len(), 0);
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(&body);
+        data
+    }
+}
+
+/// Encode the body of a manifest, it is used by `domain_main`.
+pub fn encode_body(deps: &[&str], mmio: &[MmioRange], irqs: &[u32]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(deps.len() as u16).to_le_bytes());
+    for dep in deps {
+        body.extend_from_slice(&(dep.len() as u16).to_le_bytes());
+        body.extend_from_slice(dep.as_bytes());
+    }
+    body.extend_from_slice(&(mmio.len() as u16).to_le_bytes());
+    for range in mmio {
+        body.extend_from_slice(&range.start.to_le_bytes());
+        body.extend_from_slice(&range.size.to_le_bytes());
+    }
+    body.extend_from_slice(&(irqs.len() as u16).to_le_bytes());
+    for irq in irqs {
+        body.extend_from_slice(&irq.to_le_bytes());
+    }
+    body
+}
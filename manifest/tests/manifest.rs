@@ -0,0 +1,67 @@
+//! Parse the manifest of a domain image.
+
+use manifest::{encode_body, Manifest, ManifestError, MmioRange, RawManifest, VERSION_LEN};
+
+fn manifest() -> Manifest {
+    Manifest {
+        ty: 3,
+        interface_version: 2,
+        abi_fingerprint: 0x1234_5678_9abc_def0,
+        domain_version: "0.1.0".into(),
+        deps: vec!["blk".into()],
+        mmio: vec![MmioRange {
+            start: 0x1000_1000,
+            size: 0x1000,
+        }],
+        irqs: vec![1, 8],
+    }
+}
+
+#[test]
+fn manifest_roundtrip() {
+    let manifest = manifest();
+    let data = manifest.encode();
+    assert_eq!(Manifest::parse(&data), Ok(manifest));
+}
+
+#[test]
+fn manifest_encode_has_the_raw_layout() {
+    let manifest = manifest();
+    let body: [u8; 35] = encode_body(&["blk"], &manifest.mmio, &manifest.irqs)
+        .try_into()
+        .unwrap();
+    let raw = RawManifest::new(
+        manifest.ty,
+        manifest.interface_version,
+        manifest.abi_fingerprint,
+        &manifest.domain_version,
+        body,
+    );
+    // SAFETY: `RawManifest` is `repr(C)` and only has byte arrays.
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &raw as *const RawManifest<35> as *const u8,
+            size_of_val(&raw),
+        )
+    };
+    assert_eq!(bytes, manifest.encode());
+}
+
+#[test]
+fn manifest_rejects_bad_data() {
+    let mut data = manifest().encode();
+    for len in [0, 10, data.len() - 1] {
+        assert_eq!(Manifest::parse(&data[..len]), Err(ManifestError::Truncated));
+    }
+    data[0] = b'X';
+    assert_eq!(Manifest::parse(&data), Err(ManifestError::BadMagic));
+}
+
+#[test]
+fn long_version_is_cut_at_a_char_boundary() {
+    let mut manifest = manifest();
+    // the 32nd byte is in the middle of the last 'é'
+    manifest.domain_version = format!("{}é", "a".repeat(VERSION_LEN - 1));
+    let parsed = Manifest::parse(&manifest.encode()).unwrap();
+    assert_eq!(parsed.domain_version, "a".repeat(VERSION_LEN - 1));
+}
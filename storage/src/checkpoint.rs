@@ -0,0 +1,97 @@
+use core::any::Any;
+
+use crate::{ArcValueType, StorageResult};
+
+/// The sink of a checkpoint image.
+///
+/// It is implemented by the kernel, so the domain never grows a buffer which
+/// is owned by the kernel.
+pub trait ImageWriter {
+    fn write(&mut self, data: &[u8]);
+}
+
+/// A value which can be saved to a checkpoint image and restored from it.
+///
+/// The image may be restored after a reboot, so the saved bytes must not
+/// contain pointers.
+pub trait Checkpoint: Any + Send + Sync + Sized {
+    fn save(&self, w: &mut dyn ImageWriter);
+    fn restore(data: &[u8]) -> StorageResult<Self>;
+}
+
+/// The function which saves a value of the storage.
+///
+/// It is code of the domain which inserted the value, so the kernel drops it
+/// when the storage moves to another domain.
+pub type SaveFn = fn(&ArcValueType, &mut dyn ImageWriter);
+
+#[cfg(feature = "impl")]
+mod __private {
+    use alloc::sync::Arc;
+    use core::any::type_name;
+
+    use super::{Checkpoint, ImageWriter};
+    use crate::{
+        key::type_hash, ArcValueType, CustomStorge, StorageError, StorageKey, StorageResult,
+        __private::database, get, insert,
+    };
+
+    fn save_value<T: Checkpoint>(value: &ArcValueType, w: &mut dyn ImageWriter) {
+        // SAFETY: the save function is only set for a value of type `T`
+        let value = unsafe { &*(Arc::as_ptr(value) as *const T) };
+        value.save(w);
+    }
+
+    /// Insert a value which is saved when the storage is checkpointed.
+    pub fn insert_checkpoint<T: Checkpoint>(
+        key: &StorageKey<T>,
+        value: T,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        let old = insert(key, value)?;
+        database().set_checkpoint(key.name(), Some(save_value::<T>));
+        Ok(old)
+    }
+
+    /// Save the existing value of the key when the storage is checkpointed.
+    ///
+    /// The new domain calls it after an update for the values it inherits.
+    pub fn enable_checkpoint<T: Checkpoint>(key: &StorageKey<T>) -> StorageResult<bool> {
+        if get(key)?.is_none() {
+            return Ok(false);
+        }
+        Ok(database().set_checkpoint(key.name(), Some(save_value::<T>)))
+    }
+
+    /// Restore the value of the key from the image given to the kernel.
+    ///
+    /// The type is checked by the hash of its path and layout, the type id
+    /// differs between builds. The restored value is inserted into the storage.
+    pub fn restore<T: Checkpoint>(
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        let mut res = Ok(None);
+        database().take_restored(key.name(), &mut |info, data| {
+            if info.type_hash != type_hash::<T>() {
+                res = Err(StorageError::TypeMismatch {
+                    expected: type_name::<T>(),
+                });
+            } else if info.version != key.version() {
+                res = Err(StorageError::VersionMismatch {
+                    expected: key.version(),
+                    found: info.version,
+                });
+            } else {
+                res = T::restore(data).map(Some);
+            }
+        });
+        let value = match res? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        insert_checkpoint(key, value)?;
+        get(key)
+    }
+}
+
+#[cfg(feature = "impl")]
+pub use __private::*;
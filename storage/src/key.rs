@@ -0,0 +1,189 @@
+use alloc::sync::Arc;
+use core::{
+    any::{type_name, Any},
+    fmt::{Debug, Display, Formatter},
+    marker::PhantomData,
+};
+
+use crate::{ArcValueType, CustomStorge};
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageError {
+    /// The stored value is not of the type the key expects.
+    TypeMismatch { expected: &'static str },
+    /// The stored value was written with another schema version.
+    VersionMismatch { expected: u32, found: u32 },
+    /// The migration of a value from an old version failed.
+    MigrationFailed { from: u32, to: u32 },
+    /// The value can not be taken, other clones of it are still alive.
+    Shared { strong_count: usize },
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StorageError::TypeMismatch { expected } => {
+                write!(f, "stored value is not a {}", expected)
+            }
+            StorageError::VersionMismatch { expected, found } => {
+                write!(f, "expected schema version {}, found {}", expected, found)
+            }
+            StorageError::MigrationFailed { from, to } => {
+                write!(f, "failed to migrate from version {} to {}", from, to)
+            }
+            StorageError::Shared { strong_count } => {
+                write!(f, "value is still shared, {} references", strong_count)
+            }
+        }
+    }
+}
+
+/// Return a hash of the type which is the same in every domain binary.
+///
+/// `TypeId` can not be used, it differs between two compilations of the same
+/// type. The hash covers the path of the type, its size and its alignment,
+/// the schema version of the key covers the changes of the layout which keep
+/// them.
+pub fn type_hash<T>() -> u64 {
+    // FNV-1a
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let size = core::mem::size_of::<T>() as u64;
+    let align = core::mem::align_of::<T>() as u64;
+    let bytes = type_name::<T>()
+        .bytes()
+        .chain(size.to_le_bytes())
+        .chain(align.to_le_bytes());
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// A typed handle of a value in the domain storage.
+///
+/// The key records the type of the value and its schema version. Both are
+/// saved with the value on insert and checked again when the value is read,
+/// so a new domain version can not reinterpret the state of the old one.
+pub struct StorageKey<T> {
+    name: &'static str,
+    version: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> StorageKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self::with_version(name, 0)
+    }
+
+    pub const fn with_version(name: &'static str, version: u32) -> Self {
+        Self {
+            name,
+            version,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub const fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl<T: Any + Send + Sync> StorageKey<T> {
+    /// Return the type information which is saved with the value.
+    pub fn value_info(&self) -> ValueInfo<'static> {
+        ValueInfo {
+            type_hash: type_hash::<T>(),
+            type_name: type_name::<T>(),
+            version: self.version,
+        }
+    }
+}
+
+impl<T> Clone for StorageKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for StorageKey<T> {}
+
+impl<T> Debug for StorageKey<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StorageKey")
+            .field("name", &self.name)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// The type information of a value in the domain storage.
+///
+/// The type name is borrowed from the domain, the storage should copy it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueInfo<'a> {
+    /// See [`type_hash`].
+    pub type_hash: u64,
+    pub type_name: &'a str,
+    pub version: u32,
+}
+
+/// A value in the domain storage together with the type it was inserted with.
+pub struct StorageEntry {
+    pub value: ArcValueType,
+    pub type_hash: u64,
+    pub version: u32,
+}
+
+impl Debug for StorageEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StorageEntry")
+            .field("type_hash", &self.type_hash)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+impl StorageEntry {
+    /// Check that the entry holds a value of type `T`, of any version.
+    pub fn check_type<T: Any + Send + Sync>(&self) -> StorageResult<()> {
+        if self.type_hash != type_hash::<T>() {
+            return Err(StorageError::TypeMismatch {
+                expected: type_name::<T>(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the entry holds a value of the key's type and version.
+    pub fn check<T: Any + Send + Sync>(&self, key: &StorageKey<T>) -> StorageResult<()> {
+        self.check_type::<T>()?;
+        if self.version != key.version() {
+            return Err(StorageError::VersionMismatch {
+                expected: key.version(),
+                found: self.version,
+            });
+        }
+        Ok(())
+    }
+
+    /// Convert the entry into the value of the key.
+    ///
+    /// The type hash is compared instead of calling `Any::type_id`, because
+    /// the vtable of the value may belong to a domain which is already
+    /// unloaded.
+    pub fn downcast<T: Any + Send + Sync>(
+        self,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Arc<T, CustomStorge>> {
+        self.check(key)?;
+        // SAFETY: the type hash has been checked above
+        Ok(unsafe { self.value.downcast_unchecked::<T>() })
+    }
+}
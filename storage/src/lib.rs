@@ -3,6 +3,15 @@
 #![no_std]
 #![no_main]
 extern crate alloc;
+mod checkpoint;
+mod key;
+mod meta;
+mod migrate;
+#[cfg(feature = "impl")]
+mod namespace;
+#[cfg(feature = "impl")]
+mod transaction;
+
 use alloc::{boxed::Box, sync::Arc};
 use core::{
     alloc::{AllocError, Allocator, Layout},
@@ -10,14 +19,72 @@ use core::{
     ptr::NonNull,
 };
 
+#[cfg(feature = "impl")]
+pub use checkpoint::{enable_checkpoint, insert_checkpoint, restore};
+pub use checkpoint::{Checkpoint, ImageWriter, SaveFn};
+pub use key::{StorageEntry, StorageError, StorageKey, StorageResult, ValueInfo};
+pub use meta::{EntryInfo, EntryMeta};
+pub use migrate::StorageMigration;
 use spin::Once;
 pub trait SendAllocator: Allocator + Send + Sync {}
-type ArcValueType = Arc<dyn Any + Send + Sync, CustomStorge>;
+pub type ArcValueType = Arc<dyn Any + Send + Sync, CustomStorge>;
 
 pub trait DomainDataStorage: Send + Sync {
-    fn insert(&self, key: &str, value: ArcValueType) -> Option<ArcValueType>;
-    fn get(&self, key: &str) -> Option<ArcValueType>;
-    fn remove(&self, key: &str) -> Option<ArcValueType>;
+    /// Insert a value with its type information, return the replaced entry.
+    ///
+    /// `check` gets the entry of the key under the same lock, if it fails the
+    /// value is not inserted and the entry stays.
+    fn insert(
+        &self,
+        key: &str,
+        value: ArcValueType,
+        info: ValueInfo<'_>,
+        check: &dyn Fn(&StorageEntry) -> StorageResult<()>,
+    ) -> StorageResult<Option<StorageEntry>>;
+    fn get(&self, key: &str) -> Option<StorageEntry>;
+    fn remove(&self, key: &str) -> Option<StorageEntry>;
+    /// Remove the value if `check` accepts it, under the same lock.
+    ///
+    /// `check` gets the entry and the number of references to the value which
+    /// are held outside the storage, plus one for the storage itself.
+    fn remove_if(
+        &self,
+        key: &str,
+        check: &dyn Fn(&StorageEntry, usize) -> StorageResult<()>,
+    ) -> StorageResult<Option<StorageEntry>>;
+    /// Register a migration for the value of the key.
+    fn register_migration(&self, key: &str, migration: Box<dyn StorageMigration, CustomStorge>);
+    /// Apply all writes under one lock, so no one sees a part of them.
+    ///
+    /// The replaced and removed entries are passed to `replaced`, they should
+    /// be dropped by the caller after the call returns.
+    fn commit(
+        &self,
+        ops: &mut dyn Iterator<Item = WriteOp<'_>>,
+        replaced: &mut dyn FnMut(StorageEntry),
+    );
+    /// Set the function which saves the value to a checkpoint image.
+    ///
+    /// Return false if the key does not exist.
+    fn set_checkpoint(&self, key: &str, save: Option<SaveFn>) -> bool;
+    /// Pass the restored record of the key to `f` and forget it.
+    ///
+    /// `f` gets the type information and the saved bytes.
+    fn take_restored(&self, key: &str, f: &mut dyn FnMut(ValueInfo<'_>, &[u8]));
+    /// Call `f` for every key which starts with `prefix`, in key order.
+    fn for_each(&self, prefix: &str, f: &mut dyn FnMut(&str, &EntryMeta<'_>));
+}
+
+/// A write staged by a transaction.
+pub enum WriteOp<'a> {
+    Insert {
+        key: &'a str,
+        value: ArcValueType,
+        info: ValueInfo<'a>,
+    },
+    Remove {
+        key: &'a str,
+    },
 }
 
 /// A custom allocator which allocates memory from the custom heap
@@ -68,98 +135,266 @@ impl StorageArg {
 
 #[cfg(feature = "impl")]
 mod __private {
-    use alloc::{boxed::Box, sync::Arc};
+    use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
     use core::any::Any;
 
     use spin::Once;
 
-    use crate::{ArcValueType, CustomStorge, DataStorageHeap, DomainDataStorage};
+    use crate::{
+        migrate::TypedMigration, CustomStorge, DomainDataStorage, EntryInfo, EntryMeta,
+        StorageEntry, StorageError, StorageKey, StorageResult,
+    };
 
-    pub fn insert<T: Any + Send + Sync>(key: &str, value: T) -> Option<Arc<T, CustomStorge>> {
+    /// Insert a value into the storage.
+    ///
+    /// The replaced value is returned if it has the same version as the key,
+    /// otherwise it is dropped. If the key holds a value of another type,
+    /// [`StorageError::TypeMismatch`] is returned and the value stays.
+    pub fn insert<T: Any + Send + Sync>(
+        key: &StorageKey<T>,
+        value: T,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        insert_named(key.name(), key, value)
+    }
+
+    pub fn get<T: Any + Send + Sync>(
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        get_named(key.name(), key)
+    }
+
+    pub fn get_or_insert<T: Any + Send + Sync, F: FnOnce() -> T>(
+        key: &StorageKey<T>,
+        f: F,
+    ) -> StorageResult<Arc<T, CustomStorge>> {
+        get_or_insert_named(key.name(), key, || Arc::new_in(f(), CustomStorge))
+    }
+
+    pub fn get_or_insert_in<T: Any + Send + Sync, F: FnOnce() -> Arc<T, CustomStorge>>(
+        key: &StorageKey<T>,
+        f: F,
+    ) -> StorageResult<Arc<T, CustomStorge>> {
+        get_or_insert_named(key.name(), key, f)
+    }
+
+    /// Remove a value from the storage.
+    ///
+    /// The clones of the value which are still held elsewhere stay valid, the
+    /// value is dropped when the last one is dropped.
+    pub fn remove<T: Any + Send + Sync>(
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        remove_named(key.name(), key)
+    }
+
+    /// Take the value out of the storage if no clone of it exists.
+    ///
+    /// If the value is still shared, [`StorageError::Shared`] is returned and
+    /// the value stays in the storage.
+    ///
+    /// A clone held by a domain which has been replaced is never dropped, the
+    /// kernel forgets the old domain instead of dropping it. A value cloned
+    /// by an old domain stays shared for good, use [`remove`] for it.
+    pub fn take<T: Any + Send + Sync>(key: &StorageKey<T>) -> StorageResult<Option<T>> {
+        take_named(key.name(), key)
+    }
+
+    /// Take the value out of the storage once all clones of it are dropped.
+    ///
+    /// `wait` is called with the number of references while the value is
+    /// shared, e.g. to yield. If it returns `false` or the value is still
+    /// shared after `attempts` tries, [`StorageError::Shared`] is returned and
+    /// the value stays in the storage.
+    ///
+    /// The clones leaked by a replaced domain are never dropped, see
+    /// [`take`], so the number of attempts is bounded.
+    pub fn take_when_unique<T: Any + Send + Sync, F: FnMut(usize) -> bool>(
+        key: &StorageKey<T>,
+        attempts: usize,
+        wait: F,
+    ) -> StorageResult<Option<T>> {
+        take_when_unique_named(key.name(), key, attempts, wait)
+    }
+
+    pub(crate) fn insert_named<T: Any + Send + Sync>(
+        name: &str,
+        key: &StorageKey<T>,
+        value: T,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
         let arc = Arc::new_in(value, CustomStorge);
-        let old = DATABASE.get().unwrap().insert(key, arc);
-        let old_arc = match old {
-            Some(arc) => unsafe {
-                let v = arc.downcast_unchecked::<T>();
-                Some(v)
-            },
-            None => None,
-        };
-        old_arc
+        let check = |entry: &StorageEntry| entry.check_type::<T>();
+        let old = DATABASE
+            .get()
+            .unwrap()
+            .insert(name, arc, key.value_info(), &check)?;
+        Ok(old.and_then(|entry| {
+            // SAFETY: `check` accepted the type of the replaced value, so it is
+            // dropped with the drop glue of this domain
+            let value = unsafe { entry.value.downcast_unchecked::<T>() };
+            if entry.version == key.version() {
+                return Some(value);
+            }
+            log::warn!(
+                "insert {:?}: drop the replaced value of version {}",
+                name,
+                entry.version
+            );
+            None
+        }))
     }
 
-    pub fn get<T: Any + Send + Sync>(key: &str) -> Option<Arc<T, CustomStorge>> {
-        let res = DATABASE
+    pub(crate) fn get_named<T: Any + Send + Sync>(
+        name: &str,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        DATABASE
             .get()
             .unwrap()
-            .get(key)
-            .and_then(|arc_wrapper| unsafe {
-                let res = arc_wrapper.downcast_unchecked();
-                Some(res)
-            });
-        res
+            .get(name)
+            .map(|entry| entry.downcast(key))
+            .transpose()
     }
 
-    pub fn get_or_insert<T: Any + Send + Sync, F: FnOnce() -> T>(
-        key: &str,
+    pub(crate) fn get_or_insert_named<T: Any + Send + Sync, F: FnOnce() -> Arc<T, CustomStorge>>(
+        name: &str,
+        key: &StorageKey<T>,
         f: F,
-    ) -> Arc<T, CustomStorge> {
-        let arc = get::<T>(&key);
+    ) -> StorageResult<Arc<T, CustomStorge>> {
+        let arc = get_named(name, key)?;
         match arc {
-            Some(arc) => arc,
+            Some(arc) => Ok(arc),
             None => {
                 let value = f();
-                let arc = Arc::new_in(value, CustomStorge);
-                DATABASE.get().unwrap().insert(key, arc.clone());
-                arc
+                let check = |entry: &StorageEntry| entry.check_type::<T>();
+                DATABASE
+                    .get()
+                    .unwrap()
+                    .insert(name, value.clone(), key.value_info(), &check)?;
+                Ok(value)
             }
         }
     }
 
-    pub fn get_or_insert_in<T: Any + Send + Sync, F: FnOnce() -> Arc<T, CustomStorge>>(
-        key: &str,
-        f: F,
-    ) -> Arc<T, CustomStorge> {
-        let arc = get::<T>(&key);
-        match arc {
-            Some(arc) => arc,
-            None => {
-                let value = f();
-                DATABASE.get().unwrap().insert(key, value.clone());
-                value
+    pub(crate) fn remove_named<T: Any + Send + Sync>(
+        name: &str,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        DATABASE
+            .get()
+            .unwrap()
+            .remove_if(name, &|entry, _| entry.check(key))?
+            .map(|entry| entry.downcast(key))
+            .transpose()
+    }
+
+    pub(crate) fn take_named<T: Any + Send + Sync>(
+        name: &str,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<T>> {
+        let check = |entry: &StorageEntry, strong_count: usize| {
+            entry.check(key)?;
+            if strong_count != 1 {
+                return Err(StorageError::Shared { strong_count });
+            }
+            Ok(())
+        };
+        let value = DATABASE.get().unwrap().remove_if(name, &check)?;
+        match value {
+            Some(entry) => {
+                let value = entry.downcast(key)?;
+                // no clone existed when the value was removed under the lock
+                Ok(Some(
+                    Arc::into_inner(value).expect("the value is not unique"),
+                ))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn take_when_unique_named<T: Any + Send + Sync, F: FnMut(usize) -> bool>(
+        name: &str,
+        key: &StorageKey<T>,
+        attempts: usize,
+        mut wait: F,
+    ) -> StorageResult<Option<T>> {
+        let mut res = take_named(name, key);
+        for _ in 1..attempts {
+            match res {
+                Err(StorageError::Shared { strong_count }) if wait(strong_count) => {
+                    res = take_named(name, key);
+                }
+                _ => break,
             }
         }
+        if let Err(StorageError::Shared { strong_count }) = res {
+            log::warn!(
+                "take {:?}: still {} references, a clone may be leaked",
+                name,
+                strong_count
+            );
+        }
+        res
+    }
+
+    /// Call `f` for every key which starts with `prefix`, in key order.
+    pub fn for_each_with_prefix<F: FnMut(&str, &EntryMeta<'_>)>(prefix: &str, mut f: F) {
+        DATABASE.get().unwrap().for_each(prefix, &mut f);
+    }
+
+    /// Return all keys in the storage.
+    pub fn keys() -> Vec<String> {
+        keys_with_prefix("")
+    }
+
+    /// Return all keys which start with `prefix`.
+    pub fn keys_with_prefix(prefix: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        for_each_with_prefix(prefix, |key, _| keys.push(String::from(key)));
+        keys
     }
 
-    pub fn remove<T: Any + Send + Sync>(key: &str) -> Option<Arc<T, CustomStorge>> {
-        let res = DATABASE
+    /// Return the metadata of the value with the given key.
+    pub fn metadata(key: &str) -> Option<EntryInfo> {
+        let mut info = None;
+        for_each_with_prefix(key, |k, meta| {
+            if k == key {
+                info = Some(EntryInfo::new(k, meta));
+            }
+        });
+        info
+    }
+
+    /// Register a migration from the value stored with `from` version to the
+    /// version of the key.
+    ///
+    /// Migrations are run by the kernel when the domain replaces an old one,
+    /// before the `init` of the domain, so they must be registered in the
+    /// `main` of the domain. They can form a chain, e.g. 1 -> 2 and 2 -> 3.
+    /// If one fails, the key still holds the old value.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the value stored with `from` version is a
+    /// `O`. The type hash of the old value is not checked, `O` is usually a
+    /// copy of the old type under another name.
+    pub unsafe fn register_migration<O: Any + Send + Sync, N: Any + Send + Sync>(
+        key: &StorageKey<N>,
+        from: u32,
+        f: fn(&O) -> StorageResult<N>,
+    ) {
+        let migration = TypedMigration::new(from, key.value_info(), f);
+        DATABASE
             .get()
             .unwrap()
-            .remove(key)
-            .and_then(|value| unsafe {
-                let strong_count = Arc::strong_count(&value);
-                log::info!("remove_data: {:?}, ref count: {}", key, strong_count);
-                assert!(strong_count >= 2);
-                let value = value.downcast_unchecked::<T>();
-                let value = if strong_count != 2 {
-                    // decrement strong count to 2
-                    let raw = Arc::into_raw(value);
-                    for _ in 0..(strong_count - 2) {
-                        Arc::decrement_strong_count_in(raw, CustomStorge);
-                    }
-                    Arc::from_raw_in(raw, CustomStorge)
-                } else {
-                    value
-                };
-                let strong_count = Arc::strong_count(&value);
-                log::info!("remove_data: {:?}, ref count: {}", key, strong_count);
-                Some(value)
-            });
-        res
+            .register_migration(key.name(), Box::new_in(migration, CustomStorge));
     }
 
     static DATABASE: Once<Box<dyn DomainDataStorage>> = Once::new();
 
+    pub(crate) fn database() -> &'static dyn DomainDataStorage {
+        DATABASE.get().unwrap().as_ref()
+    }
+
     pub fn init_database(database: Box<dyn DomainDataStorage>) {
         DATABASE.call_once(|| database);
         log::info!("init database success");
@@ -168,3 +403,7 @@ mod __private {
 
 #[cfg(feature = "impl")]
 pub use __private::*;
+#[cfg(feature = "impl")]
+pub use namespace::{namespace, Namespace};
+#[cfg(feature = "impl")]
+pub use transaction::{transaction, Transaction};
@@ -0,0 +1,37 @@
+use alloc::string::{String, ToString};
+
+/// The metadata of a value in the domain storage.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryMeta<'a> {
+    pub type_name: &'a str,
+    pub version: u32,
+    /// The size of the value, without the memory it owns.
+    pub size: usize,
+    pub strong_count: usize,
+    /// The time the value was inserted, in nanoseconds.
+    pub inserted_at: u64,
+}
+
+/// An owned copy of [`EntryMeta`].
+#[derive(Debug, Clone)]
+pub struct EntryInfo {
+    pub key: String,
+    pub type_name: String,
+    pub version: u32,
+    pub size: usize,
+    pub strong_count: usize,
+    pub inserted_at: u64,
+}
+
+impl EntryInfo {
+    pub fn new(key: &str, meta: &EntryMeta<'_>) -> Self {
+        Self {
+            key: key.to_string(),
+            type_name: meta.type_name.to_string(),
+            version: meta.version,
+            size: meta.size,
+            strong_count: meta.strong_count,
+            inserted_at: meta.inserted_at,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use alloc::sync::Arc;
+use core::{any::Any, marker::PhantomData};
+
+use crate::{ArcValueType, CustomStorge, StorageEntry, StorageResult, ValueInfo};
+
+/// A migration of one value in the storage from an old schema version.
+///
+/// Migrations are registered by the new domain when it is created, before
+/// its `init`, and run by the kernel during the update before it calls the
+/// `init` of the new domain, so `init` reads the migrated values.
+pub trait StorageMigration: Send + Sync {
+    /// The version of the value this migration accepts.
+    fn source_version(&self) -> u32;
+    /// Create the new value from the old one.
+    ///
+    /// The old value is not changed, so the update can be aborted if any
+    /// migration fails.
+    fn migrate(&self, old: &StorageEntry) -> StorageResult<(ArcValueType, ValueInfo<'static>)>;
+}
+
+pub(crate) struct TypedMigration<O, N> {
+    from: u32,
+    to: ValueInfo<'static>,
+    f: fn(&O) -> StorageResult<N>,
+    _marker: PhantomData<fn(&O) -> N>,
+}
+
+impl<O, N> TypedMigration<O, N> {
+    #[allow(unused)]
+    pub(crate) fn new(from: u32, to: ValueInfo<'static>, f: fn(&O) -> StorageResult<N>) -> Self {
+        Self {
+            from,
+            to,
+            f,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<O: Any + Send + Sync, N: Any + Send + Sync> StorageMigration for TypedMigration<O, N> {
+    fn source_version(&self) -> u32 {
+        self.from
+    }
+
+    fn migrate(&self, old: &StorageEntry) -> StorageResult<(ArcValueType, ValueInfo<'static>)> {
+        // The type hash of the old value is not compared here, `O` is usually
+        // a copy of the old type under another name. The version identifies
+        // the layout.
+        //
+        // SAFETY: the caller of `register_migration` guarantees that the value
+        // stored with `from` version is a `O`.
+        let old = unsafe { &*(Arc::as_ptr(&old.value) as *const O) };
+        let new = (self.f)(old)?;
+        Ok((Arc::new_in(new, CustomStorge), self.to))
+    }
+}
@@ -0,0 +1,125 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::any::Any;
+
+use crate::{
+    transaction::transaction_in,
+    CustomStorge, EntryInfo, EntryMeta, StorageKey, StorageResult, Transaction,
+    __private::{
+        for_each_with_prefix, get_named, get_or_insert_named, insert_named, remove_named,
+        take_named, take_when_unique_named,
+    },
+};
+
+/// The separator between the name of a namespace and the key.
+const SEPARATOR: &str = "/";
+
+/// A view of the storage where all keys are prefixed with the namespace.
+///
+/// Subsystems of a domain can use their own namespace, so their keys do not
+/// collide and can be listed or removed together.
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    prefix: String,
+}
+
+/// Open the namespace with the given name.
+pub fn namespace(name: &str) -> Namespace {
+    Namespace {
+        prefix: format!("{}{}", name, SEPARATOR),
+    }
+}
+
+impl Namespace {
+    /// Open a namespace inside this one.
+    pub fn namespace(&self, name: &str) -> Namespace {
+        Namespace {
+            prefix: format!("{}{}{}", self.prefix, name, SEPARATOR),
+        }
+    }
+
+    /// The prefix of all keys in the namespace.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    pub fn insert<T: Any + Send + Sync>(
+        &self,
+        key: &StorageKey<T>,
+        value: T,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        insert_named(&self.full_name(key.name()), key, value)
+    }
+
+    pub fn get<T: Any + Send + Sync>(
+        &self,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        get_named(&self.full_name(key.name()), key)
+    }
+
+    pub fn get_or_insert<T: Any + Send + Sync, F: FnOnce() -> T>(
+        &self,
+        key: &StorageKey<T>,
+        f: F,
+    ) -> StorageResult<Arc<T, CustomStorge>> {
+        get_or_insert_named(&self.full_name(key.name()), key, || {
+            Arc::new_in(f(), CustomStorge)
+        })
+    }
+
+    pub fn remove<T: Any + Send + Sync>(
+        &self,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        remove_named(&self.full_name(key.name()), key)
+    }
+
+    pub fn take<T: Any + Send + Sync>(&self, key: &StorageKey<T>) -> StorageResult<Option<T>> {
+        take_named(&self.full_name(key.name()), key)
+    }
+
+    pub fn take_when_unique<T: Any + Send + Sync, F: FnMut(usize) -> bool>(
+        &self,
+        key: &StorageKey<T>,
+        attempts: usize,
+        wait: F,
+    ) -> StorageResult<Option<T>> {
+        take_when_unique_named(&self.full_name(key.name()), key, attempts, wait)
+    }
+
+    /// Call `f` for every key in the namespace, the key is relative to it.
+    pub fn for_each<F: FnMut(&str, &EntryMeta<'_>)>(&self, mut f: F) {
+        let len = self.prefix.len();
+        for_each_with_prefix(&self.prefix, |key, meta| f(&key[len..], meta));
+    }
+
+    /// Return all keys in the namespace, relative to it.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.for_each(|key, _| keys.push(key.to_string()));
+        keys
+    }
+
+    /// Return the metadata of the value with the given key.
+    pub fn metadata(&self, name: &str) -> Option<EntryInfo> {
+        crate::__private::metadata(&self.full_name(name))
+    }
+
+    /// Run `f` in a transaction on the keys of the namespace, see
+    /// [`transaction`](crate::transaction).
+    pub fn transaction<R, E, F: FnOnce(&mut Transaction) -> Result<R, E>>(
+        &self,
+        f: F,
+    ) -> Result<R, E> {
+        transaction_in(&self.prefix, f)
+    }
+}
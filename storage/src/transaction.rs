@@ -0,0 +1,116 @@
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use core::any::Any;
+
+use crate::{
+    ArcValueType, CustomStorge, StorageEntry, StorageKey, StorageResult, ValueInfo, WriteOp,
+    __private::{database, get_named},
+};
+
+enum Staged {
+    Insert(ArcValueType, ValueInfo<'static>),
+    Remove,
+}
+
+/// The writes of a transaction which are not visible to the storage yet.
+///
+/// If the transaction is dropped without commit, e.g. when the domain unwinds
+/// through `basic::catch_unwind`, the staged values are dropped with it.
+///
+/// The writes are staged by the full name of the key, a transaction of a
+/// [`Namespace`](crate::Namespace) prefixes the keys with the namespace.
+pub struct Transaction {
+    prefix: String,
+    staged: BTreeMap<String, Staged>,
+}
+
+impl Transaction {
+    fn new(prefix: &str) -> Self {
+        Self {
+            prefix: String::from(prefix),
+            staged: BTreeMap::new(),
+        }
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    /// Stage the insert of a value.
+    pub fn insert<T: Any + Send + Sync>(&mut self, key: &StorageKey<T>, value: T) {
+        let value = Arc::new_in(value, CustomStorge);
+        self.staged.insert(
+            self.full_name(key.name()),
+            Staged::Insert(value, key.value_info()),
+        );
+    }
+
+    /// Stage the remove of a value.
+    pub fn remove<T>(&mut self, key: &StorageKey<T>) {
+        self.staged
+            .insert(self.full_name(key.name()), Staged::Remove);
+    }
+
+    /// Get a value, the writes staged in the transaction are visible.
+    pub fn get<T: Any + Send + Sync>(
+        &self,
+        key: &StorageKey<T>,
+    ) -> StorageResult<Option<Arc<T, CustomStorge>>> {
+        let name = self.full_name(key.name());
+        match self.staged.get(&name) {
+            Some(Staged::Insert(value, info)) => {
+                let entry = StorageEntry {
+                    value: value.clone(),
+                    type_hash: info.type_hash,
+                    version: info.version,
+                };
+                entry.downcast(key).map(Some)
+            }
+            Some(Staged::Remove) => Ok(None),
+            None => get_named(&name, key),
+        }
+    }
+
+    /// Return true if the key is written by the transaction.
+    pub fn is_staged(&self, name: &str) -> bool {
+        self.staged.contains_key(&self.full_name(name))
+    }
+
+    fn commit(self) {
+        let mut ops = self.staged.iter().map(|(key, staged)| match staged {
+            Staged::Insert(value, info) => WriteOp::Insert {
+                key,
+                value: value.clone(),
+                info: *info,
+            },
+            Staged::Remove => WriteOp::Remove { key },
+        });
+        // the replaced values are dropped here, after the storage is unlocked
+        let mut replaced = Vec::new();
+        database().commit(&mut ops, &mut |entry| replaced.push(entry));
+        drop(ops);
+        log::info!(
+            "commit transaction: {} writes, {} replaced",
+            self.staged.len(),
+            replaced.len()
+        );
+    }
+}
+
+/// Run `f` in a transaction.
+///
+/// The inserts and removes staged by `f` are applied to the storage at once
+/// if `f` returns `Ok`. They are discarded if `f` returns an error or panics.
+pub fn transaction<R, E, F: FnOnce(&mut Transaction) -> Result<R, E>>(f: F) -> Result<R, E> {
+    transaction_in("", f)
+}
+
+/// Run `f` in a transaction whose keys start with `prefix`.
+pub(crate) fn transaction_in<R, E, F: FnOnce(&mut Transaction) -> Result<R, E>>(
+    prefix: &str,
+    f: F,
+) -> Result<R, E> {
+    let mut tx = Transaction::new(prefix);
+    let res = f(&mut tx)?;
+    tx.commit();
+    Ok(res)
+}
@@ -28,6 +28,10 @@ impl TaskContext {
     pub fn set_sp(&mut self, sp: usize) {
         self.sp = sp;
     }
+    /// Return the address the task starts at.
+    pub fn ra(&self) -> usize {
+        self.ra
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -130,6 +134,34 @@ pub enum TaskStatus {
     Terminated,
 }
 
+/// The thread local storage template of a domain, from its `PT_TLS` segment.
+///
+/// Every task which uses thread locals gets a block of `mem_size` bytes,
+/// the first `file_size` bytes are copied from the template and the rest is
+/// zeroed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct TlsTemplate {
+    /// The address of the initialized data in the domain.
+    pub image: usize,
+    pub file_size: usize,
+    pub mem_size: usize,
+    pub align: usize,
+}
+
+impl TlsTemplate {
+    /// Initialize a block of a task.
+    ///
+    /// # Safety
+    ///
+    /// `block` must be valid for `mem_size` bytes and aligned to `align`, the
+    /// domain of the template must be loaded.
+    pub unsafe fn init_block(&self, block: *mut u8) {
+        core::ptr::copy_nonoverlapping(self.image as *const u8, block, self.file_size);
+        core::ptr::write_bytes(block.add(self.file_size), 0, self.mem_size - self.file_size);
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum TaskOperation {
     Create(TaskMeta),